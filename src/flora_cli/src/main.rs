@@ -1,10 +1,23 @@
-use clap::{Args, Parser, Subcommand};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use flora_core::{
     errors::FloraError,
+    gc::FloraGarbageKind,
     manager::{FloraManager, FloraSeedListItem},
+    process::{FloraProcess, FloraRunResult, FloraWaitMode},
+    registry::{FloraRegistryData, FloraRegistryValue},
     seed::{
-        FloraProtonSeed, FloraSeed, FloraSeedApp, FloraSeedSettings, FloraSeedType, FloraWineSeed,
+        FloraDllOverrideMode, FloraGamescopeFilter, FloraGamescopeScaler, FloraGamescopeSettings,
+        FloraGamescopeWindowMode, FloraPrefixArch, FloraProtonSeed, FloraSeed, FloraSeedApp,
+        FloraSeedSettings, FloraSeedType, FloraWindowsVersion, FloraWineOptions, FloraWineSeed,
     },
+    snapshot::FloraSnapshot,
     start_menu::FloraSeedStartMenuItem,
 };
 use tabled::{
@@ -38,6 +51,18 @@ pub enum Commands {
     Tricks(RunOpts),
     /// Run an application in a seed
     Run(RunOpts),
+    /// List processes running in seeds
+    Ps(PsOpts),
+    /// Terminate all processes running in a seed
+    Kill(KillOpts),
+    /// Edit the registry of a seed's prefix
+    Reg(RegOpts),
+    /// Remove desktop entries, icons, prefixes and logs left behind by deleted seeds and apps
+    Gc(GcOpts),
+    /// Open Windows executables, installers and shortcuts with seeds from the file manager
+    Mime(MimeOpts),
+    /// Open a Windows executable, installer or shortcut with the seed set for it in flora.toml
+    Open(OpenOpts),
 }
 
 #[derive(Args)]
@@ -56,10 +81,24 @@ pub enum SeedCommands {
     Set(SetOpts),
     /// Remove a seed
     Delete(DeleteOpts),
+    /// Copy a seed and its prefix under a new name
+    Clone(CloneOpts),
+    /// Rename a seed, along with its icons, desktop entries, log and snapshots
+    Rename(RenameOpts),
+    /// Bundle a seed, its prefix and its icons into an archive
+    Export(ExportOpts),
+    /// Create a seed from an archive made by seed export
+    Import(ImportOpts),
     /// Show a seed's information
     Info(InfoOpts),
+    /// Create or update a seed's prefix, so it is ready to use
+    Init(InitOpts),
     /// Manage a seed's environment
     Env(EnvOpts),
+    /// Manage a seed's DLL overrides
+    DllOverride(DllOverrideOpts),
+    /// Manage snapshots of a seed's prefix
+    Snapshot(SnapshotOpts),
 }
 
 #[derive(Args)]
@@ -83,6 +122,18 @@ pub struct CreateSeedOpts {
     /// Launcher command for applications
     #[arg(long, required = false)]
     launcher: Option<String>,
+    /// Wrapper command for applications, outermost first. Can be given multiple times.
+    #[arg(long = "wrapper", allow_hyphen_values = true)]
+    wrappers: Vec<String>,
+    /// Wrapper preset from flora.toml, applied outside the seed's wrappers
+    #[arg(long)]
+    wrapper_preset: Option<String>,
+    /// Windows version of the prefix, e.g. win7 or win10
+    #[arg(long)]
+    windows_version: Option<FloraWindowsVersion>,
+    /// Create the seed's prefix before returning
+    #[arg(long)]
+    init: bool,
 }
 
 #[derive(Args)]
@@ -111,6 +162,9 @@ pub struct CreateWineOpts {
     /// Wine runtime for the seed
     #[arg(short = 'r', long)]
     wine_runtime: Option<String>,
+    /// Architecture of the prefix when it is created
+    #[arg(long)]
+    prefix_arch: Option<FloraPrefixArch>,
 }
 #[derive(Args)]
 pub struct CreateProtonOpts {
@@ -152,6 +206,133 @@ pub enum SetCommands {
 pub struct SetSeedOpts {
     /// Name of seed
     name: String,
+    /// Launcher command for applications. An empty string clears it.
+    #[arg(long)]
+    launcher: Option<String>,
+    /// Wrapper command for applications, outermost first. Replaces the current wrappers.
+    #[arg(long = "wrapper", allow_hyphen_values = true)]
+    wrappers: Vec<String>,
+    /// Remove all wrappers from the seed
+    #[arg(long, conflicts_with = "wrappers")]
+    clear_wrappers: bool,
+    /// Wrapper preset from flora.toml. An empty string clears it.
+    #[arg(long)]
+    wrapper_preset: Option<String>,
+    /// Windows version of the prefix, e.g. win7 or win10
+    #[arg(long)]
+    windows_version: Option<FloraWindowsVersion>,
+    /// Stop managing the prefix's Windows version, keeping the current one
+    #[arg(long, conflicts_with = "windows_version")]
+    clear_windows_version: bool,
+
+    #[command(flatten)]
+    gamescope: SetGamescopeOpts,
+
+    #[command(flatten)]
+    options: SetWineOptionsOpts,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "Wine options")]
+pub struct SetWineOptionsOpts {
+    /// Enable or disable esync
+    #[arg(long)]
+    esync: Option<bool>,
+    /// Enable or disable fsync
+    #[arg(long)]
+    fsync: Option<bool>,
+    /// Enable or disable ntsync
+    #[arg(long)]
+    ntsync: Option<bool>,
+    /// Wine debug channels, e.g. -all. An empty string clears them.
+    #[arg(long, allow_hyphen_values = true)]
+    debug_channels: Option<String>,
+    /// Elements shown by the DXVK HUD, e.g. fps. An empty string clears them.
+    #[arg(long)]
+    dxvk_hud: Option<String>,
+    /// Enable or disable asynchronous DXVK shader compilation
+    #[arg(long)]
+    dxvk_async: Option<bool>,
+    /// Enable or disable asynchronous VKD3D-Proton pipeline compilation
+    #[arg(long)]
+    vkd3d_async: Option<bool>,
+    /// Enable or disable the Wayland driver (Proton only)
+    #[arg(long)]
+    wayland: Option<bool>,
+    /// Enable or disable Proton logging (Proton only)
+    #[arg(long)]
+    proton_log: Option<bool>,
+    /// Enable or disable large address awareness for 32-bit executables
+    #[arg(long)]
+    large_address_aware: Option<bool>,
+    /// Remove all Wine options from the seed
+    #[arg(long, conflicts_with_all = [
+        "esync",
+        "fsync",
+        "ntsync",
+        "debug_channels",
+        "dxvk_hud",
+        "dxvk_async",
+        "vkd3d_async",
+        "wayland",
+        "proton_log",
+        "large_address_aware",
+    ])]
+    reset_options: bool,
+}
+
+#[derive(Args)]
+#[command(next_help_heading = "Gamescope")]
+pub struct SetGamescopeOpts {
+    /// Output window resolution, in the form WIDTHxHEIGHT
+    #[arg(long, value_parser = parse_resolution)]
+    gamescope_output: Option<(u32, u32)>,
+    /// Resolution the game renders at, in the form WIDTHxHEIGHT
+    #[arg(long, value_parser = parse_resolution)]
+    gamescope_nested: Option<(u32, u32)>,
+    /// Frame rate limit of the output
+    #[arg(long)]
+    gamescope_refresh: Option<u32>,
+    /// Window mode: windowed, fullscreen or borderless
+    #[arg(long)]
+    gamescope_window_mode: Option<FloraGamescopeWindowMode>,
+    /// Upscaler filter: linear, nearest, fsr, nis or pixel
+    #[arg(long)]
+    gamescope_filter: Option<FloraGamescopeFilter>,
+    /// Upscaler type: auto, integer, fit, fill or stretch
+    #[arg(long)]
+    gamescope_scaler: Option<FloraGamescopeScaler>,
+    /// Enable HDR output
+    #[arg(long)]
+    gamescope_hdr: Option<bool>,
+    /// Additional gamescope argument. Replaces the current additional arguments.
+    #[arg(long = "gamescope-arg", allow_hyphen_values = true)]
+    gamescope_args: Vec<String>,
+    /// Stop running the seed inside gamescope
+    #[arg(long, conflicts_with_all = [
+        "gamescope_output",
+        "gamescope_nested",
+        "gamescope_refresh",
+        "gamescope_window_mode",
+        "gamescope_filter",
+        "gamescope_scaler",
+        "gamescope_hdr",
+        "gamescope_args",
+    ])]
+    no_gamescope: bool,
+}
+
+impl SetGamescopeOpts {
+    fn is_empty(&self) -> bool {
+        self.gamescope_output.is_none()
+            && self.gamescope_nested.is_none()
+            && self.gamescope_refresh.is_none()
+            && self.gamescope_window_mode.is_none()
+            && self.gamescope_filter.is_none()
+            && self.gamescope_scaler.is_none()
+            && self.gamescope_hdr.is_none()
+            && self.gamescope_args.is_empty()
+    }
 }
 #[derive(Args)]
 pub struct SetWineOpts {
@@ -164,6 +345,9 @@ pub struct SetWineOpts {
     /// Wine runtime for the seed
     #[arg(short = 'r', long)]
     wine_runtime: Option<String>,
+    /// Architecture of the prefix when it is created
+    #[arg(long)]
+    prefix_arch: Option<FloraPrefixArch>,
 }
 
 #[derive(Args)]
@@ -189,6 +373,15 @@ pub struct SetProtonOpts {
 pub struct DeleteOpts {
     /// Name of seed
     name: String,
+    /// Also remove the seed's desktop entries, icons and log
+    #[arg(long)]
+    purge: bool,
+    /// Also remove the seed's prefix, unless another seed uses it
+    #[arg(long, requires = "purge")]
+    delete_prefix: bool,
+    /// Do not ask for confirmation before removing the prefix
+    #[arg(short = 'y', long)]
+    yes: bool,
 }
 
 #[derive(Args)]
@@ -197,11 +390,63 @@ pub struct ListOpts {
     /// Long format of list
     long: bool,
 }
+#[derive(Args)]
+pub struct CloneOpts {
+    /// Name of seed to copy
+    name: String,
+    /// Name of the new seed
+    new_name: String,
+    /// Prefix for the new seed. Defaults to a prefix next to the original, named after the new seed.
+    #[arg(short = 'p', long)]
+    prefix: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RenameOpts {
+    /// Name of seed to rename
+    name: String,
+    /// New name of the seed
+    new_name: String,
+    /// Also rename the seed's prefix, if the prefix is named after the seed
+    #[arg(short = 'p', long)]
+    rename_prefix: bool,
+}
+
+#[derive(Args)]
+pub struct ExportOpts {
+    /// Name of seed
+    name: String,
+    /// Archive to write. Defaults to <seed>.tar.zst in the current directory.
+    #[arg(short = 'o', long)]
+    output: Option<PathBuf>,
+    /// Leave out temporary files and shader caches from the prefix
+    #[arg(long)]
+    exclude_caches: bool,
+}
+
+#[derive(Args)]
+pub struct ImportOpts {
+    /// Archive made by seed export
+    archive: PathBuf,
+    /// Name of the new seed. Defaults to the name of the exported seed.
+    #[arg(short = 'n', long)]
+    name: Option<String>,
+}
+
 #[derive(Args)]
 pub struct InfoOpts {
     /// Name of seed
     name: String,
 }
+
+#[derive(Args)]
+pub struct InitOpts {
+    /// Name of seed
+    name: String,
+    /// Redirect wineboot output to flora logs
+    #[arg(short, long)]
+    quiet: bool,
+}
 #[derive(Args)]
 pub struct EnvOpts {
     #[command(subcommand)]
@@ -248,6 +493,190 @@ pub struct SeedEnvDeleteOpts {
     env_name: String,
 }
 
+#[derive(Args)]
+pub struct DllOverrideOpts {
+    #[command(subcommand)]
+    commands: SeedDllOverrideCommands,
+}
+
+#[derive(Subcommand)]
+pub enum SeedDllOverrideCommands {
+    /// List DLL overrides in a seed
+    List(SeedDllOverrideListOpts),
+    /// Set a DLL override in a seed
+    Set(SeedDllOverrideSetOpts),
+    /// Delete a DLL override from a seed
+    Delete(SeedDllOverrideDeleteOpts),
+}
+
+#[derive(Args)]
+pub struct SnapshotOpts {
+    #[command(subcommand)]
+    commands: SeedSnapshotCommands,
+}
+
+#[derive(Subcommand)]
+pub enum SeedSnapshotCommands {
+    /// Take a snapshot of a seed's prefix
+    Create(SeedSnapshotCreateOpts),
+    /// List snapshots of a seed
+    List(AppSeedOpts),
+    /// Replace a seed's prefix with a snapshot
+    Restore(SeedSnapshotIdOpts),
+    /// Delete a snapshot of a seed
+    Delete(SeedSnapshotIdOpts),
+}
+
+#[derive(Args)]
+pub struct SeedSnapshotCreateOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// Label describing the snapshot, e.g. "before dotnet48"
+    #[arg(short = 'l', long)]
+    label: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SeedSnapshotIdOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// ID of the snapshot, as shown by snapshot list
+    id: u32,
+}
+
+#[derive(Args)]
+pub struct SeedDllOverrideListOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// List the DLL overrides of an app instead, merged over the seed's
+    #[arg(short = 'a', long)]
+    app: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SeedDllOverrideSetOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// Name of DLL, with or without the .dll extension
+    dll_name: String,
+    /// How the DLL is loaded: native, builtin, native,builtin, builtin,native or disabled
+    mode: FloraDllOverrideMode,
+    /// Set the DLL override on an app instead
+    #[arg(short = 'a', long)]
+    app: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SeedDllOverrideDeleteOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// Name of DLL, with or without the .dll extension
+    dll_name: String,
+    /// Delete the DLL override from an app instead
+    #[arg(short = 'a', long)]
+    app: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RegOpts {
+    #[command(subcommand)]
+    commands: RegCommands,
+}
+
+#[derive(Subcommand)]
+pub enum RegCommands {
+    /// Show the values of a registry key, or a single value
+    Get(RegGetOpts),
+    /// Set a registry value, creating its key if needed
+    Set(RegSetOpts),
+    /// Delete a registry value, or a key with all of its subkeys
+    Delete(RegDeleteOpts),
+    /// Export a registry key and its subkeys to a .reg file
+    Export(RegExportOpts),
+    /// Import a .reg file into the seed's registry
+    Import(RegImportOpts),
+}
+
+#[derive(Args)]
+pub struct RegGetOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// Registry key, e.g. HKCU\Software\Wine
+    key: String,
+    /// Name of value, or @ for the default value
+    value: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum RegValueType {
+    /// REG_SZ
+    Sz,
+    /// REG_EXPAND_SZ
+    ExpandSz,
+    /// REG_MULTI_SZ, one string per data argument
+    MultiSz,
+    /// REG_DWORD, in decimal or 0x-prefixed hexadecimal
+    Dword,
+    /// REG_QWORD, in decimal or 0x-prefixed hexadecimal
+    Qword,
+    /// REG_BINARY, as comma separated hexadecimal bytes
+    Binary,
+}
+
+#[derive(Args)]
+pub struct RegSetOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// Registry key, e.g. HKCU\Software\Wine
+    key: String,
+    /// Name of value, or @ for the default value
+    value: String,
+    /// Data of value
+    data: Vec<String>,
+    /// Type of value
+    #[arg(short = 't', long = "type", value_enum, default_value = "sz")]
+    value_type: RegValueType,
+}
+
+#[derive(Args)]
+pub struct RegDeleteOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// Registry key, e.g. HKCU\Software\Wine
+    key: String,
+    /// Name of value, or @ for the default value. Deletes the whole key when omitted.
+    value: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RegExportOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// Registry key, e.g. HKCU\Software\Wine
+    key: String,
+    /// File to write to, instead of standard output
+    #[arg(short = 'o', long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct RegImportOpts {
+    #[clap(flatten)]
+    seed: AppSeedOpts,
+
+    /// .reg file to import, or - for standard input
+    file: PathBuf,
+}
+
 #[derive(Args)]
 pub struct AppOpts {
     #[command(subcommand)]
@@ -297,6 +726,24 @@ pub struct AppAddOpts {
     /// App category
     #[arg(short = 'c', long)]
     app_category: Option<String>,
+    /// Arguments passed to the app, split using shell quoting rules
+    #[arg(long, allow_hyphen_values = true)]
+    arguments: Option<String>,
+    /// Directory the app is started in, either a Windows or a Unix path
+    #[arg(short = 'd', long)]
+    working_directory: Option<String>,
+    /// Environment variable for the app, in the form NAME=VALUE
+    #[arg(short = 'e', long = "env", value_parser = parse_env_pair)]
+    env: Vec<(String, String)>,
+    /// Wrapper command replacing the seed's wrappers, outermost first. Can be given multiple times.
+    #[arg(long = "wrapper", allow_hyphen_values = true)]
+    wrappers: Vec<String>,
+    /// Wrapper preset from flora.toml, replacing the seed's wrappers
+    #[arg(long)]
+    wrapper_preset: Option<String>,
+    /// Windows version reported to the app, overriding the seed's
+    #[arg(long)]
+    windows_version: Option<FloraWindowsVersion>,
 }
 
 #[derive(Args)]
@@ -312,6 +759,33 @@ pub struct AppUpdateOpts {
     /// App category
     #[arg(short = 'c', long)]
     app_category: Option<String>,
+    /// Arguments passed to the app, split using shell quoting rules. An empty string clears them.
+    #[arg(long, allow_hyphen_values = true)]
+    arguments: Option<String>,
+    /// Directory the app is started in, either a Windows or a Unix path. An empty string clears it.
+    #[arg(short = 'd', long)]
+    working_directory: Option<String>,
+    /// Environment variable for the app, in the form NAME=VALUE
+    #[arg(short = 'e', long = "env", value_parser = parse_env_pair)]
+    env: Vec<(String, String)>,
+    /// Remove an environment variable from the app
+    #[arg(long)]
+    unset_env: Vec<String>,
+    /// Wrapper command replacing the seed's wrappers, outermost first. Replaces the app's current wrappers.
+    #[arg(long = "wrapper", allow_hyphen_values = true)]
+    wrappers: Vec<String>,
+    /// Wrapper preset from flora.toml, replacing the seed's wrappers. An empty string clears it.
+    #[arg(long)]
+    wrapper_preset: Option<String>,
+    /// Remove the app's wrappers and wrapper preset, using the seed's wrappers again
+    #[arg(long, conflicts_with_all = ["wrappers", "wrapper_preset"])]
+    clear_wrappers: bool,
+    /// Windows version reported to the app, overriding the seed's
+    #[arg(long)]
+    windows_version: Option<FloraWindowsVersion>,
+    /// Remove the app's Windows version, using the seed's again
+    #[arg(long, conflicts_with = "windows_version")]
+    clear_windows_version: bool,
 }
 #[derive(Args)]
 pub struct AppRenameOpts {
@@ -339,6 +813,10 @@ pub struct AppGenerateMenuOpts {
 
     /// Name for the app
     app_name: Option<String>,
+
+    /// Also remove desktop entries and icons of apps that no longer exist
+    #[arg(long, conflicts_with = "app_name")]
+    sync: bool,
 }
 #[derive(Args)]
 pub struct StartMenuOpts {
@@ -390,6 +868,72 @@ pub struct RunOpts {
     /// Wait until the app exits
     #[arg(short, long)]
     wait: bool,
+    /// Wait until the app and every other process in the seed's prefix exit
+    #[arg(short = 'W', long)]
+    wait_prefix: bool,
+}
+
+impl RunOpts {
+    fn wait_mode(&self) -> FloraWaitMode {
+        if self.wait_prefix {
+            FloraWaitMode::Prefix
+        } else if self.wait {
+            FloraWaitMode::Process
+        } else {
+            FloraWaitMode::NoWait
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct PsOpts {
+    /// Long format
+    #[arg(short = 'l', long)]
+    long: bool,
+}
+
+#[derive(Args)]
+pub struct KillOpts {
+    /// Name of seed
+    name: String,
+}
+
+#[derive(Args)]
+pub struct GcOpts {
+    /// Only show what would be removed
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+    /// Also remove unreferenced prefixes, which are otherwise only shown
+    #[arg(long)]
+    prefixes: bool,
+    /// Do not ask for confirmation before removing prefixes
+    #[arg(short = 'y', long)]
+    yes: bool,
+}
+
+#[derive(Args)]
+pub struct MimeOpts {
+    #[command(subcommand)]
+    commands: MimeCommands,
+}
+
+#[derive(Subcommand)]
+pub enum MimeCommands {
+    /// Make a seed the default application for Windows executables, installers and shortcuts
+    Register(AppSeedOpts),
+}
+
+#[derive(Args)]
+pub struct OpenOpts {
+    /// File to open
+    file: PathBuf,
+
+    /// Redirect program output to flora logs
+    #[arg(short, long)]
+    quiet: bool,
+    /// Wait until the program exits
+    #[arg(short, long)]
+    wait: bool,
 }
 
 #[derive(Tabled)]
@@ -404,9 +948,16 @@ pub struct SeedTableRow<'a> {
 pub struct SeedTableInfo<'a> {
     pub name: &'a str,
     pub prefix: &'a str,
+    pub prefix_arch: String,
     pub runtime: &'a str,
     pub game_id: &'a str,
     pub store: &'a str,
+    pub launcher: &'a str,
+    pub wrapper_preset: &'a str,
+    pub wrappers: String,
+    pub gamescope: String,
+    pub options: String,
+    pub windows_version: String,
 }
 
 #[derive(Tabled)]
@@ -415,6 +966,10 @@ pub struct SeedAppTableRow<'a> {
     pub application_name: &'a str,
     pub application_location: &'a str,
     pub category: &'a str,
+    pub arguments: String,
+    pub working_directory: &'a str,
+    pub windows_version: String,
+    pub shortcut: &'a str,
 }
 
 #[derive(Tabled)]
@@ -424,6 +979,43 @@ pub struct SeedStartMenuTableRow<'a> {
     pub location: &'a str,
 }
 
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct ProcessTableRow<'a> {
+    pub pid: u32,
+    pub seed: &'a str,
+    pub app: &'a str,
+    pub started: String,
+    pub command: &'a str,
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct GarbageTableRow {
+    pub kind: String,
+    pub path: String,
+    pub size: String,
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct SnapshotTableRow<'a> {
+    pub id: u32,
+    pub label: &'a str,
+    pub created: String,
+    pub files: usize,
+    pub size: String,
+}
+
+#[derive(Tabled)]
+#[tabled(rename_all = "Upper Title Case")]
+pub struct RegValueTableRow<'a> {
+    pub name: &'a str,
+    #[tabled(rename = "Type")]
+    pub value_type: String,
+    pub data: String,
+}
+
 #[derive(Tabled)]
 #[tabled(rename_all = "Upper Title Case")]
 pub struct SeedEnvTableRow<'a> {
@@ -442,13 +1034,16 @@ impl<'a> From<&'a FloraSeedListItem> for SeedTableRow<'a> {
 
 impl<'a> From<(&'a str, &'a FloraSeed)> for SeedTableInfo<'a> {
     fn from(item: (&'a str, &'a FloraSeed)) -> Self {
-        match &item.1.seed_type {
+        let seed_info = match &item.1.seed_type {
             FloraSeedType::Wine(conf) => Self {
                 name: item.0,
                 prefix: conf.wine_prefix.as_deref().unwrap_or_default(),
+                prefix_arch: conf
+                    .prefix_arch
+                    .map(|prefix_arch| prefix_arch.to_string())
+                    .unwrap_or_default(),
                 runtime: conf.wine_runtime.as_deref().unwrap_or_default(),
-                game_id: "",
-                store: "",
+                ..Default::default()
             },
             FloraSeedType::Proton(conf) => Self {
                 name: item.0,
@@ -456,8 +1051,33 @@ impl<'a> From<(&'a str, &'a FloraSeed)> for SeedTableInfo<'a> {
                 runtime: conf.proton_runtime.as_deref().unwrap_or_default(),
                 game_id: conf.game_id.as_deref().unwrap_or_default(),
                 store: conf.store.as_deref().unwrap_or_default(),
+                ..Default::default()
             },
             FloraSeedType::None => Self::default(),
+        };
+
+        match &item.1.settings {
+            Some(settings) => Self {
+                launcher: settings.launcher_command.as_deref().unwrap_or_default(),
+                wrapper_preset: settings.wrapper_preset.as_deref().unwrap_or_default(),
+                wrappers: settings
+                    .wrappers
+                    .as_ref()
+                    .map(|wrappers| wrappers.join(" | "))
+                    .unwrap_or_default(),
+                gamescope: settings
+                    .gamescope
+                    .as_ref()
+                    .map(format_gamescope)
+                    .unwrap_or_default(),
+                options: settings
+                    .options
+                    .as_ref()
+                    .map(format_wine_options)
+                    .unwrap_or_default(),
+                ..seed_info
+            },
+            None => seed_info,
         }
     }
 }
@@ -468,6 +1088,21 @@ impl<'a> From<&'a FloraSeedApp> for SeedAppTableRow<'a> {
             application_name: item.application_name.as_str(),
             application_location: item.application_location.as_str(),
             category: item.category.as_deref().unwrap_or("Other"),
+            arguments: item
+                .arguments
+                .as_ref()
+                .and_then(|arguments| shlex::try_join(arguments.iter().map(String::as_str)).ok())
+                .unwrap_or_default(),
+            working_directory: item.working_directory.as_deref().unwrap_or_default(),
+            windows_version: item
+                .windows_version
+                .map(|version| version.to_string())
+                .unwrap_or_default(),
+            shortcut: item
+                .shortcut
+                .as_ref()
+                .map(|shortcut| shortcut.location.as_str())
+                .unwrap_or_default(),
         }
     }
 }
@@ -480,6 +1115,28 @@ impl<'a> From<&'a FloraSeedStartMenuItem> for SeedStartMenuTableRow<'a> {
     }
 }
 
+impl<'a> From<&'a FloraProcess> for ProcessTableRow<'a> {
+    fn from(item: &'a FloraProcess) -> Self {
+        Self {
+            pid: item.pid,
+            seed: item.seed_name.as_str(),
+            app: item.app_name.as_deref().unwrap_or_default(),
+            started: format_elapsed(item.started_at),
+            command: item.command.as_str(),
+        }
+    }
+}
+
+impl<'a> From<&'a FloraRegistryValue> for RegValueTableRow<'a> {
+    fn from(item: &'a FloraRegistryValue) -> Self {
+        Self {
+            name: item.name.as_deref().unwrap_or("(Default)"),
+            value_type: item.data.type_name(),
+            data: item.data.to_string(),
+        }
+    }
+}
+
 impl<'a> From<(&'a String, &'a String)> for SeedEnvTableRow<'a> {
     fn from(item: (&'a String, &'a String)) -> Self {
         Self {
@@ -489,27 +1146,369 @@ impl<'a> From<(&'a String, &'a String)> for SeedEnvTableRow<'a> {
     }
 }
 
+impl<'a> From<&'a FloraSnapshot> for SnapshotTableRow<'a> {
+    fn from(item: &'a FloraSnapshot) -> Self {
+        Self {
+            id: item.id,
+            label: item.label.as_deref().unwrap_or_default(),
+            created: format_elapsed(item.created_at),
+            files: item.file_count(),
+            size: format_size(item.size),
+        }
+    }
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+
+    format!("{:.1} {}", size, unit)
+}
+
+fn format_elapsed(started_at: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let elapsed = now.saturating_sub(started_at);
+
+    match elapsed {
+        0..60 => format!("{}s ago", elapsed),
+        60..3600 => format!("{}m {}s ago", elapsed / 60, elapsed % 60),
+        3600..86400 => format!("{}h {}m ago", elapsed / 3600, (elapsed % 3600) / 60),
+        _ => format!("{}d {}h ago", elapsed / 86400, (elapsed % 86400) / 3600),
+    }
+}
+
+/// Asks a yes or no question, defaulting to no
+fn confirm(question: &str) -> Result<bool, FloraError> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Asks which seed to open a file with, returning `None` if no seed is chosen
+fn choose_seed<'a>(
+    seeds: &'a [FloraSeedListItem],
+    file: &Path,
+) -> Result<Option<&'a str>, FloraError> {
+    println!("Choose a seed to open {} with:", file.display());
+    for (index, seed) in seeds.iter().enumerate() {
+        println!("  {}) {} ({})", index + 1, seed.seed_name, seed.seed_type);
+    }
+    print!("Seed [1-{}]: ", seeds.len());
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(answer
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| seeds.get(index.checked_sub(1)?))
+        .map(|seed| seed.seed_name.as_str()))
+}
+
+/// Exits with the status of a waited process, mirroring the shell's convention for signals
+fn exit_with_run_result(result: FloraRunResult) -> Result<(), FloraError> {
+    if let Some(duration) = result.duration {
+        log::info!("Process {} ran for {:?}", result.pid, duration);
+    }
+
+    if let Some(signal) = result.signal {
+        std::process::exit(128 + signal);
+    }
+    match result.exit_code {
+        Some(0) | None => Ok(()),
+        Some(exit_code) => std::process::exit(exit_code),
+    }
+}
+
+fn parse_env_pair(env: &str) -> Result<(String, String), String> {
+    env.split_once('=')
+        .map(|(name, value)| (String::from(name), String::from(value)))
+        .ok_or(format!(
+            "Invalid environment variable {}, expected NAME=VALUE",
+            env
+        ))
+}
+
+fn parse_resolution(resolution: &str) -> Result<(u32, u32), String> {
+    resolution
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or(format!(
+            "Invalid resolution {}, expected WIDTHxHEIGHT",
+            resolution
+        ))
+}
+
+fn format_gamescope(gamescope: &FloraGamescopeSettings) -> String {
+    let mut options = Vec::new();
+
+    if let (Some(width), Some(height)) = (gamescope.output_width, gamescope.output_height) {
+        options.push(format!("output {}x{}", width, height));
+    }
+    if let (Some(width), Some(height)) = (gamescope.nested_width, gamescope.nested_height) {
+        options.push(format!("nested {}x{}", width, height));
+    }
+    if let Some(refresh_rate) = gamescope.refresh_rate {
+        options.push(format!("{} Hz", refresh_rate));
+    }
+    if let Some(window_mode) = gamescope.window_mode {
+        options.push(window_mode.to_string());
+    }
+    if let Some(filter) = gamescope.filter {
+        options.push(format!("filter {}", filter));
+    }
+    if let Some(scaler) = gamescope.scaler {
+        options.push(format!("scaler {}", scaler));
+    }
+    if gamescope.hdr {
+        options.push(String::from("HDR"));
+    }
+    if let Some(extra_args) = &gamescope.extra_args {
+        options.push(extra_args.join(" "));
+    }
+
+    if options.is_empty() {
+        String::from("enabled")
+    } else {
+        options.join(", ")
+    }
+}
+
+fn format_wine_options(options: &FloraWineOptions) -> String {
+    let toggles = [
+        ("esync", options.esync),
+        ("fsync", options.fsync),
+        ("ntsync", options.ntsync),
+        ("DXVK async", options.dxvk_async),
+        ("VKD3D async", options.vkd3d_async),
+        ("Wayland", options.wayland),
+        ("Proton log", options.proton_log),
+        ("large address aware", options.large_address_aware),
+    ];
+    let mut formatted_options: Vec<_> = toggles
+        .iter()
+        .filter_map(|(name, value)| {
+            value.map(|value| format!("{} {}", name, if value { "on" } else { "off" }))
+        })
+        .collect();
+
+    if let Some(debug_channels) = &options.debug_channels {
+        formatted_options.push(format!("debug channels {}", debug_channels));
+    }
+    if let Some(dxvk_hud) = &options.dxvk_hud {
+        formatted_options.push(format!("DXVK HUD {}", dxvk_hud));
+    }
+
+    formatted_options.join(", ")
+}
+
+fn parse_reg_value_name(value_name: &str) -> Option<&str> {
+    if value_name == "@" {
+        None
+    } else {
+        Some(value_name)
+    }
+}
+
+fn parse_reg_number(number: &str) -> Result<u64, FloraError> {
+    let parsed = match number.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => number.parse(),
+    };
+
+    parsed.map_err(|_| FloraError::IncorrectArguments(String::from(number)))
+}
+
+fn parse_reg_data(
+    value_type: RegValueType,
+    data: &[String],
+) -> Result<FloraRegistryData, FloraError> {
+    if let RegValueType::MultiSz = value_type {
+        return Ok(FloraRegistryData::MultiString(data.to_vec()));
+    }
+
+    let [data] = data else {
+        return Err(FloraError::IncorrectArguments(data.join(" ")));
+    };
+    let registry_data = match value_type {
+        RegValueType::Sz => FloraRegistryData::String(data.clone()),
+        RegValueType::ExpandSz => FloraRegistryData::ExpandString(data.clone()),
+        RegValueType::Dword => FloraRegistryData::Dword(
+            parse_reg_number(data)?
+                .try_into()
+                .map_err(|_| FloraError::IncorrectArguments(data.clone()))?,
+        ),
+        RegValueType::Qword => FloraRegistryData::Qword(parse_reg_number(data)?),
+        RegValueType::Binary => FloraRegistryData::Binary(
+            data.split(',')
+                .filter(|byte| !byte.is_empty())
+                .map(|byte| u8::from_str_radix(byte.trim(), 16))
+                .collect::<Result<_, _>>()
+                .map_err(|_| FloraError::IncorrectArguments(data.clone()))?,
+        ),
+        RegValueType::MultiSz => unreachable!(),
+    };
+
+    Ok(registry_data)
+}
+
+fn parse_arguments(arguments: &str) -> Result<Option<Vec<String>>, FloraError> {
+    let arguments =
+        shlex::split(arguments).ok_or(FloraError::IncorrectArguments(String::from(arguments)))?;
+
+    Ok(if arguments.is_empty() {
+        None
+    } else {
+        Some(arguments)
+    })
+}
+
+fn create_seed_settings(args: &CreateSeedOpts) -> Option<Box<FloraSeedSettings>> {
+    if args.launcher.is_none() && args.wrappers.is_empty() && args.wrapper_preset.is_none() {
+        return None;
+    }
+
+    Some(Box::from(FloraSeedSettings {
+        launcher_command: args.launcher.clone(),
+        wrapper_preset: args.wrapper_preset.clone(),
+        wrappers: (!args.wrappers.is_empty()).then(|| args.wrappers.clone()),
+        ..Default::default()
+    }))
+}
+
+fn set_seed_settings(seed: &mut FloraSeed, args: &SetSeedOpts) {
+    let settings = seed.settings.get_or_insert_default();
+
+    if let Some(launcher) = args.launcher.clone() {
+        settings.launcher_command = (!launcher.is_empty()).then_some(launcher);
+    }
+    if !args.wrappers.is_empty() {
+        settings.wrappers = Some(args.wrappers.clone());
+    }
+    if args.clear_wrappers {
+        settings.wrappers = None;
+    }
+    if let Some(wrapper_preset) = args.wrapper_preset.clone() {
+        settings.wrapper_preset = (!wrapper_preset.is_empty()).then_some(wrapper_preset);
+    }
+
+    if args.gamescope.no_gamescope {
+        settings.gamescope = None;
+    } else if !args.gamescope.is_empty() {
+        let gamescope_args = &args.gamescope;
+        let gamescope = settings.gamescope.get_or_insert_default();
+
+        if let Some((width, height)) = gamescope_args.gamescope_output {
+            gamescope.output_width = Some(width);
+            gamescope.output_height = Some(height);
+        }
+        if let Some((width, height)) = gamescope_args.gamescope_nested {
+            gamescope.nested_width = Some(width);
+            gamescope.nested_height = Some(height);
+        }
+        if gamescope_args.gamescope_refresh.is_some() {
+            gamescope.refresh_rate = gamescope_args.gamescope_refresh;
+        }
+        if gamescope_args.gamescope_window_mode.is_some() {
+            gamescope.window_mode = gamescope_args.gamescope_window_mode;
+        }
+        if gamescope_args.gamescope_filter.is_some() {
+            gamescope.filter = gamescope_args.gamescope_filter;
+        }
+        if gamescope_args.gamescope_scaler.is_some() {
+            gamescope.scaler = gamescope_args.gamescope_scaler;
+        }
+        if let Some(hdr) = gamescope_args.gamescope_hdr {
+            gamescope.hdr = hdr;
+        }
+        if !gamescope_args.gamescope_args.is_empty() {
+            gamescope.extra_args = Some(gamescope_args.gamescope_args.clone());
+        }
+    }
+
+    if args.options.reset_options {
+        settings.options = None;
+    } else {
+        let options_args = &args.options;
+        let options = settings.options.get_or_insert_default();
+
+        let toggles = [
+            (&mut options.esync, options_args.esync),
+            (&mut options.fsync, options_args.fsync),
+            (&mut options.ntsync, options_args.ntsync),
+            (&mut options.dxvk_async, options_args.dxvk_async),
+            (&mut options.vkd3d_async, options_args.vkd3d_async),
+            (&mut options.wayland, options_args.wayland),
+            (&mut options.proton_log, options_args.proton_log),
+            (
+                &mut options.large_address_aware,
+                options_args.large_address_aware,
+            ),
+        ];
+        for (option, value) in toggles {
+            if value.is_some() {
+                *option = value;
+            }
+        }
+        if let Some(debug_channels) = options_args.debug_channels.clone() {
+            options.debug_channels = (!debug_channels.is_empty()).then_some(debug_channels);
+        }
+        if let Some(dxvk_hud) = options_args.dxvk_hud.clone() {
+            options.dxvk_hud = (!dxvk_hud.is_empty()).then_some(dxvk_hud);
+        }
+
+        if *options == FloraWineOptions::default() {
+            settings.options = None;
+        }
+    }
+}
+
 fn create_wine_seed(manager: &FloraManager, args: &CreateWineOpts) -> Result<(), FloraError> {
     let mut seed = FloraSeed::default();
     seed.seed_type = FloraSeedType::Wine(FloraWineSeed {
         wine_prefix: args.wine_prefix.clone(),
         wine_runtime: args.wine_runtime.clone(),
+        windows_version: args.seed.windows_version,
+        prefix_arch: args.prefix_arch,
     });
-    if let Some(launcher_command) = args.seed.launcher.clone() {
-        seed.settings = Some(Box::from(FloraSeedSettings {
-            launcher_command: Some(launcher_command),
-        }))
-    }
+    seed.settings = create_seed_settings(&args.seed);
     if let Some(default_application) = &args.default_opts {
         let app = FloraSeedApp {
             application_name: default_application.app_name.clone(),
             application_location: default_application.app_location.clone(),
             category: default_application.app_category.clone(),
+            ..Default::default()
         };
         seed.add_app(app)?;
     }
 
-    manager.create_seed(&args.seed.name, &seed)
+    manager.create_seed(&args.seed.name, &seed)?;
+    if args.seed.init {
+        init_seed(manager, &args.seed.name, false)?;
+    }
+
+    Ok(())
 }
 
 fn create_proton_seed(manager: &FloraManager, args: &CreateProtonOpts) -> Result<(), FloraError> {
@@ -519,23 +1518,34 @@ fn create_proton_seed(manager: &FloraManager, args: &CreateProtonOpts) -> Result
         proton_runtime: args.proton_runtime.clone(),
         game_id: args.game_id.clone(),
         store: args.store.clone(),
+        windows_version: args.seed.windows_version,
     });
 
-    if let Some(launcher_command) = args.seed.launcher.clone() {
-        seed.settings = Some(Box::from(FloraSeedSettings {
-            launcher_command: Some(launcher_command),
-        }))
-    }
+    seed.settings = create_seed_settings(&args.seed);
     if let Some(default_application) = &args.default_opts {
         let app = FloraSeedApp {
             application_name: default_application.app_name.clone(),
             application_location: default_application.app_location.clone(),
             category: default_application.app_category.clone(),
+            ..Default::default()
         };
         seed.add_app(app)?;
     }
 
-    manager.create_seed(&args.seed.name, &seed)
+    manager.create_seed(&args.seed.name, &seed)?;
+    if args.seed.init {
+        init_seed(manager, &args.seed.name, false)?;
+    }
+
+    Ok(())
+}
+
+fn init_seed(manager: &FloraManager, name: &str, quiet: bool) -> Result<(), FloraError> {
+    println!("Initializing prefix of seed {}...", name);
+    manager.seed_init(name, quiet)?;
+    println!("Prefix of seed {} is ready", name);
+
+    Ok(())
 }
 
 fn set_wine_seed(manager: &FloraManager, args: &SetWineOpts) -> Result<(), FloraError> {
@@ -548,9 +1558,19 @@ fn set_wine_seed(manager: &FloraManager, args: &SetWineOpts) -> Result<(), Flora
         if args.wine_runtime.is_some() {
             wine_settings.wine_runtime = args.wine_runtime.clone();
         }
+        if args.prefix_arch.is_some() {
+            wine_settings.prefix_arch = args.prefix_arch;
+        }
+        if args.seed.windows_version.is_some() {
+            wine_settings.windows_version = args.seed.windows_version;
+        }
+        if args.seed.clear_windows_version {
+            wine_settings.windows_version = None;
+        }
     } else {
         return Err(FloraError::SeedWrongType(args.seed.name.clone()));
     }
+    set_seed_settings(&mut seed, &args.seed);
     manager.update_seed(&args.seed.name, &seed)?;
 
     Ok(())
@@ -572,9 +1592,16 @@ fn set_proton_seed(manager: &FloraManager, args: &SetProtonOpts) -> Result<(), F
         if args.store.is_some() {
             proton_settings.store = args.store.clone();
         }
+        if args.seed.windows_version.is_some() {
+            proton_settings.windows_version = args.seed.windows_version;
+        }
+        if args.seed.clear_windows_version {
+            proton_settings.windows_version = None;
+        }
     } else {
         return Err(FloraError::SeedWrongType(args.seed.name.clone()));
     }
+    set_seed_settings(&mut seed, &args.seed);
     manager.update_seed(&args.seed.name, &seed)?;
 
     Ok(())
@@ -591,11 +1618,90 @@ fn main() -> Result<(), FloraError> {
                 CreateCommands::Wine(args) => create_wine_seed(&manager, args),
                 CreateCommands::Proton(args) => create_proton_seed(&manager, args),
             },
+            SeedCommands::Init(args) => init_seed(&manager, &args.name, args.quiet),
+            SeedCommands::Export(args) => {
+                let output = args
+                    .output
+                    .clone()
+                    .unwrap_or_else(|| PathBuf::from(format!("{}.tar.zst", args.name)));
+                println!("Exporting seed {}...", args.name);
+                let manifest = manager.export_seed(&args.name, &output, args.exclude_caches)?;
+                if !manifest.has_prefix {
+                    println!(
+                        "Prefix of seed {} does not exist, it was not exported",
+                        args.name
+                    );
+                }
+                println!("Exported seed {} to {}", args.name, output.display());
+
+                Ok(())
+            }
+            SeedCommands::Import(args) => {
+                println!("Importing {}...", args.archive.display());
+                let manifest = manager.import_seed(&args.archive, args.name.as_deref())?;
+                let name = args.name.as_deref().unwrap_or(&manifest.seed_name);
+                println!(
+                    "Imported {} seed {} with {} apps, exported by flora {}",
+                    manifest.seed_type,
+                    name,
+                    manifest.apps.len(),
+                    manifest.flora_version
+                );
+                if let Some(runtime) = &manifest.runtime {
+                    println!(
+                        "The seed uses the {} runtime, make sure it is installed",
+                        runtime
+                    );
+                }
+
+                Ok(())
+            }
+            SeedCommands::Clone(args) => {
+                manager.clone_seed(&args.name, &args.new_name, args.prefix.as_deref())
+            }
+            SeedCommands::Rename(args) => {
+                manager.rename_seed(&args.name, &args.new_name, args.rename_prefix)
+            }
             SeedCommands::Set(create_opts) => match &create_opts.commands {
                 SetCommands::Wine(args) => set_wine_seed(&manager, args),
                 SetCommands::Proton(args) => set_proton_seed(&manager, args),
             },
-            SeedCommands::Delete(args) => manager.delete_seed(&args.name),
+            SeedCommands::Delete(args) => {
+                if !args.purge {
+                    return manager.delete_seed(&args.name);
+                }
+
+                if args.delete_prefix && !args.yes {
+                    let prefix = manager.get_seed_prefix(&args.name)?;
+                    if !confirm(&format!(
+                        "Delete prefix {} and everything installed in it?",
+                        prefix.display()
+                    ))? {
+                        println!("Not deleting seed {}", args.name);
+                        return Ok(());
+                    }
+                }
+
+                let report = manager.purge_seed(&args.name, args.delete_prefix)?;
+                println!("Removed seed {}", report.seed_file.display());
+                for desktop_entry in &report.desktop_entries {
+                    println!("Removed desktop entry {}", desktop_entry.display());
+                }
+                for icon in &report.icons {
+                    println!("Removed icon {}", icon.display());
+                }
+                if let Some(log) = &report.log {
+                    println!("Removed log {}", log.display());
+                }
+                if let Some(snapshots) = &report.snapshots {
+                    println!("Removed snapshots {}", snapshots.display());
+                }
+                if let Some(prefix) = &report.prefix {
+                    println!("Removed prefix {}", prefix.display());
+                }
+
+                Ok(())
+            }
             SeedCommands::List(args) => {
                 let seeds = manager.list_seed()?;
                 if args.long {
@@ -620,6 +1726,9 @@ fn main() -> Result<(), FloraError> {
                 if seed_table.runtime.is_empty() {
                     seed_table.runtime = "(default runtime)";
                 }
+                seed_table.windows_version = manager
+                    .get_windows_version(&args.name, None)?
+                    .unwrap_or_else(|| String::from("(Wine default)"));
                 let mut table = Table::kv(vec![seed_table]);
                 table.with(Style::blank());
                 table.with(Colorization::exact(
@@ -644,7 +1753,14 @@ fn main() -> Result<(), FloraError> {
                 }
                 println!("List of apps:");
                 let apps = seed.get_apps();
-                let table_items = apps.iter().map(SeedAppTableRow::from);
+                let mut table_items = Vec::new();
+                for app in &apps {
+                    let mut table_item = SeedAppTableRow::from(app);
+                    table_item.windows_version = manager
+                        .get_windows_version(&args.name, Some(&app.application_name))?
+                        .unwrap_or_default();
+                    table_items.push(table_item);
+                }
 
                 let mut table = Table::new(table_items);
                 table.with(Style::blank());
@@ -695,6 +1811,103 @@ fn main() -> Result<(), FloraError> {
                     seed.delete_env(&seed_env_delete_opts.env_name);
                     manager.update_seed(seed_name, &seed)?;
 
+                    Ok(())
+                }
+            },
+            SeedCommands::Snapshot(snapshot_opts) => match &snapshot_opts.commands {
+                SeedSnapshotCommands::Create(create_opts) => {
+                    let seed_name = &create_opts.seed.name;
+                    println!("Taking snapshot of seed {}...", seed_name);
+                    let snapshot =
+                        manager.create_snapshot(seed_name, create_opts.label.as_deref())?;
+                    println!(
+                        "Created snapshot {} of seed {} ({} files, {})",
+                        snapshot.id,
+                        seed_name,
+                        snapshot.file_count(),
+                        format_size(snapshot.size)
+                    );
+
+                    Ok(())
+                }
+                SeedSnapshotCommands::List(list_opts) => {
+                    let snapshots = manager.list_snapshots(&list_opts.name)?;
+                    if snapshots.is_empty() {
+                        println!("  No snapshots taken");
+                    } else {
+                        let mut table = Table::new(snapshots.iter().map(SnapshotTableRow::from));
+                        table.with(Style::blank());
+                        table.with(Colorization::exact([Color::FG_BRIGHT_BLUE], Rows::first()));
+                        table.modify(Columns::first(), Alignment::left());
+                        println!("{}", table);
+                    }
+
+                    Ok(())
+                }
+                SeedSnapshotCommands::Restore(restore_opts) => {
+                    let seed_name = &restore_opts.seed.name;
+                    println!(
+                        "Restoring snapshot {} of seed {}...",
+                        restore_opts.id, seed_name
+                    );
+                    manager.restore_snapshot(seed_name, restore_opts.id)?;
+                    println!(
+                        "Restored snapshot {} of seed {}",
+                        restore_opts.id, seed_name
+                    );
+
+                    Ok(())
+                }
+                SeedSnapshotCommands::Delete(delete_opts) => {
+                    manager.delete_snapshot(&delete_opts.seed.name, delete_opts.id)
+                }
+            },
+            SeedCommands::DllOverride(dll_override_opts) => match &dll_override_opts.commands {
+                SeedDllOverrideCommands::List(list_opts) => {
+                    let seed = manager.get_seed(&list_opts.seed.name)?;
+                    let mut dll_overrides = seed.get_dll_overrides();
+                    if let Some(app_name) = &list_opts.app {
+                        dll_overrides.extend(seed.get_app(app_name)?.get_dll_overrides());
+                    }
+
+                    if dll_overrides.is_empty() {
+                        println!("  No DLL overrides defined");
+                    } else {
+                        for (dll_name, mode) in dll_overrides {
+                            println!("{} = {}", dll_name, mode);
+                        }
+                    }
+
+                    Ok(())
+                }
+                SeedDllOverrideCommands::Set(set_opts) => {
+                    let seed_name = &set_opts.seed.name;
+                    let mut seed = manager.get_seed(seed_name)?;
+
+                    if let Some(app_name) = &set_opts.app {
+                        let mut app = seed.get_app(app_name)?;
+                        app.update_dll_override(&set_opts.dll_name, set_opts.mode)?;
+                        seed.update_app(app_name, app)?;
+                    } else {
+                        seed.update_dll_override(&set_opts.dll_name, set_opts.mode)?;
+                    }
+                    manager.update_seed(seed_name, &seed)?;
+
+                    Ok(())
+                }
+                SeedDllOverrideCommands::Delete(delete_opts) => {
+                    let seed_name = &delete_opts.seed.name;
+                    let mut seed = manager.get_seed(seed_name)?;
+
+                    if let Some(app_name) = &delete_opts.app {
+                        let mut app = seed.get_app(app_name)?;
+                        app.delete_dll_override(&delete_opts.dll_name)?;
+                        seed.update_app(app_name, app)?;
+                    } else {
+                        seed.delete_dll_override(&delete_opts.dll_name)?;
+                    }
+                    manager.update_seed(seed_name, &seed)?;
+
                     Ok(())
                 }
             },
@@ -723,14 +1936,27 @@ fn main() -> Result<(), FloraError> {
                 let seed_name = &app_add_opts.seed.name;
                 let mut seed = manager.get_seed(seed_name)?;
 
-                let new_app = FloraSeedApp {
+                let mut new_app = FloraSeedApp {
                     application_name: app_add_opts.app_name.clone(),
                     application_location: app_add_opts.app_location.clone(),
                     category: app_add_opts.app_category.clone(),
+                    working_directory: app_add_opts.working_directory.clone(),
+                    wrapper_preset: app_add_opts.wrapper_preset.clone(),
+                    wrappers: (!app_add_opts.wrappers.is_empty())
+                        .then(|| app_add_opts.wrappers.clone()),
+                    windows_version: app_add_opts.windows_version,
+                    ..Default::default()
                 };
+                if let Some(arguments) = &app_add_opts.arguments {
+                    new_app.arguments = parse_arguments(arguments)?;
+                }
+                for (env_name, env_value) in &app_add_opts.env {
+                    new_app.update_env(env_name, env_value);
+                }
 
                 seed.add_app(new_app)?;
                 manager.update_seed(seed_name, &seed)?;
+                manager.sync_desktop_entries_on_app_change(seed_name)?;
 
                 Ok(())
             }
@@ -739,12 +1965,48 @@ fn main() -> Result<(), FloraError> {
                 let mut seed = manager.get_seed(seed_name)?;
 
                 let mut app = seed.get_app(&app_update_opts.app_name)?;
+                // The old version is kept in the registry under the app's old executable
+                if app_update_opts.clear_windows_version {
+                    manager.clear_app_windows_version(seed_name, &app)?;
+                }
                 if let Some(app_location) = app_update_opts.app_location.clone() {
                     app.application_location = app_location;
                 }
                 if let Some(app_category) = app_update_opts.app_category.clone() {
                     app.category = Some(app_category);
                 }
+                if let Some(arguments) = &app_update_opts.arguments {
+                    app.arguments = parse_arguments(arguments)?;
+                }
+                if let Some(working_directory) = app_update_opts.working_directory.clone() {
+                    app.working_directory = if working_directory.is_empty() {
+                        None
+                    } else {
+                        Some(working_directory)
+                    };
+                }
+                for (env_name, env_value) in &app_update_opts.env {
+                    app.update_env(env_name, env_value);
+                }
+                for env_name in &app_update_opts.unset_env {
+                    app.delete_env(env_name);
+                }
+                if !app_update_opts.wrappers.is_empty() {
+                    app.wrappers = Some(app_update_opts.wrappers.clone());
+                }
+                if let Some(wrapper_preset) = app_update_opts.wrapper_preset.clone() {
+                    app.wrapper_preset = (!wrapper_preset.is_empty()).then_some(wrapper_preset);
+                }
+                if app_update_opts.clear_wrappers {
+                    app.wrappers = None;
+                    app.wrapper_preset = None;
+                }
+                if app_update_opts.windows_version.is_some() {
+                    app.windows_version = app_update_opts.windows_version;
+                }
+                if app_update_opts.clear_windows_version {
+                    app.windows_version = None;
+                }
 
                 seed.update_app(&app_update_opts.app_name, app)?;
                 manager.update_seed(seed_name, &seed)?;
@@ -757,6 +2019,7 @@ fn main() -> Result<(), FloraError> {
 
                 seed.rename_app(&app_rename_opts.old_app_name, &app_rename_opts.new_app_name)?;
                 manager.update_seed(seed_name, &seed)?;
+                manager.sync_desktop_entries_on_app_change(seed_name)?;
 
                 Ok(())
             }
@@ -766,13 +2029,26 @@ fn main() -> Result<(), FloraError> {
 
                 seed.delete_app(&app_delete_opts.app_name)?;
                 manager.update_seed(seed_name, &seed)?;
+                manager.sync_desktop_entries_on_app_change(seed_name)?;
+
+                Ok(())
+            }
+            AppCommands::GenerateMenu(app_generate_menu_opts) => {
+                if !app_generate_menu_opts.sync {
+                    return manager.create_desktop_entries(
+                        app_generate_menu_opts.seed_name.as_deref(),
+                        app_generate_menu_opts.app_name.as_deref(),
+                    );
+                }
+
+                let removed =
+                    manager.sync_desktop_entries(app_generate_menu_opts.seed_name.as_deref())?;
+                for item in removed {
+                    println!("Removed stale {} {}", item.kind, item.path.display());
+                }
 
                 Ok(())
             }
-            AppCommands::GenerateMenu(app_generate_menu_opts) => manager.create_desktop_entries(
-                app_generate_menu_opts.seed_name.as_deref(),
-                app_generate_menu_opts.app_name.as_deref(),
-            ),
         },
         Commands::StartMenu(opts) => match &opts.commands {
             StartMenuCommands::List(start_menu_list_opts) => {
@@ -806,17 +2082,27 @@ fn main() -> Result<(), FloraError> {
                 .args
                 .as_ref()
                 .map(|m| m.iter().map(|s| s.as_str()).collect());
-            manager.seed_config(&opts.name, &args, opts.quiet, opts.wait)
+            exit_with_run_result(manager.seed_config(
+                &opts.name,
+                &args,
+                opts.quiet,
+                opts.wait_mode(),
+            )?)
         }
         Commands::Tricks(opts) => {
             let args = opts
                 .args
                 .as_ref()
                 .map(|m| m.iter().map(|s| s.as_str()).collect());
-            manager.seed_tricks(&opts.name, &args, opts.quiet, opts.wait)
+            exit_with_run_result(manager.seed_tricks(
+                &opts.name,
+                &args,
+                opts.quiet,
+                opts.wait_mode(),
+            )?)
         }
         Commands::Run(opts) => {
-            match &opts.args {
+            let result = match &opts.args {
                 Some(args) => {
                     if opts.app {
                         // Launch an app entry
@@ -825,17 +2111,221 @@ fn main() -> Result<(), FloraError> {
                             &opts.name,
                             &Some(joined_args.as_str()),
                             opts.quiet,
-                            opts.wait,
+                            opts.wait_mode(),
                         )
                     } else {
                         let args = args.iter().map(|s| s.as_str()).collect();
                         // Launch executable
-                        manager.seed_run_executable(&opts.name, &args, opts.quiet, opts.wait)
+                        manager.seed_run_executable(&opts.name, &args, opts.quiet, opts.wait_mode())
                     }
                 }
                 // Launch the default app entry if none is specified
-                None => manager.seed_run_app(&opts.name, &None, opts.quiet, opts.wait),
+                None => manager.seed_run_app(&opts.name, &None, opts.quiet, opts.wait_mode()),
+            }?;
+
+            exit_with_run_result(result)
+        }
+        Commands::Ps(opts) => {
+            let processes = manager.list_processes()?;
+            if processes.is_empty() {
+                println!("No processes running");
+            } else if opts.long {
+                let table_items = processes.iter().map(ProcessTableRow::from);
+
+                let mut table = Table::new(table_items);
+                table.with(Style::blank());
+                table.with(Colorization::exact([Color::FG_BRIGHT_BLUE], Rows::first()));
+                table.modify(Columns::first(), Alignment::left());
+
+                println!("{}", table);
+            } else {
+                for process in processes {
+                    match &process.app_name {
+                        Some(app_name) => {
+                            println!("{} {} ({})", process.pid, process.seed_name, app_name)
+                        }
+                        None => println!("{} {}", process.pid, process.seed_name),
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        Commands::Kill(opts) => {
+            let processes = manager.kill_seed(&opts.name)?;
+            for process in processes {
+                println!("Terminated {} ({})", process.pid, process.command);
+            }
+
+            Ok(())
+        }
+        Commands::Gc(opts) => {
+            if opts.prefixes && !opts.dry_run && !opts.yes {
+                let prefixes: Vec<_> = manager
+                    .collect_garbage(true, false)?
+                    .into_iter()
+                    .filter(|item| item.kind == FloraGarbageKind::Prefix)
+                    .collect();
+                if !prefixes.is_empty() {
+                    for prefix in &prefixes {
+                        println!("{} ({})", prefix.path.display(), format_size(prefix.size));
+                    }
+                    if !confirm(&format!(
+                        "Delete these {} prefixes and everything installed in them?",
+                        prefixes.len()
+                    ))? {
+                        println!("Not removing anything");
+                        return Ok(());
+                    }
+                }
             }
+
+            let garbage = manager.collect_garbage(opts.dry_run, opts.prefixes)?;
+            if garbage.is_empty() {
+                println!("Nothing to remove");
+                return Ok(());
+            }
+
+            let table_items = garbage.iter().map(|item| GarbageTableRow {
+                kind: item.kind.to_string(),
+                path: item.path.to_string_lossy().to_string(),
+                size: format_size(item.size),
+            });
+
+            let mut table = Table::new(table_items);
+            table.with(Style::blank());
+            table.with(Colorization::exact([Color::FG_BRIGHT_BLUE], Rows::first()));
+
+            println!("{}", table);
+
+            let (removed, kept): (Vec<_>, Vec<_>) = garbage.iter().partition(|item| {
+                item.removed
+                    || (opts.dry_run && (opts.prefixes || item.kind != FloraGarbageKind::Prefix))
+            });
+            let size = format_size(removed.iter().map(|item| item.size).sum());
+            if opts.dry_run {
+                println!("Would remove {} items, freeing {}", removed.len(), size);
+            } else {
+                println!("Removed {} items, freeing {}", removed.len(), size);
+            }
+            if !kept.is_empty() {
+                println!(
+                    "Kept {} unreferenced prefixes using {}, remove them with --prefixes",
+                    kept.len(),
+                    format_size(kept.iter().map(|item| item.size).sum())
+                );
+            }
+
+            Ok(())
+        }
+        Commands::Mime(mime_opts) => match &mime_opts.commands {
+            MimeCommands::Register(opts) => {
+                let desktop_entry = manager.register_mime_handler(&opts.name)?;
+                println!(
+                    "Windows executables, installers and shortcuts now open with seed {} ({})",
+                    opts.name,
+                    desktop_entry.display()
+                );
+
+                Ok(())
+            }
+        },
+        Commands::Open(opts) => {
+            let seed_name = match manager.find_open_seed(&opts.file)? {
+                Some(seed_name) => seed_name,
+                None => {
+                    let seeds = manager.list_seed()?;
+                    if seeds.is_empty() {
+                        println!("No seeds to open {} with", opts.file.display());
+                        return Ok(());
+                    }
+                    match choose_seed(&seeds, &opts.file)? {
+                        Some(seed_name) => seed_name.to_string(),
+                        None => {
+                            println!("Not opening {}", opts.file.display());
+                            return Ok(());
+                        }
+                    }
+                }
+            };
+
+            let wait = match opts.wait {
+                true => FloraWaitMode::Process,
+                false => FloraWaitMode::NoWait,
+            };
+            let result = manager.seed_open_file(&seed_name, &opts.file, opts.quiet, wait)?;
+
+            exit_with_run_result(result)
         }
+        Commands::Reg(reg_opts) => match &reg_opts.commands {
+            RegCommands::Get(opts) => {
+                let registry = manager.get_registry(&opts.seed.name)?;
+                let key = registry.get_key(&opts.key.parse()?)?;
+
+                if let Some(value_name) = &opts.value {
+                    let value_name = parse_reg_value_name(value_name);
+                    let value =
+                        key.get_value(value_name)
+                            .ok_or(FloraError::RegistryValueNotFound(String::from(
+                                value_name.unwrap_or("@"),
+                            )))?;
+                    println!("{}", value.data);
+                } else if key.values.is_empty() {
+                    println!("  No values defined");
+                } else {
+                    let value_items = key.values.iter().map(RegValueTableRow::from);
+
+                    let mut table = Table::new(value_items);
+                    table.with(Style::blank());
+                    table.with(Colorization::exact([Color::FG_BRIGHT_BLUE], Rows::first()));
+                    table.modify(Columns::first(), Alignment::left());
+                    println!("{}", table);
+                }
+
+                Ok(())
+            }
+            RegCommands::Set(opts) => {
+                let registry = manager.get_registry(&opts.seed.name)?;
+                let data = parse_reg_data(opts.value_type, &opts.data)?;
+
+                registry.set_value(
+                    &opts.key.parse()?,
+                    FloraRegistryValue::new(parse_reg_value_name(&opts.value), data),
+                )
+            }
+            RegCommands::Delete(opts) => {
+                let registry = manager.get_registry(&opts.seed.name)?;
+                let key = opts.key.parse()?;
+
+                match &opts.value {
+                    Some(value_name) => {
+                        registry.delete_value(&key, parse_reg_value_name(value_name))
+                    }
+                    None => registry.delete_key(&key),
+                }
+            }
+            RegCommands::Export(opts) => {
+                let registry = manager.get_registry(&opts.seed.name)?;
+                let export = registry.export(&opts.key.parse()?)?;
+
+                match &opts.output {
+                    Some(output) => fs::write(output, export)?,
+                    None => print!("{}", export),
+                }
+
+                Ok(())
+            }
+            RegCommands::Import(opts) => {
+                let registry = manager.get_registry(&opts.seed.name)?;
+                let content = if opts.file.as_os_str() == "-" {
+                    io::read_to_string(io::stdin())?
+                } else {
+                    let content = fs::read(&opts.file)?;
+                    String::from_utf8_lossy(&content).into_owned()
+                };
+
+                registry.import(&content)
+            }
+        },
     }
 }
@@ -0,0 +1,500 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Read,
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{desktop, errors::FloraError, prefix, seed::FloraSeed};
+
+const MANIFEST_PATH: &str = "manifest.toml";
+const SEED_PATH: &str = "seed.toml";
+const ICONS_DIR: &str = "icons";
+const PREFIX_DIR: &str = "prefix";
+
+/// Directories holding caches that Wine and apps recreate, relative to the prefix
+const CACHE_DIRS: [&str; 4] = [
+    "drive_c/windows/temp",
+    "appdata/local/temp",
+    "appdata/local/microsoft/windows/inetcache",
+    "appdata/local/d3dscache",
+];
+/// Extensions of shader cache files
+const CACHE_EXTENSIONS: [&str; 2] = ["dxvk-cache", "vkd3d-proton.cache"];
+
+/// Describes the contents of a seed archive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FloraSeedArchiveManifest {
+    /// Version of flora that created the archive
+    pub flora_version: String,
+    pub seed_name: String,
+    pub seed_type: String,
+    /// Runtime used by the seed, which needs to be installed on the importing machine
+    pub runtime: Option<String>,
+    /// Names of the seed's apps
+    pub apps: Vec<String>,
+    /// Creation time, in seconds since the UNIX epoch
+    pub created_at: u64,
+    /// Location of the prefix on the exporting machine
+    pub prefix: PathBuf,
+    /// Whether the archive contains the prefix
+    pub has_prefix: bool,
+    /// App names, by the file name of their icon in the archive
+    #[serde(default)]
+    pub icons: BTreeMap<String, String>,
+}
+
+/// Contents of a seed archive, with the prefix already extracted
+pub(crate) struct FloraSeedArchive {
+    pub manifest: FloraSeedArchiveManifest,
+    pub seed: FloraSeed,
+    /// Icon contents, by app name
+    pub icons: BTreeMap<String, Vec<u8>>,
+}
+
+/// Returns the file name of an app's icon in an archive
+///
+/// App names may contain dots or slashes, so they are encoded like desktop file IDs.
+pub(crate) fn icon_file_name(app_name: &str) -> String {
+    format!("{}.png", desktop::desktop_id_part(app_name))
+}
+
+fn is_cache(relative_path: &Path) -> bool {
+    let path = format!("/{}", relative_path.to_string_lossy().to_lowercase());
+
+    CACHE_DIRS
+        .iter()
+        .any(|cache_dir| path.ends_with(&format!("/{}", cache_dir)))
+        || CACHE_EXTENSIONS
+            .iter()
+            .any(|extension| path.ends_with(&format!(".{}", extension)))
+}
+
+/// Writes a seed, its icons and its prefix into a zstd-compressed tarball
+pub(crate) fn export(
+    output: &Path,
+    manifest: &FloraSeedArchiveManifest,
+    seed: &FloraSeed,
+    icons: &BTreeMap<String, PathBuf>,
+    exclude_caches: bool,
+) -> Result<(), FloraError> {
+    let encoder = zstd::Encoder::new(File::create(output)?, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+
+    append_file(
+        &mut builder,
+        MANIFEST_PATH,
+        toml::to_string(manifest)?.as_bytes(),
+    )?;
+    append_file(&mut builder, SEED_PATH, toml::to_string(seed)?.as_bytes())?;
+
+    for (app_name, icon_path) in icons {
+        builder.append_path_with_name(
+            icon_path,
+            Path::new(ICONS_DIR).join(icon_file_name(app_name)),
+        )?;
+    }
+
+    if manifest.has_prefix {
+        let walker = WalkDir::new(&manifest.prefix)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|entry| {
+                let relative_path = entry.path().strip_prefix(&manifest.prefix).unwrap();
+                !(exclude_caches && is_cache(relative_path))
+            });
+
+        for entry in walker {
+            let entry = entry.map_err(|e| FloraError::FileAccessError(e.into()))?;
+            let relative_path = entry.path().strip_prefix(&manifest.prefix).unwrap();
+            let file_type = entry.file_type();
+
+            let archive_path = Path::new(PREFIX_DIR).join(relative_path);
+
+            if file_type.is_symlink() {
+                // Link targets are written as-is, tar would otherwise turn a target of / into //
+                let mut header = tar::Header::new_gnu();
+                header.set_metadata(&entry.path().symlink_metadata()?);
+                header.set_size(0);
+                header.set_link_name_literal(
+                    fs::read_link(entry.path())?.as_os_str().as_encoded_bytes(),
+                )?;
+                builder.append_data(&mut header, archive_path, std::io::empty())?;
+            } else if file_type.is_file() || file_type.is_dir() {
+                builder.append_path_with_name(entry.path(), archive_path)?;
+            } else {
+                debug!("Skipping special file {}", entry.path().display());
+            }
+        }
+    }
+
+    builder.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+fn append_file<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    content: &[u8],
+) -> Result<(), FloraError> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+    );
+    header.set_cksum();
+    builder.append_data(&mut header, path, content)?;
+
+    Ok(())
+}
+
+/// Reads the manifest of a seed archive, without extracting it
+pub(crate) fn read_manifest(archive: &Path) -> Result<FloraSeedArchiveManifest, FloraError> {
+    let decoder = zstd::Decoder::new(File::open(archive)?)?;
+    let mut archive_reader = tar::Archive::new(decoder);
+
+    // The manifest is written first, so the rest of the archive is not read
+    for entry in archive_reader.entries()? {
+        let mut entry = entry?;
+        if entry.path()? == Path::new(MANIFEST_PATH) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            return Ok(toml::from_str(&content)?);
+        }
+    }
+
+    Err(FloraError::InvalidArchive(String::from("missing manifest")))
+}
+
+/// Reads a seed archive, extracting its prefix into a new directory
+///
+/// Paths in the extracted prefix are relocated to its new location. The prefix is extracted next
+/// to its destination first, so a failed import leaves no partial prefix behind.
+pub(crate) fn import(archive: &Path, prefix: &Path) -> Result<FloraSeedArchive, FloraError> {
+    let mut staging = prefix.as_os_str().to_os_string();
+    staging.push(".flora-import");
+    let staging = PathBuf::from(staging);
+    if fs::exists(&staging)? {
+        fs::remove_dir_all(&staging)?;
+    }
+
+    let result = unpack(archive, &staging).and_then(|seed_archive| {
+        if seed_archive.manifest.has_prefix {
+            let staged_prefix = staging.join(PREFIX_DIR);
+            fs::create_dir_all(&staged_prefix)?;
+            if !staged_prefix.symlink_metadata()?.is_dir() {
+                return Err(FloraError::InvalidArchive(String::from(
+                    "prefix is not a directory",
+                )));
+            }
+            prefix::relocate_prefix(&staged_prefix, &seed_archive.manifest.prefix, prefix)?;
+            if let Some(parent) = prefix.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&staged_prefix, prefix)?;
+        }
+
+        Ok(seed_archive)
+    });
+    let _ = fs::remove_dir_all(&staging);
+
+    result
+}
+
+/// Reads the seed and icons of an archive, extracting its prefix into the prefix directory of
+/// `staging`
+fn unpack(archive: &Path, staging: &Path) -> Result<FloraSeedArchive, FloraError> {
+    fs::create_dir_all(staging)?;
+
+    let decoder = zstd::Decoder::new(File::open(archive)?)?;
+    let mut archive_reader = tar::Archive::new(decoder);
+    archive_reader.set_preserve_permissions(true);
+
+    let mut manifest = None;
+    let mut seed = None;
+    let mut icons = BTreeMap::new();
+
+    for entry in archive_reader.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+
+        // Refuse entries escaping the archive, e.g. ../../.bashrc
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(FloraError::InvalidArchive(format!(
+                "unsafe path {}",
+                path.display()
+            )));
+        }
+
+        if path == Path::new(MANIFEST_PATH) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            manifest = Some(toml::from_str::<FloraSeedArchiveManifest>(&content)?);
+        } else if path == Path::new(SEED_PATH) {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            seed = Some(toml::from_str::<FloraSeed>(&content)?);
+        } else if let Ok(icon_path) = path.strip_prefix(ICONS_DIR) {
+            // The manifest is written first, and maps icon file names back to app names
+            let app_name = manifest
+                .as_ref()
+                .and_then(|manifest: &FloraSeedArchiveManifest| {
+                    manifest.icons.get(icon_path.to_string_lossy().as_ref())
+                });
+            let Some(app_name) = app_name.cloned() else {
+                debug!("Skipping unknown icon {}", path.display());
+                continue;
+            };
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            icons.insert(app_name, content);
+        } else if path.starts_with(PREFIX_DIR) {
+            // Links are never followed out of the staging directory, and hard links must point
+            // inside of it
+            if !entry.unpack_in(staging)? {
+                return Err(FloraError::InvalidArchive(format!(
+                    "unsafe path {}",
+                    path.display()
+                )));
+            }
+        } else {
+            debug!("Skipping unknown archive entry {}", path.display());
+        }
+    }
+
+    let manifest =
+        manifest.ok_or_else(|| FloraError::InvalidArchive(String::from("missing manifest")))?;
+    let seed = seed.ok_or_else(|| FloraError::InvalidArchive(String::from("missing seed")))?;
+
+    Ok(FloraSeedArchive {
+        manifest,
+        seed,
+        icons,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        fs::{self, File},
+        path::{Path, PathBuf},
+    };
+
+    use crate::{
+        archive::{
+            self, FloraSeedArchiveManifest, MANIFEST_PATH, SEED_PATH, append_file, icon_file_name,
+            is_cache,
+        },
+        errors::FloraError,
+        seed::{FloraSeed, FloraSeedType, FloraWineSeed},
+    };
+
+    enum TestEntry<'a> {
+        File(&'a str, &'a str),
+        Symlink(&'a str, &'a str),
+        HardLink(&'a str, &'a str),
+    }
+
+    fn wine_seed() -> FloraSeed {
+        let mut seed = FloraSeed::default();
+        seed.seed_type = FloraSeedType::Wine(FloraWineSeed {
+            wine_prefix: None,
+            wine_runtime: None,
+            windows_version: None,
+            prefix_arch: None,
+        });
+
+        seed
+    }
+
+    /// Writes an archive with a valid manifest and seed, followed by the given prefix entries
+    fn write_archive(output: &Path, entries: &[TestEntry]) {
+        let manifest = FloraSeedArchiveManifest {
+            flora_version: String::from("0.0.0"),
+            seed_name: String::from("seed"),
+            seed_type: String::from("wine"),
+            runtime: None,
+            apps: Vec::new(),
+            created_at: 0,
+            prefix: PathBuf::from("/home/flora/prefix"),
+            has_prefix: true,
+            icons: BTreeMap::new(),
+        };
+        let seed = wine_seed();
+        let encoder = zstd::Encoder::new(File::create(output).unwrap(), 0).unwrap();
+        let mut builder = tar::Builder::new(encoder);
+        append_file(
+            &mut builder,
+            MANIFEST_PATH,
+            toml::to_string(&manifest).unwrap().as_bytes(),
+        )
+        .unwrap();
+        append_file(
+            &mut builder,
+            SEED_PATH,
+            toml::to_string(&seed).unwrap().as_bytes(),
+        )
+        .unwrap();
+
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(0o644);
+            let (path, content) = match entry {
+                TestEntry::File(path, content) => {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_size(content.len() as u64);
+                    (path, content.as_bytes())
+                }
+                TestEntry::Symlink(path, target) | TestEntry::HardLink(path, target) => {
+                    header.set_entry_type(if matches!(entry, TestEntry::Symlink(..)) {
+                        tar::EntryType::Symlink
+                    } else {
+                        tar::EntryType::Link
+                    });
+                    header.set_size(0);
+                    header.set_link_name_literal(target.as_bytes()).unwrap();
+                    (path, &[][..])
+                }
+            };
+            // Written directly, as tar refuses to write paths containing ..
+            let name = &mut header.as_gnu_mut().unwrap().name;
+            name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, content).unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn import(root: &Path, entries: &[TestEntry]) -> Result<PathBuf, FloraError> {
+        let archive_path = root.join("seed.tar.zst");
+        write_archive(&archive_path, entries);
+        let prefix = root.join("prefixes/seed");
+        archive::import(&archive_path, &prefix)?;
+
+        Ok(prefix)
+    }
+
+    #[test]
+    fn import_prefix() {
+        let root = tempfile::tempdir().unwrap();
+        let prefix = import(
+            root.path(),
+            &[
+                TestEntry::File("prefix/drive_c/win.ini", "[windows]"),
+                TestEntry::Symlink("prefix/dosdevices/z:", "/"),
+                TestEntry::HardLink("prefix/drive_c/system.ini", "prefix/drive_c/win.ini"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(prefix.join("drive_c/system.ini")).unwrap(),
+            "[windows]"
+        );
+        assert_eq!(
+            fs::read_link(prefix.join("dosdevices/z:")).unwrap(),
+            Path::new("/")
+        );
+        assert!(!fs::exists(root.path().join("prefixes/seed.flora-import")).unwrap());
+    }
+
+    #[test]
+    fn import_path_traversal() {
+        let root = tempfile::tempdir().unwrap();
+        let result = import(
+            root.path(),
+            &[TestEntry::File("prefix/../../escaped", "escaped")],
+        );
+
+        assert!(matches!(result, Err(FloraError::InvalidArchive(_))));
+        assert!(!fs::exists(root.path().join("escaped")).unwrap());
+        assert!(!fs::exists(root.path().join("prefixes/seed")).unwrap());
+    }
+
+    #[test]
+    fn import_symlink_escape() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = root.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+
+        let result = import(
+            root.path(),
+            &[
+                TestEntry::Symlink("prefix/link", outside.to_str().unwrap()),
+                TestEntry::File("prefix/link/escaped", "escaped"),
+            ],
+        );
+        assert!(result.is_err());
+        assert!(!fs::exists(outside.join("escaped")).unwrap());
+
+        let result = import(
+            root.path(),
+            &[TestEntry::HardLink("prefix/passwd", "../../../etc/passwd")],
+        );
+        assert!(result.is_err());
+        assert!(!fs::exists(root.path().join("prefixes/seed")).unwrap());
+    }
+
+    #[test]
+    fn icons_of_dotted_apps() {
+        let root = tempfile::tempdir().unwrap();
+        let app_names = ["Foo.Bar", "Foo", "v1.2"];
+        let mut icons = BTreeMap::new();
+        for (index, app_name) in app_names.iter().enumerate() {
+            let icon_path = root.path().join(format!("{}.png", index));
+            fs::write(&icon_path, app_name).unwrap();
+            icons.insert(app_name.to_string(), icon_path);
+        }
+        let manifest = FloraSeedArchiveManifest {
+            flora_version: String::from("0.0.0"),
+            seed_name: String::from("seed"),
+            seed_type: String::from("wine"),
+            runtime: None,
+            apps: app_names.map(String::from).to_vec(),
+            created_at: 0,
+            prefix: root.path().join("prefix"),
+            has_prefix: false,
+            icons: app_names
+                .map(|app_name| (icon_file_name(app_name), app_name.to_string()))
+                .into(),
+        };
+
+        let archive_path = root.path().join("seed.tar.zst");
+        archive::export(&archive_path, &manifest, &wine_seed(), &icons, false).unwrap();
+        let seed_archive = archive::import(&archive_path, &root.path().join("imported")).unwrap();
+
+        assert_eq!(seed_archive.icons.len(), app_names.len());
+        for app_name in app_names {
+            assert_eq!(seed_archive.icons[app_name], app_name.as_bytes());
+        }
+    }
+
+    #[test]
+    fn cache_paths() {
+        assert!(is_cache(Path::new("drive_c/windows/temp")));
+        assert!(is_cache(Path::new(
+            "drive_c/users/steamuser/AppData/Local/Temp"
+        )));
+        assert!(is_cache(Path::new(
+            "drive_c/users/steamuser/AppData/Local/Game/shaders.dxvk-cache"
+        )));
+        assert!(!is_cache(Path::new("drive_c/windows/temporary")));
+        assert!(!is_cache(Path::new("drive_c/Games/Temp")));
+    }
+}
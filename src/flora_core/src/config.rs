@@ -1,4 +1,4 @@
-use std::fs;
+use std::{collections::BTreeMap, fs};
 
 use serde::{Deserialize, Serialize};
 
@@ -8,6 +8,18 @@ use crate::{dirs::FloraDirs, errors::FloraError};
 pub struct FloraConfig {
     pub wine: FloraWineConfig,
     pub proton: FloraProtonConfig,
+
+    /// Named wrapper chains that seeds and apps can refer to
+    #[serde(default)]
+    pub wrapper_presets: BTreeMap<String, Vec<String>>,
+
+    /// Desktop integration settings
+    #[serde(default)]
+    pub desktop: FloraDesktopConfig,
+
+    /// Seeds that `flora open` opens files with
+    #[serde(default)]
+    pub open: FloraOpenConfig,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,6 +40,43 @@ pub struct FloraProtonConfig {
     pub default_proton_runtime: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FloraDesktopConfig {
+    /// Syncs a seed's desktop entries when its apps are added, renamed or deleted
+    #[serde(default)]
+    pub sync_on_app_change: bool,
+
+    /// Groups each seed's apps in a submenu of the Flora menu
+    #[serde(default)]
+    pub seed_submenus: bool,
+
+    /// Command that runs flora in desktop entries and their actions, such as
+    /// `["flatpak", "run", "<app ID>"]`. Defaults to `["flora"]`.
+    #[serde(default)]
+    pub exec_command: Option<Vec<String>>,
+
+    /// Comment of desktop entries, where `{app}`, `{seed}` and `{runner}` are replaced with the
+    /// app, seed and runner names
+    #[serde(default)]
+    pub comment: Option<String>,
+
+    /// Additional keys of desktop entries. Values support the same placeholders as `comment`.
+    #[serde(default)]
+    pub extra_keys: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FloraOpenConfig {
+    /// Seeds opening files by extension, e.g. `msi = "installers"`
+    #[serde(default)]
+    pub extensions: BTreeMap<String, String>,
+
+    /// Seeds opening files inside a directory, e.g. `"/home/user/Games" = "games"`. These take
+    /// precedence over extensions, and the deepest directory wins.
+    #[serde(default)]
+    pub directories: BTreeMap<String, String>,
+}
+
 impl FloraConfig {
     pub fn read_config(dirs: &FloraDirs) -> Result<FloraConfig, FloraError> {
         let config_path = {
@@ -64,6 +113,9 @@ impl FloraConfig {
                 },
                 default_proton_runtime: None,
             },
+            wrapper_presets: BTreeMap::new(),
+            desktop: FloraDesktopConfig::default(),
+            open: FloraOpenConfig::default(),
         };
 
         if !fs::exists(&config_path)? {
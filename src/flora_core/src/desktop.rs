@@ -1,42 +1,1033 @@
-use crate::{dirs::FloraDirs, errors::FloraError};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-pub(crate) fn initialize_desktop_entries(dirs: &FloraDirs) -> Result<(), FloraError> {
+use flora_icon::FloraLink;
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::FloraDesktopConfig,
+    dirs::FloraDirs,
+    errors::FloraError,
+    runners::FloraRunner,
+    seed::{FloraSeedApp, FloraSeedAppShortcut},
+    winepath,
+};
+
+/// Refreshes the MIME type cache of the applications directory, if `update-desktop-database` is
+/// installed
+pub(crate) fn update_desktop_database(dirs: &FloraDirs) -> Result<(), FloraError> {
+    let desktop_entry_root = dirs.get_desktop_entry_root();
+    let applications_dir = desktop_entry_root.parent().unwrap_or(&desktop_entry_root);
+
+    match Command::new("update-desktop-database")
+        .arg(applications_dir)
+        .status()
+    {
+        Ok(status) if !status.success() => {
+            warn!("update-desktop-database exited with {}", status);
+        }
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            debug!("update-desktop-database not found, skipping");
+        }
+        Err(e) => return Err(FloraError::RunnerExecError(e)),
+    }
+
+    Ok(())
+}
+
+/// Category shared by all flora desktop entries
+const FLORA_CATEGORY: &str = "X-Flora";
+/// Marks files written by flora, followed by the hash of the rest of the file
+const GENERATED_MARKER: &str = "flora-generated:";
+
+/// Characters that need an `Exec` argument to be quoted
+const EXEC_RESERVED_CHARS: &[char] = &[
+    ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')',
+    '`',
+];
+
+/// A desktop entry, written following the Desktop Entry Specification's escaping rules
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FloraDesktopEntry {
+    pub name: String,
+    pub comment: Option<String>,
+    pub icon: Option<String>,
+    /// Command line, unquoted
+    pub exec: Vec<String>,
+    /// Field code appended to the command line, such as `%f`
+    pub exec_field_code: Option<&'static str>,
+    pub categories: Vec<String>,
+    pub mime_types: Vec<String>,
+    pub keywords: Vec<String>,
+    /// Window class the launched app's windows have, so docks group them with the entry
+    pub startup_wm_class: Option<String>,
+    pub terminal: bool,
+    /// Hides the entry from menus, for entries only used to open files
+    pub no_display: bool,
+    /// Writes a `Type=Directory` entry naming a menu, which has no command line
+    pub directory: bool,
+    pub actions: Vec<FloraDesktopAction>,
+    /// Additional keys, written as-is after the other keys
+    pub extra_keys: BTreeMap<String, String>,
+}
+
+/// An additional action of a desktop entry, shown when right-clicking it in launchers
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FloraDesktopAction {
+    /// Identifier of the action, only made of ASCII letters, digits and `-`
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    /// Command line, unquoted
+    pub exec: Vec<String>,
+}
+
+impl FloraDesktopEntry {
+    /// Renders the entry as the contents of a `.desktop` file
+    pub fn render(&self) -> Result<String, FloraError> {
+        if self.name.is_empty() {
+            return Err(FloraError::InvalidDesktopEntry(String::from(
+                "Name is empty",
+            )));
+        }
+        let mut lines = vec![String::from("[Desktop Entry]")];
+        if self.directory {
+            lines.push(String::from("Type=Directory"));
+        } else {
+            validate_exec(&self.exec)?;
+            lines.push(String::from("Type=Application"));
+        }
+        lines.push(format!("Name={}", escape_string(&self.name)));
+        if let Some(comment) = &self.comment {
+            lines.push(format!("Comment={}", escape_string(comment)));
+        }
+        if let Some(icon) = &self.icon {
+            lines.push(format!("Icon={}", escape_string(icon)));
+        }
+        if !self.directory {
+            let mut exec = quote_exec(&self.exec);
+            if let Some(field_code) = self.exec_field_code {
+                exec.push(' ');
+                exec.push_str(field_code);
+            }
+            lines.push(format!("Exec={}", escape_string(&exec)));
+        }
+        if !self.categories.is_empty() {
+            lines.push(format!("Categories={}", escape_list(&self.categories)));
+        }
+        if !self.mime_types.is_empty() {
+            lines.push(format!("MimeType={}", escape_list(&self.mime_types)));
+        }
+        if !self.keywords.is_empty() {
+            lines.push(format!("Keywords={}", escape_list(&self.keywords)));
+        }
+        if let Some(startup_wm_class) = &self.startup_wm_class {
+            lines.push(format!(
+                "StartupWMClass={}",
+                escape_string(startup_wm_class)
+            ));
+        }
+        if !self.directory {
+            lines.push(format!("Terminal={}", self.terminal));
+        }
+        if self.no_display {
+            lines.push(String::from("NoDisplay=true"));
+        }
+        if !self.actions.is_empty() {
+            let action_ids: Vec<_> = self
+                .actions
+                .iter()
+                .map(|action| action.id.clone())
+                .collect();
+            lines.push(format!("Actions={}", escape_list(&action_ids)));
+        }
+        for (key, value) in &self.extra_keys {
+            lines.push(format!("{}={}", key, escape_string(value)));
+        }
+
+        for action in &self.actions {
+            if action.id.is_empty()
+                || !action
+                    .id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+            {
+                return Err(FloraError::InvalidDesktopEntry(format!(
+                    "invalid action {:?}",
+                    action.id
+                )));
+            }
+            validate_exec(&action.exec)?;
+
+            lines.push(String::new());
+            lines.push(format!("[Desktop Action {}]", action.id));
+            lines.push(format!("Name={}", escape_string(&action.name)));
+            if let Some(icon) = &action.icon {
+                lines.push(format!("Icon={}", escape_string(icon)));
+            }
+            lines.push(format!("Exec={}", escape_string(&quote_exec(&action.exec))));
+        }
+
+        let mut content = lines.join("\n");
+        content.push('\n');
+        validate(&content)?;
+
+        Ok(content)
+    }
+}
+
+/// Checks the program of an `Exec` key
+fn validate_exec(exec: &[String]) -> Result<(), FloraError> {
+    // The program is looked up as is, and an = would make it look like an environment variable
+    match exec.first() {
+        Some(program) if !program.is_empty() && !program.contains('=') => Ok(()),
+        _ => Err(FloraError::InvalidDesktopEntry(format!(
+            "invalid program in Exec {:?}",
+            exec
+        ))),
+    }
+}
+
+/// Escapes a string or localestring value
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            // Leading spaces would otherwise be trimmed by parsers
+            ' ' if index == 0 => escaped.push_str("\\s"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Escapes a list of strings, which also escapes the separator
+fn escape_list(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|value| format!("{};", escape_string(value).replace(';', "\\;")))
+        .collect()
+}
+
+/// Quotes the arguments of an `Exec` key
+///
+/// The result is escaped as a string value afterwards, which doubles the backslashes.
+fn quote_exec(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            // A literal % would be read as a field code
+            let arg = arg.replace('%', "%%");
+            if !arg.is_empty() && !arg.contains(EXEC_RESERVED_CHARS) {
+                return arg;
+            }
+
+            let mut quoted = String::from('"');
+            for c in arg.chars() {
+                if matches!(c, '"' | '`' | '$' | '\\') {
+                    quoted.push('\\');
+                }
+                quoted.push(c);
+            }
+            quoted.push('"');
+
+            quoted
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Checks that a rendered desktop entry only has group headers and valid, unique key-value pairs
+fn validate(content: &str) -> Result<(), FloraError> {
+    let mut keys = HashSet::new();
+    for line in content.lines() {
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            keys.clear();
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(FloraError::InvalidDesktopEntry(format!(
+                "invalid line {:?}",
+                line
+            )));
+        };
+        if !is_valid_key(key) {
+            return Err(FloraError::InvalidDesktopEntry(format!(
+                "invalid key {:?}",
+                key
+            )));
+        }
+        if !keys.insert(key) {
+            return Err(FloraError::InvalidDesktopEntry(format!(
+                "duplicate key {}",
+                key
+            )));
+        }
+        if value.chars().any(char::is_control) {
+            return Err(FloraError::InvalidDesktopEntry(format!(
+                "control character in {}",
+                key
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks a key name, made of ASCII letters, digits and `-`, optionally followed by a locale such
+/// as `[pt_BR]`
+fn is_valid_key(key: &str) -> bool {
+    let (name, locale) = match key.split_once('[') {
+        Some((name, locale)) => match locale.strip_suffix(']') {
+            Some(locale) => (name, Some(locale)),
+            None => return false,
+        },
+        None => (key, None),
+    };
+
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && locale.is_none_or(|locale| {
+            !locale.is_empty()
+                && locale
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '@' | '.'))
+        })
+}
+
+/// Separates the seed from the app in desktop file IDs, and never appears in an encoded name
+pub(crate) const DESKTOP_ID_SEPARATOR: &str = "__";
+
+/// Turns a seed or app name into a part of a desktop file ID
+///
+/// Desktop file IDs may only contain ASCII letters, digits, `-` and `_`. Other characters, `_`
+/// included, are written as `_` and their hex UTF-8 bytes, so different names never share a file.
+pub(crate) fn desktop_id_part(name: &str) -> String {
+    let mut id_part = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            id_part.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                id_part.push_str(&format!("_{:02x}", byte));
+            }
+        }
+    }
+
+    id_part
+}
+
+/// Returns the desktop file ID of an app, without extension
+///
+/// An encoded name has a hex digit after every `_`, so the separator is always found where the
+/// seed ends and different seed and app names never give the same ID.
+pub(crate) fn desktop_id(seed_name: &str, app_name: &str) -> String {
+    format!(
+        "{}{}{}",
+        desktop_id_part(seed_name),
+        DESKTOP_ID_SEPARATOR,
+        desktop_id_part(app_name)
+    )
+}
+
+/// Returns the actions shared by the desktop entries of a seed's apps
+///
+/// `flora_command` is the command that runs flora, such as `["flora"]`.
+pub(crate) fn seed_actions(
+    flora_command: &[String],
+    seed_name: &str,
+    prefix: &Path,
+    log: &Path,
+) -> Vec<FloraDesktopAction> {
+    let flora = |command: &str| {
+        let mut exec = flora_command.to_vec();
+        exec.push(String::from(command));
+        exec.push(String::from("--"));
+        exec.push(seed_name.to_string());
+
+        exec
+    };
+    let open = |path: &Path| vec![String::from("xdg-open"), path.to_string_lossy().to_string()];
+
+    vec![
+        FloraDesktopAction {
+            id: String::from("configure-prefix"),
+            name: String::from("Configure prefix"),
+            icon: Some(String::from("preferences-system")),
+            exec: flora("config"),
+        },
+        FloraDesktopAction {
+            id: String::from("winetricks"),
+            name: String::from("Winetricks"),
+            icon: Some(String::from("system-software-install")),
+            exec: flora("tricks"),
+        },
+        FloraDesktopAction {
+            id: String::from("open-prefix"),
+            name: String::from("Open prefix folder"),
+            icon: Some(String::from("folder-open")),
+            exec: open(prefix),
+        },
+        FloraDesktopAction {
+            id: String::from("kill-seed"),
+            name: String::from("Kill seed"),
+            icon: Some(String::from("process-stop")),
+            exec: flora("kill"),
+        },
+        FloraDesktopAction {
+            id: String::from("open-log"),
+            name: String::from("Open log"),
+            icon: Some(String::from("text-x-log")),
+            exec: open(log),
+        },
+    ]
+}
+
+/// Returns the window class of an app's windows, derived from its executable
+///
+/// Wine names the window class of a process after its executable file, in lowercase.
+pub(crate) fn startup_wm_class(exe_location: &str) -> Option<String> {
+    let exe_name = exe_location.rsplit(['\\', '/']).next()?.to_lowercase();
+
+    exe_name.ends_with(".exe").then_some(exe_name)
+}
+
+/// Creates the desktop entry of a seed's app, along with its icon
+///
+/// Only the runner's prefix and identity are used, so entries look the same for every runner. The
+/// entry follows the templates in the `desktop` section of `flora.toml`.
+pub(crate) fn create_desktop_entry(
+    dirs: &FloraDirs,
+    config: &FloraDesktopConfig,
+    runner: &dyn FloraRunner,
+    app: &FloraSeedApp,
+) -> Result<(), FloraError> {
+    let seed_name = runner.get_seed_name();
+    let prefix = runner.get_prefix();
+
+    // Get link path
+    let target_linux_path = winepath::windows_to_unix(prefix, &app.application_location);
+
+    let exe_find = match &app.shortcut {
+        Some(FloraSeedAppShortcut {
+            icon_location: Some(icon_location),
+            icon_index,
+            ..
+        }) => FloraLink::WindowsIco(icon_location.clone(), *icon_index),
+        _ => flora_icon::find_lnk_exe_location(&target_linux_path)?,
+    };
+
+    let startup_wm_class = match &exe_find {
+        FloraLink::LinuxExe(path) => startup_wm_class(&path.to_string_lossy()),
+        FloraLink::WindowsExe(path) => startup_wm_class(path),
+        _ => startup_wm_class(&app.application_location),
+    };
+
+    let icon_name = resolve_icon(dirs, prefix, seed_name, app, exe_find)?;
+
+    let fill = |template: &str| fill_template(template, app, runner);
+    let flora_command = config
+        .exec_command
+        .clone()
+        .unwrap_or_else(|| vec![String::from("flora")]);
+
+    // Create desktop entry files
+    let desktop_entry = FloraDesktopEntry {
+        name: app.application_name.clone(),
+        comment: Some(match &config.comment {
+            Some(comment) => fill(comment),
+            None => fill("Run {app} with Flora ({runner} seed {seed})"),
+        }),
+        icon: Some(icon_name),
+        exec: [
+            flora_command.clone(),
+            // Names starting with - would otherwise be read as options
+            ["run", "-a", "-w", "--", seed_name, &app.application_name]
+                .map(String::from)
+                .to_vec(),
+        ]
+        .concat(),
+        categories: desktop_categories(seed_name, app.category.as_deref()),
+        keywords: vec![
+            app.application_name.clone(),
+            seed_name.to_string(),
+            runner.get_runner_name().to_string(),
+        ],
+        startup_wm_class,
+        terminal: false,
+        actions: seed_actions(
+            &flora_command,
+            seed_name,
+            prefix,
+            &dirs.get_log_path(seed_name),
+        ),
+        extra_keys: config
+            .extra_keys
+            .iter()
+            .map(|(key, value)| (key.clone(), fill(value)))
+            .collect(),
+        ..Default::default()
+    }
+    .render()?;
+
+    let desktop_entry_location = dirs.get_desktop_entry_file(seed_name, &app.application_name);
+
+    debug!(
+        "Writing {} desktop entry to {}",
+        seed_name,
+        desktop_entry_location.to_string_lossy()
+    );
+
+    fs::write(&desktop_entry_location, &desktop_entry)
+        .map_err(|e| FloraError::DesktopEntryWriteError(desktop_entry_location, e))?;
+
+    Ok(())
+}
+
+/// Resolves the icon of an app's desktop entry, extracting it from its executable or icon file
+///
+/// Returns the path of the extracted icon, or an icon name from the icon theme.
+fn resolve_icon(
+    dirs: &FloraDirs,
+    prefix: &Path,
+    seed_name: &str,
+    app: &FloraSeedApp,
+    exe_find: FloraLink,
+) -> Result<String, FloraError> {
+    let icon_path = dirs.get_icon_file(seed_name, &app.application_name);
+    let mut icon_name = String::from("applications-other");
+
+    if let FloraLink::Other(location) = exe_find {
+        // Not an EXE or LNK, use other icon
+        icon_name = flora_icon::get_icon_name_from_path(&location)?;
+    } else if let FloraLink::WindowsIco(ico_path, icon_index) = exe_find {
+        let windows_ico_path = winepath::windows_to_unix(prefix, &ico_path);
+        debug!("We got icon from {}", &windows_ico_path.to_string_lossy());
+
+        if windows_ico_path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("ico"))
+        {
+            flora_icon::extract_icon_from_ico(&icon_path, &windows_ico_path)?;
+            icon_name = String::from(icon_path.to_string_lossy());
+        } else if flora_icon::extract_icon_from_exe(&icon_path, &windows_ico_path, icon_index)? {
+            icon_name = String::from(icon_path.to_string_lossy());
+        }
+    } else {
+        debug!("No icon location, search exe for icons");
+        let exe_location = match exe_find {
+            FloraLink::LinuxExe(path) => path,
+            FloraLink::WindowsExe(path) => winepath::windows_to_unix(prefix, &path),
+            _ => panic!("Windows ICO should be handled in the former case!"),
+        };
+
+        if flora_icon::extract_icon_from_exe(&icon_path, &exe_location, 0)? {
+            debug!("We got icon from {}", exe_location.to_string_lossy());
+            icon_name = String::from(icon_path.to_string_lossy());
+        };
+    }
+
+    Ok(icon_name)
+}
+
+/// Replaces `{app}`, `{seed}` and `{runner}` in a desktop entry template
+fn fill_template(template: &str, app: &FloraSeedApp, runner: &dyn FloraRunner) -> String {
+    template
+        .replace("{app}", &app.application_name)
+        .replace("{seed}", runner.get_seed_name())
+        .replace("{runner}", runner.get_runner_name())
+}
+
+/// MIME types of Windows executables, installers and shortcuts
+pub const WINDOWS_MIME_TYPES: &[&str] = &[
+    "application/x-ms-dos-executable",
+    "application/x-msi",
+    "application/x-ms-shortcut",
+];
+
+/// Creates the hidden desktop entry opening Windows programs with a seed
+///
+/// Returns the location of the desktop entry.
+pub(crate) fn create_mime_desktop_entry(
+    dirs: &FloraDirs,
+    config: &FloraDesktopConfig,
+    runner: &dyn FloraRunner,
+) -> Result<PathBuf, FloraError> {
+    let seed_name = runner.get_seed_name();
+    let mut exec = config
+        .exec_command
+        .clone()
+        .unwrap_or_else(|| vec![String::from("flora")]);
+    exec.extend(["run", "--", seed_name, "start", "/unix"].map(String::from));
+
+    let desktop_entry = FloraDesktopEntry {
+        name: format!("Flora ({})", seed_name),
+        comment: Some(format!(
+            "Open Windows programs with Flora ({} seed {})",
+            runner.get_runner_name(),
+            seed_name
+        )),
+        icon: Some(String::from("application-x-ms-dos-executable")),
+        exec,
+        exec_field_code: Some("%f"),
+        categories: desktop_categories(seed_name, None),
+        mime_types: WINDOWS_MIME_TYPES.iter().map(|m| m.to_string()).collect(),
+        terminal: false,
+        no_display: true,
+        ..Default::default()
+    }
+    .render()?;
+
+    let desktop_entry_location = dirs.get_mime_desktop_entry_file(seed_name);
+    debug!(
+        "Writing {} MIME desktop entry to {}",
+        seed_name,
+        desktop_entry_location.to_string_lossy()
+    );
+
+    fs::write(&desktop_entry_location, &desktop_entry)
+        .map_err(|e| FloraError::DesktopEntryWriteError(desktop_entry_location.clone(), e))?;
+
+    Ok(desktop_entry_location)
+}
+
+/// Makes a desktop entry the default application for Windows programs, using `xdg-mime`
+pub(crate) fn register_mime_handler(
+    dirs: &FloraDirs,
+    desktop_entry: &Path,
+) -> Result<(), FloraError> {
+    let desktop_file_id = desktop_file_id(dirs, desktop_entry)?;
+    debug!(
+        "Registering {} as the handler of Windows programs",
+        desktop_file_id
+    );
+
+    let status = match Command::new("xdg-mime")
+        .arg("default")
+        .arg(&desktop_file_id)
+        .args(WINDOWS_MIME_TYPES)
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(FloraError::MimeRegisterError(String::from(
+                "xdg-mime not found",
+            )));
+        }
+        Err(e) => return Err(FloraError::RunnerExecError(e)),
+    };
+    if !status.success() {
+        return Err(FloraError::MimeRegisterError(format!(
+            "xdg-mime exited with {}",
+            status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the desktop file ID of a desktop entry, its path relative to the applications directory
+/// with `/` replaced by `-`
+fn desktop_file_id(dirs: &FloraDirs, desktop_entry: &Path) -> Result<String, FloraError> {
+    let desktop_entry_root = dirs.get_desktop_entry_root();
+    let applications_dir = desktop_entry_root.parent().unwrap_or(&desktop_entry_root);
+
+    let relative_path = desktop_entry
+        .strip_prefix(applications_dir)
+        .map_err(|_| FloraError::MimeRegisterError(desktop_entry.display().to_string()))?;
+
+    Ok(relative_path.to_string_lossy().replace('/', "-"))
+}
+
+/// Returns the categories of an app's desktop entry
+///
+/// Entries are always in the Flora category and the seed's category, plus the app's own
+/// categories, separated by `;`, if it has any.
+pub(crate) fn desktop_categories(seed_name: &str, category: Option<&str>) -> Vec<String> {
+    let mut categories = vec![String::from(FLORA_CATEGORY), seed_category(seed_name)];
+    if let Some(category) = category {
+        categories.extend(
+            category
+                .split(';')
+                .filter(|category| !category.is_empty())
+                .map(String::from),
+        );
+    }
+
+    categories
+}
+
+/// Category of a seed's desktop entries, encoded like desktop file IDs so seeds never share one
+fn seed_category(seed_name: &str) -> String {
+    format!("{}-{}", FLORA_CATEGORY, desktop_id_part(seed_name))
+}
+
+fn hash_content(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Checks whether a file was written by flora and has not been edited since
+fn is_unmodified(file_content: &str) -> bool {
+    let Some((first_line, content)) = file_content.split_once('\n') else {
+        return false;
+    };
+    let Some((_, hash)) = first_line.split_once(GENERATED_MARKER) else {
+        return false;
+    };
+
+    hash.trim_end_matches("-->").trim() == hash_content(content)
+}
+
+/// Writes a generated file with a marker line holding its hash, unless the existing file was
+/// written by hand or edited since it was generated
+fn write_generated_file(path: &Path, comment: &str, content: &str) -> Result<(), FloraError> {
+    if fs::exists(path)? && !is_unmodified(&fs::read_to_string(path)?) {
+        info!("{} was edited by hand, not overwriting it", path.display());
+        return Ok(());
+    }
+
+    debug!("Writing {}", path.display());
+    let marker = match comment {
+        "<!--" => format!("<!-- {} {} -->", GENERATED_MARKER, hash_content(content)),
+        _ => format!("{} {} {}", comment, GENERATED_MARKER, hash_content(content)),
+    };
+    fs::write(path, format!("{}\n{}", marker, content))?;
+
+    Ok(())
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes the Flora menu, with a submenu for each of the given seeds
+pub(crate) fn initialize_desktop_entries(
+    dirs: &FloraDirs,
+    seed_names: &[String],
+) -> Result<(), FloraError> {
     dirs.create_desktop_dirs()?;
 
-    // let flora_directory = "[Desktop Entry]
-    // Type=Directory
-    // Name=Flora
-    // Icon=flower-shape
-    // ";
-    // let flora_menu = r#"<!DOCTYPE Menu PUBLIC "-//freedesktop//DTD Menu 1.0//EN"
-    // "http://www.freedesktop.org/standards/menu-spec/menu-1.0.dtd">
-    // <Menu>
-    // <Name>Applications</Name>
-    // <Menu>
-    // <Name>flora</Name>
-    // <Directory>flora.directory</Directory>
-    // <Include>
-    // <Category>X-Flora</Category>
-    // </Include>
-    // </Menu>
-    // </Menu>
-    // "#;
-    // let directory_file = dirs.get_desktop_directory_file();
-    // debug!(
-    //     "Writing directory entry to {}",
-    //     directory_file.to_string_lossy()
-    // );
-
-    // if !fs::exists(&directory_file)? {
-    //     fs::write(directory_file, flora_directory).map(|_| ())?;
-    // }
-
-    // let menu_file = dirs.get_desktop_menu_file();
-    // debug!("Writing menu entry to {}", menu_file.to_string_lossy());
-
-    // if !fs::exists(&menu_file)? {
-    //     fs::write(menu_file, flora_menu).map(|_| ())?;
-    // }
+    let flora_directory = FloraDesktopEntry {
+        name: String::from("Flora"),
+        icon: Some(String::from("flower-shape")),
+        directory: true,
+        ..Default::default()
+    };
+    write_generated_file(
+        &dirs.get_desktop_directory_file(None),
+        "#",
+        &flora_directory.render()?,
+    )?;
+
+    let mut seed_menus = String::new();
+    for seed_name in seed_names {
+        let seed_directory = FloraDesktopEntry {
+            name: seed_name.clone(),
+            icon: Some(String::from("folder")),
+            directory: true,
+            ..Default::default()
+        };
+        let seed_directory_file = dirs.get_desktop_directory_file(Some(seed_name));
+        write_generated_file(&seed_directory_file, "#", &seed_directory.render()?)?;
+
+        seed_menus.push_str(&format!(
+            "    <Menu>
+      <Name>{}</Name>
+      <Directory>{}</Directory>
+      <Include>
+        <Category>{}</Category>
+      </Include>
+    </Menu>
+",
+            escape_xml(seed_name),
+            escape_xml(&seed_directory_file.file_name().unwrap().to_string_lossy()),
+            seed_category(seed_name)
+        ));
+    }
+
+    // With submenus, the Flora menu only shows apps not in a seed's submenu
+    let flora_include = match seed_names.is_empty() {
+        true => format!("<Category>{}</Category>", FLORA_CATEGORY),
+        false => format!(
+            "<And>
+        <Category>{}</Category>
+        <Not>
+          <Or>
+{}          </Or>
+        </Not>
+      </And>",
+            FLORA_CATEGORY,
+            seed_names
+                .iter()
+                .map(|seed_name| format!(
+                    "            <Category>{}</Category>\n",
+                    seed_category(seed_name)
+                ))
+                .collect::<String>()
+        ),
+    };
+    let flora_menu = format!(
+        r#"<!DOCTYPE Menu PUBLIC "-//freedesktop//DTD Menu 1.0//EN"
+ "http://www.freedesktop.org/standards/menu-spec/menu-1.0.dtd">
+<Menu>
+  <Name>Applications</Name>
+  <Menu>
+    <Name>Flora</Name>
+    <Directory>{}</Directory>
+    <Include>
+      {}
+    </Include>
+{}  </Menu>
+</Menu>
+"#,
+        escape_xml(
+            &dirs
+                .get_desktop_directory_file(None)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+        ),
+        flora_include,
+        seed_menus
+    );
+    write_generated_file(&dirs.get_desktop_menu_file(), "<!--", &flora_menu)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::desktop::{
+        DESKTOP_ID_SEPARATOR, FloraDesktopAction, FloraDesktopEntry, WINDOWS_MIME_TYPES,
+        desktop_categories, desktop_id, desktop_id_part, hash_content, is_unmodified,
+        startup_wm_class,
+    };
+
+    #[test]
+    fn generated_marker() {
+        let content = "[Desktop Entry]\nName=Flora\n";
+        let generated = format!("# flora-generated: {}\n{}", hash_content(content), content);
+        let generated_xml = format!(
+            "<!-- flora-generated: {} -->\n{}",
+            hash_content(content),
+            content
+        );
+
+        assert!(is_unmodified(&generated));
+        assert!(is_unmodified(&generated_xml));
+        assert!(!is_unmodified(&generated.replace("Flora", "Wine")));
+        assert!(!is_unmodified(content));
+    }
+
+    #[test]
+    fn categories() {
+        assert_eq!(
+            desktop_categories("my seed", None),
+            vec!["X-Flora", "X-Flora-my_20seed"]
+        );
+        assert_ne!(
+            desktop_categories("my-seed", None),
+            desktop_categories("my seed", None)
+        );
+        assert_eq!(
+            desktop_categories("games", Some("Game;Emulator;")),
+            vec!["X-Flora", "X-Flora-games", "Game", "Emulator"]
+        );
+    }
+
+    #[test]
+    fn hostile_names() {
+        let entry = FloraDesktopEntry {
+            name: String::from("Evil\"; rm -rf ~ \\ 100%\nExec=sh"),
+            exec: vec![
+                String::from("flora"),
+                String::from("run"),
+                String::from("$(seed)"),
+                String::from("Evil\"; rm -rf ~ \\ 100%\nExec=sh"),
+            ],
+            categories: vec![String::from("A;B")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry.render().unwrap(),
+            concat!(
+                "[Desktop Entry]\n",
+                "Type=Application\n",
+                "Name=Evil\"; rm -rf ~ \\\\ 100%\\nExec=sh\n",
+                "Exec=flora run \"\\\\$(seed)\" ",
+                "\"Evil\\\\\"; rm -rf ~ \\\\\\\\ 100%%\\nExec=sh\"\n",
+                "Categories=A\\;B;\n",
+                "Terminal=false\n",
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_entries() {
+        let entry = FloraDesktopEntry {
+            name: String::from("App"),
+            exec: vec![String::from("WINEDEBUG=-all"), String::from("wine")],
+            ..Default::default()
+        };
+        assert!(entry.render().is_err());
+
+        let entry = FloraDesktopEntry {
+            exec: vec![String::from("flora")],
+            ..Default::default()
+        };
+        assert!(entry.render().is_err());
+    }
+
+    #[test]
+    fn actions() {
+        let entry = FloraDesktopEntry {
+            name: String::from("App"),
+            exec: vec![String::from("flora")],
+            startup_wm_class: startup_wm_class("C:\\Program Files\\App\\App.EXE"),
+            actions: vec![FloraDesktopAction {
+                id: String::from("open-log"),
+                name: String::from("Open log"),
+                icon: None,
+                exec: vec![String::from("xdg-open"), String::from("/logs/my seed.log")],
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry.render().unwrap(),
+            concat!(
+                "[Desktop Entry]\n",
+                "Type=Application\n",
+                "Name=App\n",
+                "Exec=flora\n",
+                "StartupWMClass=app.exe\n",
+                "Terminal=false\n",
+                "Actions=open-log;\n",
+                "\n",
+                "[Desktop Action open-log]\n",
+                "Name=Open log\n",
+                "Exec=xdg-open \"/logs/my seed.log\"\n",
+            )
+        );
+        assert_eq!(startup_wm_class("setup.msi"), None);
+
+        let mut invalid_entry = entry.clone();
+        invalid_entry.actions[0].id = String::from("open log");
+        assert!(invalid_entry.render().is_err());
+    }
+
+    #[test]
+    fn extra_keys() {
+        let entry = FloraDesktopEntry {
+            name: String::from("App"),
+            exec: vec![String::from("flatpak"), String::from("run")],
+            extra_keys: [
+                ("X-Flatpak", "com.example.App"),
+                ("Comment[pt_BR]", "Executar\tApp"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry.render().unwrap(),
+            concat!(
+                "[Desktop Entry]\n",
+                "Type=Application\n",
+                "Name=App\n",
+                "Exec=flatpak run\n",
+                "Terminal=false\n",
+                "Comment[pt_BR]=Executar\\tApp\n",
+                "X-Flatpak=com.example.App\n",
+            )
+        );
+
+        for key in ["Exec", "Bad Key", "Name[]", "Name[pt"] {
+            let mut invalid_entry = entry.clone();
+            invalid_entry
+                .extra_keys
+                .insert(key.to_string(), String::from("value"));
+            assert!(invalid_entry.render().is_err(), "{} is accepted", key);
+        }
+    }
+
+    #[test]
+    fn mime_entries() {
+        let entry = FloraDesktopEntry {
+            name: String::from("Flora (my seed)"),
+            exec: ["flora", "run", "--", "my seed", "start", "/unix"]
+                .map(String::from)
+                .to_vec(),
+            exec_field_code: Some("%f"),
+            mime_types: WINDOWS_MIME_TYPES.iter().map(|m| m.to_string()).collect(),
+            no_display: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry.render().unwrap(),
+            concat!(
+                "[Desktop Entry]\n",
+                "Type=Application\n",
+                "Name=Flora (my seed)\n",
+                "Exec=flora run -- \"my seed\" start /unix %f\n",
+                "MimeType=application/x-ms-dos-executable;application/x-msi;application/x-ms-shortcut;\n",
+                "Terminal=false\n",
+                "NoDisplay=true\n",
+            )
+        );
+    }
+
+    #[test]
+    fn desktop_ids() {
+        assert_eq!(desktop_id_part("Game-1"), "Game-1");
+        assert_eq!(desktop_id_part("My App"), "My_20App");
+        assert_eq!(desktop_id_part("a_b"), "a_5fb");
+        assert_eq!(desktop_id_part("../x"), "_2e_2e_2fx");
+        assert_eq!(desktop_id_part("é"), "_c3_a9");
+        assert_eq!(desktop_id("My Seed", "App"), "My_20Seed__App");
+        assert_ne!(desktop_id("x ", "5fy"), desktop_id("x", "20_y"));
+        assert_ne!(desktop_id("a_", "b"), desktop_id("a", "_b"));
+        // MIME entries are named after the seed alone
+        assert!(!desktop_id_part("my__seed").contains(DESKTOP_ID_SEPARATOR));
+    }
+
+    #[test]
+    fn directory_entries() {
+        let entry = FloraDesktopEntry {
+            name: String::from(" my\\seed\nName=x"),
+            icon: Some(String::from("folder")),
+            directory: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            entry.render().unwrap(),
+            concat!(
+                "[Desktop Entry]\n",
+                "Type=Directory\n",
+                "Name=\\smy\\\\seed\\nName=x\n",
+                "Icon=folder\n",
+            )
+        );
+    }
+}
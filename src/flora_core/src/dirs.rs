@@ -5,7 +5,7 @@ use std::{
 
 use directories::BaseDirs;
 
-use crate::errors::FloraError;
+use crate::{desktop, errors::FloraError};
 
 pub struct FloraDirs {
     pub flora_root: PathBuf,
@@ -49,12 +49,24 @@ impl FloraDirs {
 
         log_root
     }
+    pub fn get_process_root(&self) -> PathBuf {
+        let mut process_root = self.flora_root.clone();
+        process_root.push("processes");
+
+        process_root
+    }
     pub fn get_prefixes_root(&self) -> PathBuf {
         let mut wine_root = self.flora_root.clone();
         wine_root.push("prefixes");
 
         wine_root
     }
+    pub fn get_snapshot_root(&self) -> PathBuf {
+        let mut snapshot_root = self.flora_root.clone();
+        snapshot_root.push("snapshots");
+
+        snapshot_root
+    }
     pub fn get_icons_root(&self) -> PathBuf {
         let mut wine_root = self.flora_root.clone();
         wine_root.push("icons");
@@ -74,25 +86,65 @@ impl FloraDirs {
 
         wine_root
     }
+    pub fn get_log_path(&self, name: &str) -> PathBuf {
+        let mut log_path = self.get_log_root();
+        log_path.push(format!("{}.log", name));
+
+        log_path
+    }
     pub fn get_log_file(&self, name: &str) -> Result<File, FloraError> {
-        let mut log_file = self.get_log_root();
-        log_file.push(format!("{}.log", name));
+        let log_file = self.get_log_path(name);
         log::debug!("Logging outputs to {}", log_file.clone().to_string_lossy(),);
 
         let log_file = File::options().append(true).create(true).open(log_file)?;
 
         Ok(log_file)
     }
+    pub fn get_desktop_entry_root(&self) -> PathBuf {
+        self.applications_entry_dir.clone()
+    }
+    /// Returns the directory entry of the Flora menu, or of a seed's submenu
+    pub fn get_desktop_directory_file(&self, seed_name: Option<&str>) -> PathBuf {
+        let mut directory_file = self.applications_directory_dir.clone();
+        match seed_name {
+            Some(seed_name) => directory_file.push(format!(
+                "flora-{}.directory",
+                desktop::desktop_id_part(seed_name)
+            )),
+            None => directory_file.push("flora.directory"),
+        }
+
+        directory_file
+    }
+    pub fn get_desktop_menu_file(&self) -> PathBuf {
+        let mut menu_file = self.config_menu_dir.clone();
+        menu_file.push("flora.menu");
+
+        menu_file
+    }
     pub fn get_desktop_entry_file(&self, name: &str, application_name: &str) -> PathBuf {
         let mut desktop_entry_location = self.applications_entry_dir.clone();
-        desktop_entry_location.push(format!("{}_{}.desktop", name, application_name));
+        desktop_entry_location.push(format!(
+            "{}.desktop",
+            desktop::desktop_id(name, application_name)
+        ));
+
+        desktop_entry_location
+    }
+
+    /// Returns the desktop entry opening Windows programs with a seed. Its ID is the encoded seed
+    /// name alone, which never contains the separator of app entries, so it never shares a file
+    /// with an app entry of any seed.
+    pub fn get_mime_desktop_entry_file(&self, name: &str) -> PathBuf {
+        let mut desktop_entry_location = self.applications_entry_dir.clone();
+        desktop_entry_location.push(format!("{}.desktop", desktop::desktop_id_part(name)));
 
         desktop_entry_location
     }
 
     pub fn get_icon_file(&self, name: &str, app_name: &str) -> PathBuf {
         let mut icon_path = self.get_icons_root();
-        icon_path.push(format!("{}_{}.png", name, app_name));
+        icon_path.push(format!("{}.png", desktop::desktop_id(name, app_name)));
 
         icon_path
     }
@@ -104,6 +156,7 @@ impl FloraDirs {
         fs::create_dir_all(self.get_wine_root())?;
         fs::create_dir_all(self.get_proton_root())?;
         fs::create_dir_all(self.get_log_root())?;
+        fs::create_dir_all(self.get_process_root())?;
         fs::create_dir_all(self.get_prefixes_root())?;
         fs::create_dir_all(self.get_icons_root())?;
 
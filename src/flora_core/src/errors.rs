@@ -8,14 +8,62 @@ pub enum FloraError {
     // Start Menu related errors
     #[error("Unable to find start menu location for {0}")]
     StartMenuLocationNotFound(String),
+    #[error("Invalid desktop entry: {0}")]
+    InvalidDesktopEntry(String),
     #[error("Unable to write desktop entry {0} to {1}")]
     DesktopEntryWriteError(PathBuf, std::io::Error),
+    #[error("Unable to register MIME handler: {0}")]
+    MimeRegisterError(String),
     #[error("Unable to extract icon: {0}")]
     IconExtractionError(#[from] FloraLinkError),
     #[error("Unable to execute in runner: {0}")]
     RunnerExecError(std::io::Error),
     #[error("Unable to parse launcher command: {0}")]
     IncorrectLauncherCommand(String),
+    #[error("Unable to parse registry at line {0}: {1}")]
+    RegistryParseError(usize, String),
+    #[error("Invalid registry key: {0}")]
+    InvalidRegistryKey(String),
+    #[error("Registry key not found: {0}")]
+    RegistryKeyNotFound(String),
+    #[error("Registry value not found: {0}")]
+    RegistryValueNotFound(String),
+    #[error("Registry hive not found: {0}")]
+    RegistryHiveNotFound(PathBuf),
+    #[error("Refusing to write the registry while wineserver is running for prefix {0}")]
+    WineserverRunning(PathBuf),
+    #[error("Invalid DLL name: {0}")]
+    InvalidDllName(String),
+    #[error("DLL override not found: {0}")]
+    DllOverrideNotFound(String),
+    #[error("Wrapper preset not found: {0}")]
+    WrapperPresetNotFound(String),
+    #[error("Unable to parse arguments: {0}")]
+    IncorrectArguments(String),
+    #[error("Invalid seed archive: {0}")]
+    InvalidArchive(String),
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(u32),
+    #[error("Snapshot {0} is corrupted, missing data for {1}")]
+    SnapshotCorrupted(u32, PathBuf),
+    #[error("Seed {0} has running processes")]
+    SeedRunning(String),
+    #[error("Prefix not found: {0}")]
+    PrefixNotFound(PathBuf),
+    #[error("Prefix already exists: {0}")]
+    PrefixExists(PathBuf),
+    #[error("Prefix {0} is still used by seed {1}")]
+    PrefixInUse(PathBuf, String),
+    #[error("Prefix {0} cannot be moved to {1}, which is on another filesystem")]
+    PrefixCrossesFilesystems(PathBuf, PathBuf),
+    #[error("Prefix {0} is not named after seed {1}")]
+    PrefixNotNamedAfterSeed(PathBuf, String),
+    #[error("Unable to initialize prefix of seed {0}: {1}")]
+    PrefixInitFailed(String, String),
+    #[error("Hook {0} failed: {1}")]
+    HookFailed(String, String),
+    #[error("Unable to signal process {0}: {1}")]
+    ProcessSignalError(u32, nix::errno::Errno),
 
     #[error("Unable to access file: {0}")]
     FileAccessError(#[from] std::io::Error),
@@ -31,6 +79,8 @@ pub enum FloraError {
 
     #[error("Seed not found: {0}")]
     SeedNotFound(String),
+    #[error("Invalid seed name: {0}")]
+    InvalidSeedName(String),
     #[error("Seed already exists: {0}")]
     SeedExists(String),
     #[error("Seed not of the correct type")]
@@ -0,0 +1,195 @@
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use walkdir::WalkDir;
+
+use crate::{errors::FloraError, prefix};
+
+/// Entries every Wine prefix has, so other directories in the prefix location are left alone
+const PREFIX_ENTRIES: [&str; 3] = ["system.reg", "user.reg", "drive_c"];
+/// Marks the staging paths that imports, restores and registry writes place next to their target
+const STAGING_MARKER: &str = ".flora-";
+
+/// Kinds of artifacts left behind by deleted or renamed seeds and apps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FloraGarbageKind {
+    DesktopEntry,
+    Icon,
+    Prefix,
+    Log,
+    /// Snapshots of a deleted seed
+    Snapshots,
+}
+
+impl fmt::Display for FloraGarbageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FloraGarbageKind::DesktopEntry => write!(f, "desktop entry"),
+            FloraGarbageKind::Icon => write!(f, "icon"),
+            FloraGarbageKind::Prefix => write!(f, "prefix"),
+            FloraGarbageKind::Log => write!(f, "log"),
+            FloraGarbageKind::Snapshots => write!(f, "snapshots"),
+        }
+    }
+}
+
+/// An artifact no seed references anymore
+#[derive(Debug, Clone)]
+pub struct FloraGarbage {
+    pub kind: FloraGarbageKind,
+    pub path: PathBuf,
+    /// Size on disk, including the contents of directories
+    pub size: u64,
+    /// Whether the artifact was removed, or only reported
+    pub removed: bool,
+}
+
+/// Finds the entries of a directory that are not referenced
+///
+/// Prefixes and snapshots are directories, every other kind is a file with the given extension.
+/// Directories only count as prefixes if they contain the files of a Wine prefix, and staging
+/// paths of operations in progress are always skipped. Paths are compared after resolving symlinks, so a referenced path is matched however it is
+/// written.
+pub(crate) fn find_unreferenced(
+    dir: &Path,
+    kind: FloraGarbageKind,
+    extension: Option<&str>,
+    referenced: &HashSet<PathBuf>,
+) -> Result<Vec<FloraGarbage>, FloraError> {
+    if !fs::exists(dir)? {
+        return Ok(Vec::new());
+    }
+
+    let referenced: HashSet<_> = referenced
+        .iter()
+        .map(|path| prefix::canonical_path(path))
+        .collect();
+
+    let mut garbage = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if entry.file_name().to_string_lossy().contains(STAGING_MARKER) {
+            continue;
+        }
+
+        let is_candidate = match kind {
+            FloraGarbageKind::Prefix => {
+                file_type.is_dir()
+                    && PREFIX_ENTRIES
+                        .iter()
+                        .all(|prefix_entry| path.join(prefix_entry).exists())
+            }
+            FloraGarbageKind::Snapshots => file_type.is_dir(),
+            _ => {
+                file_type.is_file()
+                    && path.extension().is_some_and(|path_extension| {
+                        Some(path_extension) == extension.map(AsRef::as_ref)
+                    })
+            }
+        };
+        if !is_candidate || referenced.contains(&prefix::canonical_path(&path)) {
+            continue;
+        }
+
+        debug!("Found unreferenced {} {}", kind, path.display());
+        garbage.push(FloraGarbage {
+            kind,
+            size: path_size(&path)?,
+            path,
+            removed: false,
+        });
+    }
+    garbage.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(garbage)
+}
+
+fn path_size(path: &Path) -> Result<u64, FloraError> {
+    let mut size = 0;
+    for entry in WalkDir::new(path).follow_links(false) {
+        let entry = entry.map_err(|e| FloraError::FileAccessError(e.into()))?;
+        if entry.file_type().is_file() {
+            size += entry.metadata().map_err(io::Error::from)?.len();
+        }
+    }
+
+    Ok(size)
+}
+
+/// Removes an unreferenced artifact
+///
+/// Snapshots are removed through the snapshot store instead, which also frees their data.
+pub(crate) fn remove(garbage: &mut FloraGarbage) -> Result<(), FloraError> {
+    debug!("Removing {} {}", garbage.kind, garbage.path.display());
+
+    match garbage.kind {
+        FloraGarbageKind::Prefix | FloraGarbageKind::Snapshots => {
+            fs::remove_dir_all(&garbage.path)?
+        }
+        _ => fs::remove_file(&garbage.path)?,
+    }
+    garbage.removed = true;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, os::unix::fs::symlink};
+
+    use crate::gc::{self, FloraGarbageKind};
+
+    #[test]
+    fn unreferenced_prefixes() {
+        let root = tempfile::tempdir().unwrap();
+        let prefix_location = root.path().join("prefixes");
+        for name in ["referenced", "unreferenced", "unreferenced.flora-restore"] {
+            fs::create_dir_all(prefix_location.join(name).join("drive_c")).unwrap();
+            fs::write(prefix_location.join(name).join("system.reg"), "WINE").unwrap();
+            fs::write(prefix_location.join(name).join("user.reg"), "WINE").unwrap();
+        }
+        // Seeds may reference a prefix through a symlink or an unnormalized path
+        symlink(&prefix_location, root.path().join("link")).unwrap();
+        let referenced = HashSet::from([root.path().join("link/./referenced")]);
+
+        let mut garbage = gc::find_unreferenced(
+            &prefix_location,
+            FloraGarbageKind::Prefix,
+            None,
+            &referenced,
+        )
+        .unwrap();
+        assert_eq!(garbage.len(), 1);
+        assert_eq!(garbage[0].path, prefix_location.join("unreferenced"));
+        assert_eq!(garbage[0].size, 8);
+
+        gc::remove(&mut garbage[0]).unwrap();
+        assert!(garbage[0].removed);
+        assert!(!fs::exists(prefix_location.join("unreferenced")).unwrap());
+        assert!(fs::exists(prefix_location.join("referenced/drive_c")).unwrap());
+    }
+
+    #[test]
+    fn non_prefix_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let prefix_location = root.path().join("prefixes");
+        fs::create_dir_all(prefix_location.join("Games/drive_c")).unwrap();
+        fs::write(prefix_location.join("Games/save.dat"), "SAVE").unwrap();
+
+        let garbage = gc::find_unreferenced(
+            &prefix_location,
+            FloraGarbageKind::Prefix,
+            None,
+            &HashSet::new(),
+        )
+        .unwrap();
+        assert!(garbage.is_empty());
+    }
+}
@@ -25,6 +25,34 @@ pub mod start_menu;
 /// Contains functions related to Flora seeds. Flora seeds are a single Wine or Proton configuration, with its own WINEPREFIX.
 pub mod seed;
 
+/// Flora process registry
+///
+/// Tracks processes launched in seeds
+pub mod process;
+
+/// Wine registry editing
+///
+/// Reads and writes the registry hives of a seed's prefix
+pub mod registry;
+
+/// Prefix copying and relocation
+mod prefix;
+
+/// Prefix snapshots
+///
+/// Stores compressed, deduplicated copies of seed prefixes
+pub mod snapshot;
+
+/// Seed archives
+///
+/// Bundles a seed with its prefix and icons, to move it to another machine
+pub mod archive;
+
+/// Garbage collection
+///
+/// Finds artifacts left behind by deleted or renamed seeds
+pub mod gc;
+
 /// Flora runners
 /// Contains functionality to launch seeds
 mod runners;
@@ -1,19 +1,28 @@
 use std::{
+    collections::{BTreeMap, HashSet},
     fs::{self, read_dir},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use directories::ProjectDirs;
 use log::debug;
+use nix::errno::Errno;
 
 use crate::{
+    archive::{self, FloraSeedArchiveManifest},
     config::FloraConfig,
     desktop,
     dirs::FloraDirs,
     errors::FloraError,
+    gc::{self, FloraGarbage, FloraGarbageKind},
+    prefix,
+    process::{self, FloraProcess, FloraRunResult, FloraWaitMode},
+    registry::{self, FloraRegistry, FloraRegistryData, FloraRegistryKeyPath, FloraRegistryRoot},
     runners,
-    seed::{FloraSeed, FloraSeedApp, FloraSeedType},
-    start_menu::FloraSeedStartMenuItem,
+    seed::{self, FloraSeed, FloraSeedApp, FloraSeedType},
+    snapshot::{self, FloraSnapshot},
+    start_menu::{self, FloraSeedStartMenuItem},
 };
 
 /// Manages Flora seeds configurations
@@ -22,6 +31,17 @@ pub struct FloraManager {
     config: Box<FloraConfig>,
 }
 
+/// Steps of a seed rename to undo if a later step fails
+#[derive(Default)]
+struct FloraRenameUndo {
+    /// Old and new prefix location
+    prefix: Option<(PathBuf, PathBuf)>,
+    /// Old and new location of moved files
+    moved_files: Vec<(PathBuf, PathBuf)>,
+    snapshots: bool,
+    desktop_entries: Vec<PathBuf>,
+}
+
 // Instance functions
 impl FloraManager {
     fn seed_path(&self, name: &str) -> PathBuf {
@@ -49,6 +69,7 @@ impl FloraManager {
 
     /// Creates a new Flora seed
     pub fn create_seed(&self, name: &str, new_seed: &FloraSeed) -> Result<(), FloraError> {
+        seed::validate_seed_name(name)?;
         if self.is_seed_exists(name)? {
             return Err(FloraError::SeedExists(name.to_string()));
         }
@@ -94,7 +115,7 @@ impl FloraManager {
         let seed = self.read_seed(name)?;
 
         let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
-        runner.list_start_menu_entries()
+        start_menu::list_start_menu_entries(runner.get_prefix(), &runner.get_prefix_user())
     }
 
     /// Creates an app for seed from Start Menu item
@@ -105,14 +126,16 @@ impl FloraManager {
 
         let seed = self.read_seed(name)?;
         let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
-        let start_menu_location = runner.get_start_menu_entry_location(menu_name)?;
+        let start_menu_location = start_menu::get_start_menu_entry_location(
+            runner.get_prefix(),
+            &runner.get_prefix_user(),
+            menu_name,
+        )?;
+        let app =
+            start_menu::resolve_start_menu_app(runner.get_prefix(), menu_name, start_menu_location);
 
         let mut upd_seed = seed.clone();
-        upd_seed.add_app(FloraSeedApp {
-            application_name: menu_name.to_string(),
-            application_location: start_menu_location,
-            category: None,
-        })?;
+        upd_seed.add_app(app)?;
 
         self.update_seed(name, &upd_seed)
     }
@@ -132,6 +155,689 @@ impl FloraManager {
         Ok(())
     }
 
+    /// Deletes a seed along with its desktop entries, icons, log and snapshots
+    ///
+    /// With `delete_prefix`, the prefix is deleted as well, unless another seed uses it. Returns
+    /// what was removed.
+    pub fn purge_seed(
+        &self,
+        name: &str,
+        delete_prefix: bool,
+    ) -> Result<FloraSeedPurgeReport, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+        if process::list(&self.flora_dirs)?
+            .iter()
+            .any(|process| process.seed_name == name)
+        {
+            return Err(FloraError::SeedRunning(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let prefix = match delete_prefix {
+            true => Some(self.get_seed_prefix(name)?),
+            false => None,
+        };
+        if let Some(prefix) = &prefix {
+            let canonical_prefix = prefix::canonical_path(prefix);
+            for other_seed in self.list_seed()? {
+                if other_seed.seed_name == name {
+                    continue;
+                }
+                let other_prefix = self
+                    .read_seed(&other_seed.seed_name)
+                    .map(|seed| self.resolve_seed_prefix(&seed))?;
+                if other_prefix.is_some_and(|other_prefix| {
+                    prefix::canonical_path(&other_prefix) == canonical_prefix
+                }) {
+                    return Err(FloraError::PrefixInUse(
+                        prefix.clone(),
+                        other_seed.seed_name,
+                    ));
+                }
+            }
+            if registry::is_wineserver_running(prefix) {
+                return Err(FloraError::WineserverRunning(prefix.clone()));
+            }
+        }
+
+        let mut report = FloraSeedPurgeReport {
+            seed_file: self.seed_path(name),
+            ..Default::default()
+        };
+        for app in seed.get_apps() {
+            let desktop_entry = self
+                .flora_dirs
+                .get_desktop_entry_file(name, &app.application_name);
+            if fs::exists(&desktop_entry)? {
+                debug!("Deleting desktop entry {}", desktop_entry.to_string_lossy());
+                fs::remove_file(&desktop_entry)?;
+                report.desktop_entries.push(desktop_entry);
+            }
+
+            let icon = self.flora_dirs.get_icon_file(name, &app.application_name);
+            if fs::exists(&icon)? {
+                debug!("Deleting icon {}", icon.to_string_lossy());
+                fs::remove_file(&icon)?;
+                report.icons.push(icon);
+            }
+        }
+
+        let mime_desktop_entry = self.flora_dirs.get_mime_desktop_entry_file(name);
+        if fs::exists(&mime_desktop_entry)? {
+            debug!(
+                "Deleting MIME desktop entry {}",
+                mime_desktop_entry.to_string_lossy()
+            );
+            fs::remove_file(&mime_desktop_entry)?;
+            report.desktop_entries.push(mime_desktop_entry);
+        }
+
+        let log = self.flora_dirs.get_log_path(name);
+        if fs::exists(&log)? {
+            debug!("Deleting log {}", log.to_string_lossy());
+            fs::remove_file(&log)?;
+            report.log = Some(log);
+        }
+
+        if let Some(prefix) = prefix
+            && fs::exists(&prefix)?
+        {
+            debug!("Deleting prefix {}", prefix.to_string_lossy());
+            fs::remove_dir_all(&prefix)?;
+            report.prefix = Some(prefix);
+        }
+
+        report.snapshots = snapshot::delete_seed(&self.flora_dirs, name)?;
+
+        // The seed goes last, so an interrupted purge can be run again
+        self.delete_seed(name)?;
+
+        Ok(report)
+    }
+
+    /// Returns the location of a seed's prefix
+    pub fn get_seed_prefix(&self, name: &str) -> Result<PathBuf, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        self.resolve_seed_prefix(&seed)
+            .ok_or(FloraError::RunnerNone)
+    }
+
+    /// Resolves a seed's prefix without creating a runner, so a missing runtime does not matter
+    fn resolve_seed_prefix(&self, seed: &FloraSeed) -> Option<PathBuf> {
+        match &seed.seed_type {
+            FloraSeedType::Wine(wine_seed) => Some(runners::resolve_prefix(
+                wine_seed.wine_prefix.as_deref(),
+                &self.config.wine.wine_prefix_location,
+                &self.config.wine.default_wine_prefix,
+            )),
+            FloraSeedType::Proton(proton_seed) => Some(runners::resolve_prefix(
+                proton_seed.proton_prefix.as_deref(),
+                &self.config.proton.proton_prefix_location,
+                &self.config.proton.default_proton_prefix,
+            )),
+            FloraSeedType::None => None,
+        }
+    }
+
+    /// Finds desktop entries, icons, prefixes, logs and snapshots that no seed references, and
+    /// removes them unless `dry_run` is set
+    ///
+    /// Prefixes are only reported, unless `remove_prefixes` is set as well. Only prefixes directly
+    /// inside the configured prefix locations are considered, and prefixes with a running
+    /// wineserver are kept.
+    pub fn collect_garbage(
+        &self,
+        dry_run: bool,
+        remove_prefixes: bool,
+    ) -> Result<Vec<FloraGarbage>, FloraError> {
+        let (desktop_entries, icons) = self.referenced_app_files()?;
+        let mut logs = HashSet::new();
+        let mut snapshots = HashSet::new();
+        // Default prefixes are used by any seed created without a prefix
+        let mut prefixes = HashSet::from([
+            PathBuf::from(&self.config.wine.default_wine_prefix),
+            PathBuf::from(&self.config.proton.default_proton_prefix),
+        ]);
+
+        for seed_item in self.list_seed()? {
+            let name = &seed_item.seed_name;
+            let seed = self.read_seed(name)?;
+
+            logs.insert(self.flora_dirs.get_log_path(name));
+            snapshots.insert(snapshot::seed_snapshot_dir(&self.flora_dirs, name));
+            if let Some(prefix) = self.resolve_seed_prefix(&seed) {
+                // A prefix nested in a subdirectory keeps the subdirectory
+                prefixes.extend(
+                    prefix::canonical_path(&prefix)
+                        .ancestors()
+                        .map(Path::to_path_buf),
+                );
+            }
+        }
+
+        let mut garbage = Vec::new();
+        garbage.extend(gc::find_unreferenced(
+            &self.flora_dirs.get_desktop_entry_root(),
+            FloraGarbageKind::DesktopEntry,
+            Some("desktop"),
+            &desktop_entries,
+        )?);
+        garbage.extend(gc::find_unreferenced(
+            &self.flora_dirs.get_icons_root(),
+            FloraGarbageKind::Icon,
+            Some("png"),
+            &icons,
+        )?);
+
+        let mut prefix_locations = vec![PathBuf::from(&self.config.wine.wine_prefix_location)];
+        let proton_prefix_location = PathBuf::from(&self.config.proton.proton_prefix_location);
+        if !prefix_locations.contains(&proton_prefix_location) {
+            prefix_locations.push(proton_prefix_location);
+        }
+        for prefix_location in prefix_locations {
+            let unreferenced_prefixes =
+                gc::find_unreferenced(&prefix_location, FloraGarbageKind::Prefix, None, &prefixes)?;
+            garbage.extend(unreferenced_prefixes.into_iter().filter(|prefix| {
+                let running = registry::is_wineserver_running(&prefix.path);
+                if running {
+                    debug!(
+                        "Keeping prefix {}, wineserver is running",
+                        prefix.path.display()
+                    );
+                }
+                !running
+            }));
+        }
+
+        garbage.extend(gc::find_unreferenced(
+            &self.flora_dirs.get_log_root(),
+            FloraGarbageKind::Log,
+            Some("log"),
+            &logs,
+        )?);
+        garbage.extend(gc::find_unreferenced(
+            &snapshot::seeds_root(&self.flora_dirs),
+            FloraGarbageKind::Snapshots,
+            None,
+            &snapshots,
+        )?);
+
+        if !dry_run {
+            for item in &mut garbage {
+                match item.kind {
+                    FloraGarbageKind::Prefix if !remove_prefixes => {}
+                    FloraGarbageKind::Snapshots => {
+                        let seed_name = item.path.file_name().unwrap_or_default().to_string_lossy();
+                        snapshot::delete_seed(&self.flora_dirs, &seed_name)?;
+                        item.removed = true;
+                    }
+                    _ => gc::remove(item)?,
+                }
+            }
+        }
+
+        Ok(garbage)
+    }
+
+    /// Returns the desktop entries and icons of every app of every seed
+    fn referenced_app_files(&self) -> Result<(HashSet<PathBuf>, HashSet<PathBuf>), FloraError> {
+        let mut desktop_entries = HashSet::new();
+        let mut icons = HashSet::new();
+
+        for seed_item in self.list_seed()? {
+            let name = &seed_item.seed_name;
+            desktop_entries.insert(self.flora_dirs.get_mime_desktop_entry_file(name));
+            for app in self.read_seed(name)?.get_apps() {
+                desktop_entries.insert(
+                    self.flora_dirs
+                        .get_desktop_entry_file(name, &app.application_name),
+                );
+                icons.insert(self.flora_dirs.get_icon_file(name, &app.application_name));
+            }
+        }
+
+        Ok((desktop_entries, icons))
+    }
+
+    /// Copies a seed under a new name, along with its prefix
+    ///
+    /// Without a new prefix location, the copy is placed next to the original prefix and named
+    /// after the new seed.
+    pub fn clone_seed(
+        &self,
+        name: &str,
+        new_name: &str,
+        new_prefix: Option<&str>,
+    ) -> Result<(), FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+        seed::validate_seed_name(new_name)?;
+        if self.is_seed_exists(new_name)? {
+            return Err(FloraError::SeedExists(new_name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+        let prefix = runner.get_prefix().to_path_buf();
+
+        let mut new_seed = seed.clone();
+        let prefix_setting = match &mut new_seed.seed_type {
+            FloraSeedType::Wine(wine_seed) => &mut wine_seed.wine_prefix,
+            FloraSeedType::Proton(proton_seed) => &mut proton_seed.proton_prefix,
+            FloraSeedType::None => return Err(FloraError::RunnerNone),
+        };
+        *prefix_setting = Some(match new_prefix {
+            Some(new_prefix) => new_prefix.to_string(),
+            // Keep relative prefixes relative to the configured prefix location
+            None if prefix_setting
+                .as_ref()
+                .is_some_and(|path| Path::new(path).is_relative()) =>
+            {
+                new_name.to_string()
+            }
+            None => prefix
+                .with_file_name(new_name)
+                .to_string_lossy()
+                .to_string(),
+        });
+
+        let new_runner =
+            runners::create_runner(new_name, &self.flora_dirs, &self.config, &new_seed)?;
+        let new_prefix = new_runner.get_prefix().to_path_buf();
+        if fs::exists(&new_prefix)? {
+            return Err(FloraError::PrefixExists(new_prefix));
+        }
+
+        if fs::exists(&prefix)? {
+            // Wineserver keeps the registry in memory, so a running prefix is not consistent
+            if registry::is_wineserver_running(&prefix) {
+                return Err(FloraError::WineserverRunning(prefix));
+            }
+
+            debug!(
+                "Copying prefix {} to {}",
+                prefix.to_string_lossy(),
+                new_prefix.to_string_lossy()
+            );
+            if let Err(e) = prefix::copy_prefix(&prefix, &new_prefix) {
+                // Do not leave a partial copy behind
+                let _ = fs::remove_dir_all(&new_prefix);
+                return Err(e);
+            }
+        } else {
+            debug!(
+                "Prefix {} not found, not copying it",
+                prefix.to_string_lossy()
+            );
+        }
+
+        self.create_seed(new_name, &new_seed)
+    }
+
+    /// Renames a seed, moving its icons, desktop entries, log and snapshots along
+    ///
+    /// With `rename_prefix`, a prefix directory named after the seed is renamed as well. If any
+    /// step fails, the steps already done are undone.
+    pub fn rename_seed(
+        &self,
+        name: &str,
+        new_name: &str,
+        rename_prefix: bool,
+    ) -> Result<(), FloraError> {
+        seed::validate_seed_name(new_name)?;
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+        if self.is_seed_exists(new_name)? {
+            return Err(FloraError::SeedExists(new_name.to_string()));
+        }
+        // Running processes are tracked and logged under the seed name
+        if process::list(&self.flora_dirs)?
+            .iter()
+            .any(|process| process.seed_name == name)
+        {
+            return Err(FloraError::SeedRunning(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+        let prefix = runner.get_prefix().to_path_buf();
+
+        let mut new_seed = seed.clone();
+        if rename_prefix {
+            if prefix.file_name().is_none_or(|file_name| file_name != name) {
+                return Err(FloraError::PrefixNotNamedAfterSeed(
+                    prefix,
+                    name.to_string(),
+                ));
+            }
+            let prefix_setting = match &mut new_seed.seed_type {
+                FloraSeedType::Wine(wine_seed) => &mut wine_seed.wine_prefix,
+                FloraSeedType::Proton(proton_seed) => &mut proton_seed.proton_prefix,
+                FloraSeedType::None => return Err(FloraError::RunnerNone),
+            };
+            // Keep relative prefixes relative to the configured prefix location
+            let setting = prefix_setting
+                .as_deref()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| prefix.clone());
+            *prefix_setting = Some(
+                setting
+                    .with_file_name(new_name)
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+
+        let new_runner =
+            runners::create_runner(new_name, &self.flora_dirs, &self.config, &new_seed)?;
+        let new_prefix = new_runner.get_prefix().to_path_buf();
+        let move_prefix = new_prefix != prefix && fs::exists(&prefix)?;
+        if move_prefix {
+            if fs::exists(&new_prefix)? {
+                return Err(FloraError::PrefixExists(new_prefix));
+            }
+            // Wineserver keeps the registry in memory, and would write it back to the old location
+            if registry::is_wineserver_running(&prefix) {
+                return Err(FloraError::WineserverRunning(prefix));
+            }
+        }
+
+        let apps = seed.get_apps();
+        let mut moved_files = Vec::new();
+        for app in &apps {
+            moved_files.push((
+                self.flora_dirs.get_icon_file(name, &app.application_name),
+                self.flora_dirs
+                    .get_icon_file(new_name, &app.application_name),
+            ));
+        }
+        moved_files.push((
+            self.flora_dirs.get_log_path(name),
+            self.flora_dirs.get_log_path(new_name),
+        ));
+        moved_files.retain(|(path, _)| path.exists());
+        let desktop_entries: Vec<_> = apps
+            .iter()
+            .filter(|app| {
+                self.flora_dirs
+                    .get_desktop_entry_file(name, &app.application_name)
+                    .exists()
+            })
+            .collect();
+
+        let mut undo = FloraRenameUndo::default();
+        let result = (|| {
+            if move_prefix {
+                debug!(
+                    "Moving prefix {} to {}",
+                    prefix.to_string_lossy(),
+                    new_prefix.to_string_lossy()
+                );
+                // Prefixes are only renamed in place, so they are never copied between filesystems
+                fs::rename(&prefix, &new_prefix).map_err(|e| {
+                    if e.raw_os_error() == Some(Errno::EXDEV as i32) {
+                        FloraError::PrefixCrossesFilesystems(prefix.clone(), new_prefix.clone())
+                    } else {
+                        e.into()
+                    }
+                })?;
+                undo.prefix = Some((prefix.clone(), new_prefix.clone()));
+                prefix::relocate_prefix(&new_prefix, &prefix, &new_prefix)?;
+            }
+            for (path, new_path) in &moved_files {
+                debug!(
+                    "Moving {} to {}",
+                    path.to_string_lossy(),
+                    new_path.to_string_lossy()
+                );
+                fs::rename(path, new_path)?;
+                undo.moved_files.push((path.clone(), new_path.clone()));
+            }
+            snapshot::rename_seed(&self.flora_dirs, name, new_name)?;
+            undo.snapshots = true;
+
+            // Desktop entries contain the seed name in their command line, so they are recreated
+            for app in &desktop_entries {
+                undo.desktop_entries.push(
+                    self.flora_dirs
+                        .get_desktop_entry_file(new_name, &app.application_name),
+                );
+                desktop::create_desktop_entry(
+                    &self.flora_dirs,
+                    &self.config.desktop,
+                    new_runner.as_ref(),
+                    app,
+                )?;
+            }
+
+            self.create_seed(new_name, &new_seed)
+        })();
+
+        if let Err(e) = result {
+            debug!("Unable to rename seed {}, undoing", name);
+            for desktop_entry in &undo.desktop_entries {
+                let _ = fs::remove_file(desktop_entry);
+            }
+            if undo.snapshots {
+                let _ = snapshot::rename_seed(&self.flora_dirs, new_name, name);
+            }
+            for (path, new_path) in undo.moved_files.iter().rev() {
+                let _ = fs::rename(new_path, path);
+            }
+            if let Some((prefix, new_prefix)) = &undo.prefix {
+                let _ = prefix::relocate_prefix(new_prefix, new_prefix, prefix);
+                let _ = fs::rename(new_prefix, prefix);
+            }
+
+            return Err(e);
+        }
+
+        for app in &desktop_entries {
+            fs::remove_file(
+                self.flora_dirs
+                    .get_desktop_entry_file(name, &app.application_name),
+            )?;
+        }
+        self.delete_seed(name)?;
+
+        // The MIME handler runs the seed by name, so it is registered again under the new name
+        let mime_desktop_entry = self.flora_dirs.get_mime_desktop_entry_file(name);
+        if fs::exists(&mime_desktop_entry)? {
+            fs::remove_file(&mime_desktop_entry)?;
+            self.register_mime_handler(new_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Exports a seed, its icons and its prefix into an archive
+    pub fn export_seed(
+        &self,
+        name: &str,
+        output: &Path,
+        exclude_caches: bool,
+    ) -> Result<FloraSeedArchiveManifest, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+        let prefix = runner.get_prefix();
+        // Wineserver keeps the registry in memory, so a running prefix is not consistent
+        if registry::is_wineserver_running(prefix) {
+            return Err(FloraError::WineserverRunning(prefix.to_path_buf()));
+        }
+
+        let (seed_type, runtime) = match &seed.seed_type {
+            FloraSeedType::Wine(wine_seed) => ("wine", wine_seed.wine_runtime.clone()),
+            FloraSeedType::Proton(proton_seed) => ("proton", proton_seed.proton_runtime.clone()),
+            FloraSeedType::None => return Err(FloraError::RunnerNone),
+        };
+        let apps = seed.get_apps();
+        let icons: BTreeMap<String, PathBuf> = apps
+            .iter()
+            .map(|app| {
+                let icon_path = self.flora_dirs.get_icon_file(name, &app.application_name);
+                (app.application_name.clone(), icon_path)
+            })
+            .filter(|(_, icon_path)| icon_path.exists())
+            .collect();
+
+        let manifest = FloraSeedArchiveManifest {
+            flora_version: env!("CARGO_PKG_VERSION").to_string(),
+            seed_name: name.to_string(),
+            seed_type: seed_type.to_string(),
+            runtime,
+            apps: apps.into_iter().map(|app| app.application_name).collect(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            prefix: prefix.to_path_buf(),
+            has_prefix: fs::exists(prefix)?,
+            icons: icons
+                .keys()
+                .map(|app_name| (archive::icon_file_name(app_name), app_name.clone()))
+                .collect(),
+        };
+
+        debug!("Exporting seed {} to {}", name, output.to_string_lossy());
+        archive::export(output, &manifest, &seed, &icons, exclude_caches)?;
+
+        Ok(manifest)
+    }
+
+    /// Imports a seed from an archive, under its original name or a new one
+    ///
+    /// The prefix is placed in the configured prefix location, named after the seed. Returns the
+    /// manifest of the archive.
+    pub fn import_seed(
+        &self,
+        archive_path: &Path,
+        new_name: Option<&str>,
+    ) -> Result<FloraSeedArchiveManifest, FloraError> {
+        let manifest = archive::read_manifest(archive_path)?;
+        let name = new_name.unwrap_or(&manifest.seed_name);
+        seed::validate_seed_name(name)?;
+        if self.is_seed_exists(name)? {
+            return Err(FloraError::SeedExists(name.to_string()));
+        }
+
+        let mut prefix = PathBuf::from(match manifest.seed_type.as_str() {
+            "wine" => &self.config.wine.wine_prefix_location,
+            "proton" => &self.config.proton.proton_prefix_location,
+            seed_type => {
+                return Err(FloraError::InvalidArchive(format!(
+                    "unknown seed type {}",
+                    seed_type
+                )));
+            }
+        });
+        prefix.push(name);
+        if fs::exists(&prefix)? {
+            return Err(FloraError::PrefixExists(prefix));
+        }
+
+        debug!(
+            "Importing seed {} with prefix {}",
+            name,
+            prefix.to_string_lossy()
+        );
+        let mut seed_archive = archive::import(archive_path, &prefix)?;
+
+        // Prefixes are relative to the configured prefix location
+        match &mut seed_archive.seed.seed_type {
+            FloraSeedType::Wine(wine_seed) => wine_seed.wine_prefix = Some(name.to_string()),
+            FloraSeedType::Proton(proton_seed) => {
+                proton_seed.proton_prefix = Some(name.to_string())
+            }
+            FloraSeedType::None => return Err(FloraError::RunnerNone),
+        }
+        for (app_name, icon) in &seed_archive.icons {
+            fs::write(self.flora_dirs.get_icon_file(name, app_name), icon)?;
+        }
+
+        self.create_seed(name, &seed_archive.seed)?;
+
+        Ok(seed_archive.manifest)
+    }
+
+    /// Takes a snapshot of a seed's prefix
+    pub fn create_snapshot(
+        &self,
+        name: &str,
+        label: Option<&str>,
+    ) -> Result<FloraSnapshot, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+        let prefix = runner.get_prefix();
+        if !fs::exists(prefix)? {
+            return Err(FloraError::PrefixNotFound(prefix.to_path_buf()));
+        }
+        // Wineserver keeps the registry in memory, so a running prefix is not consistent
+        if registry::is_wineserver_running(prefix) {
+            return Err(FloraError::WineserverRunning(prefix.to_path_buf()));
+        }
+
+        snapshot::create(&self.flora_dirs, name, prefix, label)
+    }
+
+    /// Lists the snapshots of a seed, oldest first
+    pub fn list_snapshots(&self, name: &str) -> Result<Vec<FloraSnapshot>, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        snapshot::list(&self.flora_dirs, name)
+    }
+
+    /// Replaces a seed's prefix with the contents of a snapshot
+    pub fn restore_snapshot(&self, name: &str, id: u32) -> Result<(), FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let snapshot = snapshot::get(&self.flora_dirs, name, id)?;
+        if process::list(&self.flora_dirs)?
+            .iter()
+            .any(|process| process.seed_name == name)
+        {
+            return Err(FloraError::SeedRunning(name.to_string()));
+        }
+
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+        let prefix = runner.get_prefix();
+        if registry::is_wineserver_running(prefix) {
+            return Err(FloraError::WineserverRunning(prefix.to_path_buf()));
+        }
+
+        debug!("Restoring snapshot {} to {}", id, prefix.to_string_lossy());
+        snapshot::restore(&self.flora_dirs, &snapshot, prefix)
+    }
+
+    /// Deletes a snapshot of a seed
+    pub fn delete_snapshot(&self, name: &str, id: u32) -> Result<(), FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        snapshot::delete(&self.flora_dirs, name, id)
+    }
+
     pub fn list_seed(&self) -> Result<Vec<FloraSeedListItem>, FloraError> {
         let seed_dir = self.flora_dirs.get_seed_root();
 
@@ -176,14 +882,41 @@ impl FloraManager {
         Ok(seed_config)
     }
 
+    /// Creates or updates a seed's prefix with wineboot, waiting until it is ready
+    pub fn seed_init(&self, name: &str, quiet: bool) -> Result<FloraRunResult, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+        let result = runner.init_prefix(quiet)?;
+        match (result.exit_code, result.signal) {
+            (Some(0), _) => Ok(result),
+            (Some(exit_code), _) => Err(FloraError::PrefixInitFailed(
+                name.to_string(),
+                format!("wineboot exited with code {}", exit_code),
+            )),
+            (None, Some(signal)) => Err(FloraError::PrefixInitFailed(
+                name.to_string(),
+                format!("wineboot was terminated by signal {}", signal),
+            )),
+            (None, None) => Err(FloraError::PrefixInitFailed(
+                name.to_string(),
+                String::from("wineboot exited abnormally"),
+            )),
+        }
+    }
+
     /// Launches the prefix configuration dialog of an seed (usually winecfg)
     pub fn seed_config(
         &self,
         name: &str,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
         if !self.is_seed_exists(name)? {
             return Err(FloraError::SeedNotFound(name.to_string()));
         }
@@ -200,8 +933,8 @@ impl FloraManager {
         name: &str,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
         if !self.is_seed_exists(name)? {
             return Err(FloraError::SeedNotFound(name.to_string()));
         }
@@ -218,8 +951,8 @@ impl FloraManager {
         name: &str,
         app_name: &Option<&str>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
         if !self.is_seed_exists(name)? {
             return Err(FloraError::SeedNotFound(name.to_string()));
         }
@@ -227,11 +960,8 @@ impl FloraManager {
 
         let app_entry = seed.get_app_or_default(app_name)?;
 
-        // Determine arguments to be passed to runner
-        let new_args = [app_entry.application_location.as_str()];
-
         let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
-        runner.run_executable(&new_args, quiet, wait)
+        runner.run_app(&app_entry, quiet, wait)
     }
 
     /// Launches an executable inside an seed's prefix
@@ -240,8 +970,8 @@ impl FloraManager {
         name: &str,
         args: &Vec<&str>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
         if !self.is_seed_exists(name)? {
             return Err(FloraError::SeedNotFound(name.to_string()));
         }
@@ -255,15 +985,126 @@ impl FloraManager {
         runner.run_executable(new_args, quiet, wait)
     }
 
+    /// Lists running processes launched by Flora
+    pub fn list_processes(&self) -> Result<Vec<FloraProcess>, FloraError> {
+        process::list(&self.flora_dirs)
+    }
+
+    /// Terminates all processes running in a seed
+    pub fn kill_seed(&self, name: &str) -> Result<Vec<FloraProcess>, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+
+        let processes: Vec<_> = process::list(&self.flora_dirs)?
+            .into_iter()
+            .filter(|process| process.seed_name == name)
+            .collect();
+
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+        runner.kill()?;
+
+        // Terminate processes that outlived the prefix, e.g. launcher wrappers. Every process is
+        // unregistered, even if signaling another one failed.
+        let mut result = Ok(());
+        for process in processes.iter() {
+            if let Err(e) = process::terminate(&self.flora_dirs, process)
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        }
+
+        result.map(|_| processes)
+    }
+
+    /// Opens the registry of a seed's prefix
+    pub fn get_registry(&self, name: &str) -> Result<FloraRegistry, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+
+        Ok(FloraRegistry::new(runner.get_prefix()))
+    }
+
+    /// Gets the effective Windows version of a seed, or of one of its apps
+    ///
+    /// Falls back to the version currently set in the prefix when none is configured, and
+    /// returns None when the prefix uses Wine's default.
+    pub fn get_windows_version(
+        &self,
+        name: &str,
+        app_name: Option<&str>,
+    ) -> Result<Option<String>, FloraError> {
+        let seed = self.get_seed(name)?;
+        let app = app_name
+            .map(|app_name| seed.get_app(app_name))
+            .transpose()?;
+        let seed_version = match &seed.seed_type {
+            FloraSeedType::Wine(wine_seed) => wine_seed.windows_version,
+            FloraSeedType::Proton(proton_seed) => proton_seed.windows_version,
+            FloraSeedType::None => None,
+        };
+
+        if let Some(version) = app.as_ref().and_then(|app| app.windows_version) {
+            return Ok(Some(version.to_string()));
+        }
+
+        // Wine prefers the app's AppDefaults over the prefix-wide version
+        let registry = self.get_registry(name).ok();
+        let read_version = |key_path: FloraRegistryKeyPath| {
+            let key = registry.as_ref()?.get_key(&key_path).ok()?;
+            match &key.get_value(Some("Version"))?.data {
+                FloraRegistryData::String(version) => Some(version.clone()),
+                _ => None,
+            }
+        };
+        if let Some(version) = app
+            .as_ref()
+            .and_then(|app| read_version(runners::app_defaults_key(app)))
+        {
+            return Ok(Some(version));
+        }
+        if let Some(version) = seed_version {
+            return Ok(Some(version.to_string()));
+        }
+        let current_version = read_version(runners::wine_key());
+
+        Ok(current_version)
+    }
+
+    /// Removes the Windows version of an app from the prefix, once it is cleared from the app
+    ///
+    /// Wine keeps using the version in the app's `AppDefaults` until it is deleted.
+    pub fn clear_app_windows_version(
+        &self,
+        name: &str,
+        app: &FloraSeedApp,
+    ) -> Result<(), FloraError> {
+        let registry = self.get_registry(name)?;
+        if !registry.hive_exists(FloraRegistryRoot::CurrentUser) {
+            return Ok(());
+        }
+
+        match registry.delete_value(&runners::app_defaults_key(app), Some("Version")) {
+            Err(FloraError::RegistryKeyNotFound(_) | FloraError::RegistryValueNotFound(_)) => {
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
     /// Creates a desktop entry for seed
     pub fn create_desktop_entries(
         &self,
         seed_name: Option<&str>,
         app_name: Option<&str>,
     ) -> Result<(), FloraError> {
-        // Initialize menus
-        desktop::initialize_desktop_entries(&self.flora_dirs)?;
-
         let seed_dir = self.flora_dirs.get_seed_root();
 
         let mut files = read_dir(&seed_dir)?
@@ -276,6 +1117,18 @@ impl FloraManager {
                 Ok((String::from(name), seed))
             })
             .collect::<Result<Vec<_>, FloraError>>()?;
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        // Initialize menus, with a submenu for every seed with apps
+        let submenu_seeds: Vec<_> = match self.config.desktop.seed_submenus {
+            true => files
+                .iter()
+                .filter(|(_, seed)| !seed.get_apps().is_empty())
+                .map(|(name, _)| name.clone())
+                .collect(),
+            false => Vec::new(),
+        };
+        desktop::initialize_desktop_entries(&self.flora_dirs, &submenu_seeds)?;
 
         if let Some(seed_name) = seed_name {
             files.retain(|(name, _)| name == seed_name);
@@ -291,12 +1144,159 @@ impl FloraManager {
             }
 
             for app in apps {
-                runner.create_desktop_entry(&app)?;
+                desktop::create_desktop_entry(
+                    &self.flora_dirs,
+                    &self.config.desktop,
+                    runner.as_ref(),
+                    &app,
+                )?;
             }
         }
 
         Ok(())
     }
+
+    /// Regenerates the desktop entries of all seeds, or of one seed, then removes desktop entries
+    /// and icons of apps that no longer exist
+    ///
+    /// Returns the removed files.
+    pub fn sync_desktop_entries(
+        &self,
+        seed_name: Option<&str>,
+    ) -> Result<Vec<FloraGarbage>, FloraError> {
+        if let Some(seed_name) = seed_name
+            && !self.is_seed_exists(seed_name)?
+        {
+            return Err(FloraError::SeedNotFound(seed_name.to_string()));
+        }
+
+        self.create_desktop_entries(seed_name, None)?;
+
+        let (desktop_entries, icons) = self.referenced_app_files()?;
+        let mut stale = gc::find_unreferenced(
+            &self.flora_dirs.get_desktop_entry_root(),
+            FloraGarbageKind::DesktopEntry,
+            Some("desktop"),
+            &desktop_entries,
+        )?;
+        stale.extend(gc::find_unreferenced(
+            &self.flora_dirs.get_icons_root(),
+            FloraGarbageKind::Icon,
+            Some("png"),
+            &icons,
+        )?);
+        if let Some(seed_name) = seed_name {
+            // Files are named <seed>__<app>
+            let file_prefix = format!(
+                "{}{}",
+                desktop::desktop_id_part(seed_name),
+                desktop::DESKTOP_ID_SEPARATOR
+            );
+            stale.retain(|item| {
+                item.path
+                    .file_name()
+                    .is_some_and(|file_name| file_name.to_string_lossy().starts_with(&file_prefix))
+            });
+        }
+
+        for item in &mut stale {
+            gc::remove(item)?;
+        }
+        desktop::update_desktop_database(&self.flora_dirs)?;
+
+        Ok(stale)
+    }
+
+    /// Syncs a seed's desktop entries after its apps changed, if enabled in the configuration
+    pub fn sync_desktop_entries_on_app_change(&self, name: &str) -> Result<(), FloraError> {
+        if self.config.desktop.sync_on_app_change {
+            debug!("Syncing desktop entries of seed {}", name);
+            self.sync_desktop_entries(Some(name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes a seed the default application for Windows executables, installers and shortcuts
+    ///
+    /// Returns the location of the desktop entry handling them.
+    pub fn register_mime_handler(&self, name: &str) -> Result<PathBuf, FloraError> {
+        if !self.is_seed_exists(name)? {
+            return Err(FloraError::SeedNotFound(name.to_string()));
+        }
+
+        let seed = self.read_seed(name)?;
+        let runner = runners::create_runner(name, &self.flora_dirs, &self.config, &seed)?;
+
+        fs::create_dir_all(self.flora_dirs.get_desktop_entry_root())?;
+        let desktop_entry = desktop::create_mime_desktop_entry(
+            &self.flora_dirs,
+            &self.config.desktop,
+            runner.as_ref(),
+        )?;
+        desktop::update_desktop_database(&self.flora_dirs)?;
+        desktop::register_mime_handler(&self.flora_dirs, &desktop_entry)?;
+
+        Ok(desktop_entry)
+    }
+
+    /// Finds the seed that opens a file, following the `open` section of the configuration
+    ///
+    /// Directory mappings take precedence over extension mappings, and the deepest matching
+    /// directory wins. Returns `None` if no mapping matches.
+    pub fn find_open_seed(&self, file: &Path) -> Result<Option<String>, FloraError> {
+        let file = fs::canonicalize(file)?;
+        let open_config = &self.config.open;
+
+        let directory_seed = open_config
+            .directories
+            .iter()
+            .filter_map(|(directory, seed_name)| {
+                let directory = fs::canonicalize(directory).ok()?;
+                file.starts_with(&directory)
+                    .then_some((directory.components().count(), seed_name))
+            })
+            .max_by_key(|(depth, _)| *depth)
+            .map(|(_, seed_name)| seed_name);
+        let seed_name = directory_seed.or_else(|| {
+            let extension = file.extension()?.to_string_lossy().to_lowercase();
+            open_config
+                .extensions
+                .iter()
+                .find(|(configured, _)| {
+                    configured.trim_start_matches('.').to_lowercase() == extension
+                })
+                .map(|(_, seed_name)| seed_name)
+        });
+
+        match seed_name {
+            Some(seed_name) if !self.is_seed_exists(seed_name)? => {
+                Err(FloraError::SeedNotFound(seed_name.to_string()))
+            }
+            seed_name => Ok(seed_name.cloned()),
+        }
+    }
+
+    /// Opens a Windows executable, installer or shortcut with a seed, as if it was double-clicked
+    /// in Windows
+    pub fn seed_open_file(
+        &self,
+        name: &str,
+        file: &Path,
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        let file = fs::canonicalize(file)?;
+        let file = file.to_string_lossy();
+
+        let mut args = vec!["start"];
+        if !matches!(wait, FloraWaitMode::NoWait) {
+            args.push("/wait");
+        }
+        args.extend(["/unix", &file]);
+
+        self.seed_run_executable(name, &args, quiet, wait)
+    }
 }
 
 // Static functions
@@ -321,7 +1321,63 @@ impl FloraManager {
 }
 
 // List models
+/// Files removed when purging a seed
+#[derive(Default)]
+pub struct FloraSeedPurgeReport {
+    pub seed_file: PathBuf,
+    pub desktop_entries: Vec<PathBuf>,
+    pub icons: Vec<PathBuf>,
+    pub log: Option<PathBuf>,
+    /// Directory of the seed's snapshots
+    pub snapshots: Option<PathBuf>,
+    pub prefix: Option<PathBuf>,
+}
+
 pub struct FloraSeedListItem {
     pub seed_name: String,
     pub seed_type: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        config::FloraConfig,
+        dirs::FloraDirs,
+        errors::FloraError,
+        manager::FloraManager,
+        seed::{FloraSeed, FloraSeedType, FloraWineSeed},
+    };
+
+    #[test]
+    fn rename_to_invalid_name() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().join("flora")).unwrap();
+        fs::create_dir_all(dirs.get_seed_root()).unwrap();
+        let config = FloraConfig::read_config(&dirs).unwrap();
+        let manager = FloraManager {
+            flora_dirs: Box::new(dirs),
+            config: Box::new(config),
+        };
+        let mut seed = FloraSeed::default();
+        seed.seed_type = FloraSeedType::Wine(FloraWineSeed {
+            wine_prefix: None,
+            wine_runtime: None,
+            windows_version: None,
+            prefix_arch: None,
+        });
+        manager.create_seed("seed", &seed).unwrap();
+
+        for new_name in ["", "..", "../escaped"] {
+            let result = manager.rename_seed("seed", new_name, true);
+            assert!(matches!(result, Err(FloraError::InvalidSeedName(_))));
+        }
+        // Names are validated before anything else is looked at
+        let result = manager.rename_seed("missing", "../escaped", false);
+        assert!(matches!(result, Err(FloraError::InvalidSeedName(_))));
+
+        assert!(manager.is_seed_exists("seed").unwrap());
+        assert!(!fs::exists(root.path().join("flora/escaped.toml")).unwrap());
+    }
+}
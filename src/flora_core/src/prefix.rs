@@ -0,0 +1,257 @@
+use std::{
+    fs,
+    os::unix::fs::symlink,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use walkdir::WalkDir;
+
+use crate::errors::FloraError;
+
+/// Copies a prefix to a new location, then rewrites paths pointing at the old location.
+///
+/// Files are copied with `std::fs::copy`, which uses `copy_file_range` on Linux, so filesystems
+/// supporting reflinks share the data instead of duplicating it. Symlinks, such as the drives in
+/// `dosdevices`, are recreated as symlinks. Directory permissions are applied last, so read-only
+/// directories can still be filled and relocated.
+pub(crate) fn copy_prefix(source: &Path, destination: &Path) -> Result<(), FloraError> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut dir_permissions = Vec::new();
+    for entry in WalkDir::new(source).follow_links(false) {
+        let entry = entry.map_err(|e| FloraError::FileAccessError(e.into()))?;
+        let relative_path = entry.path().strip_prefix(source).unwrap();
+        let target = destination.join(relative_path);
+        let file_type = entry.file_type();
+
+        if file_type.is_symlink() {
+            symlink(fs::read_link(entry.path())?, &target)?;
+        } else if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            dir_permissions.push((
+                target,
+                entry
+                    .metadata()
+                    .map_err(std::io::Error::from)?
+                    .permissions(),
+            ));
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target)?;
+        } else {
+            debug!("Skipping special file {}", entry.path().display());
+        }
+    }
+
+    relocate_prefix(destination, source, destination)?;
+
+    // Directories are walked parents first, so children are done before their parents
+    for (dir, permissions) in dir_permissions.into_iter().rev() {
+        fs::set_permissions(dir, permissions)?;
+    }
+
+    Ok(())
+}
+
+/// Rewrites absolute paths in a prefix's symlinks and registry hives from an old prefix location
+/// to a new one
+pub(crate) fn relocate_prefix(prefix: &Path, old: &Path, new: &Path) -> Result<(), FloraError> {
+    if old == new {
+        return Ok(());
+    }
+
+    for entry in WalkDir::new(prefix).follow_links(false) {
+        let entry = entry.map_err(|e| FloraError::FileAccessError(e.into()))?;
+        if !entry.path_is_symlink() {
+            continue;
+        }
+
+        let link = fs::read_link(entry.path())?;
+        let relocated_link = relocate_link(&link, old, new);
+        if relocated_link != link {
+            debug!("Rewriting symlink {}", entry.path().display());
+            fs::remove_file(entry.path())?;
+            symlink(relocated_link, entry.path())?;
+        }
+    }
+
+    for entry in fs::read_dir(prefix)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "reg") {
+            continue;
+        }
+        // Hives are written by Wine as ASCII, anything else is not a hive
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+
+        let relocated = relocate_registry(&content, old, new);
+        if relocated != content {
+            debug!("Rewriting prefix paths in {}", path.display());
+            fs::write(&path, relocated)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves symlinks, `.` and `..` in a path, so different ways of writing a prefix compare equal
+///
+/// Parts of the path that do not exist yet are appended as written to their longest existing
+/// ancestor.
+pub(crate) fn canonical_path(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = fs::canonicalize(existing) {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |canonical, name| canonical.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return path.components().collect(),
+        }
+    }
+}
+
+/// Points a symlink inside the old prefix to the same location inside the new prefix
+fn relocate_link(link: &Path, old: &Path, new: &Path) -> PathBuf {
+    match link.strip_prefix(old) {
+        Ok(relative_path) if link.is_absolute() => new.join(relative_path),
+        _ => link.to_path_buf(),
+    }
+}
+
+/// Replaces the old prefix location in registry hive content, as both Unix and Z: paths
+fn relocate_registry(content: &str, old: &Path, new: &Path) -> String {
+    let unix_path = |path: &Path| path.to_string_lossy().trim_end_matches('/').to_string();
+    // Backslashes are escaped in hive strings
+    let windows_path = |path: &Path| format!("Z:{}", unix_path(path).replace('/', "\\\\"));
+
+    let content = replace_path(content, &unix_path(old), &unix_path(new));
+    replace_path(&content, &windows_path(old), &windows_path(new))
+}
+
+/// Replaces a path, skipping matches that are only the start of a longer file name
+fn replace_path(content: &str, old: &str, new: &str) -> String {
+    let mut replaced = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(index) = rest.find(old) {
+        let after = &rest[index + old.len()..];
+        let is_file_name = after
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-' | ' '));
+
+        replaced.push_str(&rest[..index]);
+        replaced.push_str(if is_file_name { old } else { new });
+        rest = after;
+    }
+    replaced.push_str(rest);
+
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, Permissions},
+        os::unix::fs::{PermissionsExt, symlink},
+        path::Path,
+    };
+
+    use crate::prefix::{canonical_path, copy_prefix, relocate_link, relocate_registry};
+
+    #[test]
+    fn copy_read_only_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let source = root.path().join("source");
+        let destination = root.path().join("destination");
+        fs::create_dir_all(source.join("drive_c/Game")).unwrap();
+        fs::write(source.join("drive_c/Game/game.exe"), "MZ").unwrap();
+        symlink(source.join("drive_c"), source.join("drive_c/Game/c")).unwrap();
+        fs::set_permissions(source.join("drive_c/Game"), Permissions::from_mode(0o555)).unwrap();
+
+        copy_prefix(&source, &destination).unwrap();
+
+        let game_dir = destination.join("drive_c/Game");
+        assert_eq!(
+            fs::metadata(&game_dir).unwrap().permissions().mode() & 0o777,
+            0o555
+        );
+        assert_eq!(fs::read_to_string(game_dir.join("game.exe")).unwrap(), "MZ");
+        assert_eq!(
+            fs::read_link(game_dir.join("c")).unwrap(),
+            destination.join("drive_c")
+        );
+
+        for dir in [&source, &destination] {
+            fs::set_permissions(dir.join("drive_c/Game"), Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn canonical_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = dir.path().join("prefix");
+        fs::create_dir(&prefix).unwrap();
+        fs::create_dir(dir.path().join("x")).unwrap();
+        symlink(&prefix, dir.path().join("alias")).unwrap();
+
+        let canonical = canonical_path(&prefix);
+        assert_eq!(canonical_path(&dir.path().join("alias")), canonical);
+        assert_eq!(canonical_path(&dir.path().join("prefix/")), canonical);
+        assert_eq!(canonical_path(&dir.path().join("x/../prefix")), canonical);
+        assert_eq!(
+            canonical_path(&dir.path().join("alias/missing")),
+            canonical.join("missing")
+        );
+    }
+
+    #[test]
+    fn relocate_registry_paths() {
+        let content = concat!(
+            "[Software\\\\Wine\\\\Fonts] 1\n",
+            "\"Path\"=\"Z:\\\\games\\\\old\\\\drive_c\\\\fonts\"\n",
+            "\"Unix\"=\"/games/old/drive_c\"\n",
+            "\"Other\"=\"Z:\\\\games\\\\older\"\n",
+        );
+
+        let relocated =
+            relocate_registry(content, Path::new("/games/old"), Path::new("/games/new"));
+
+        assert_eq!(
+            relocated,
+            concat!(
+                "[Software\\\\Wine\\\\Fonts] 1\n",
+                "\"Path\"=\"Z:\\\\games\\\\new\\\\drive_c\\\\fonts\"\n",
+                "\"Unix\"=\"/games/new/drive_c\"\n",
+                "\"Other\"=\"Z:\\\\games\\\\older\"\n",
+            )
+        );
+    }
+
+    #[test]
+    fn relocate_links() {
+        let old = Path::new("/games/old");
+        let new = Path::new("/games/new");
+
+        assert_eq!(
+            relocate_link(Path::new("/games/old/drive_c"), old, new),
+            Path::new("/games/new/drive_c")
+        );
+        assert_eq!(
+            relocate_link(Path::new("../drive_c"), old, new),
+            Path::new("../drive_c")
+        );
+        assert_eq!(relocate_link(Path::new("/"), old, new), Path::new("/"));
+    }
+}
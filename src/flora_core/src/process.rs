@@ -0,0 +1,329 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, read_dir},
+    io,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+use nix::{
+    errno::Errno,
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{dirs::FloraDirs, errors::FloraError};
+
+/// A process launched by Flora
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FloraProcess {
+    pub pid: u32,
+    pub seed_name: String,
+    pub app_name: Option<String>,
+    pub command: String,
+    /// Launch time, in seconds since the UNIX epoch
+    pub started_at: u64,
+    /// Process start time in clock ticks since boot, used to detect PID reuse
+    start_ticks: Option<u64>,
+}
+
+impl FloraProcess {
+    /// Checks whether the process is still running
+    pub fn is_alive(&self) -> bool {
+        match process_start_ticks(self.pid) {
+            Some(start_ticks) => self.start_ticks.is_none_or(|ticks| ticks == start_ticks),
+            None => false,
+        }
+    }
+}
+
+/// How long to wait for a process launched in a seed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloraWaitMode {
+    /// Return as soon as the process is spawned
+    #[default]
+    NoWait,
+    /// Wait until the process exits
+    Process,
+    /// Wait until the process exits, then until the prefix's wineserver shuts down
+    Prefix,
+}
+
+/// Result of launching a process in a seed
+#[derive(Debug, Clone)]
+pub struct FloraRunResult {
+    pub pid: u32,
+    /// Exit code of the process, if it was waited on and exited normally
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the process, if it was waited on and killed
+    pub signal: Option<i32>,
+    /// Time the process ran for, if it was waited on
+    pub duration: Option<Duration>,
+}
+
+impl FloraRunResult {
+    /// Creates a result for a process that was not waited on
+    pub(crate) fn spawned(pid: u32) -> Self {
+        Self {
+            pid,
+            exit_code: None,
+            signal: None,
+            duration: None,
+        }
+    }
+
+    /// Creates a result for a process that has exited
+    pub(crate) fn exited(pid: u32, status: ExitStatus, duration: Duration) -> Self {
+        Self {
+            pid,
+            exit_code: status.code(),
+            signal: status.signal(),
+            duration: Some(duration),
+        }
+    }
+
+    /// Checks whether the process exited successfully, or is still running
+    pub fn success(&self) -> bool {
+        self.signal.is_none() && self.exit_code.is_none_or(|code| code == 0)
+    }
+}
+
+/// Reads the start time of a running process from procfs, ignoring zombie processes
+fn process_start_ticks(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // The process name may contain spaces, so skip past its closing parenthesis
+    let (_, fields) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+
+    if fields.first() == Some(&"Z") {
+        return None;
+    }
+
+    // starttime is the 22nd field, counting from the pid
+    fields.get(19)?.parse().ok()
+}
+
+fn process_file(dirs: &FloraDirs, pid: u32) -> PathBuf {
+    let mut process_file = dirs.get_process_root();
+    process_file.push(format!("{}.toml", pid));
+
+    process_file
+}
+
+/// Records a spawned process in the process registry
+pub(crate) fn register(
+    dirs: &FloraDirs,
+    seed_name: &str,
+    app_name: Option<&str>,
+    command: &Command,
+    child: &Child,
+) -> Result<FloraProcess, FloraError> {
+    let pid = child.id();
+    let command_line: Vec<_> = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(OsStr::to_string_lossy)
+        .collect();
+
+    let process = FloraProcess {
+        pid,
+        seed_name: seed_name.to_string(),
+        app_name: app_name.map(String::from),
+        command: command_line.join(" "),
+        started_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        start_ticks: process_start_ticks(pid),
+    };
+
+    let process_file = process_file(dirs, pid);
+    debug!("Registering process {} at {}", pid, process_file.display());
+
+    let process_toml = toml::to_string(&process)?;
+    fs::write(process_file, process_toml)?;
+
+    Ok(process)
+}
+
+fn remove_process_file(process_file: &Path) -> Result<(), FloraError> {
+    match fs::remove_file(process_file) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Removes a process from the process registry
+pub(crate) fn unregister(dirs: &FloraDirs, pid: u32) -> Result<(), FloraError> {
+    debug!("Unregistering process {}", pid);
+    remove_process_file(&process_file(dirs, pid))
+}
+
+/// Waits for a registered process to exit, removing it from the registry even if waiting fails
+pub(crate) fn wait(
+    dirs: &FloraDirs,
+    pid: u32,
+    child: &mut Child,
+) -> Result<ExitStatus, FloraError> {
+    let status = child.wait().map_err(FloraError::RunnerExecError);
+    unregister(dirs, pid)?;
+
+    status
+}
+
+/// Reaps a registered process that is not waited on once it exits, and removes it from the
+/// registry
+///
+/// Without this, every launch from a long-running caller such as flora_server would leave a
+/// zombie process behind.
+pub(crate) fn detach(dirs: &FloraDirs, pid: u32, mut child: Child) {
+    let process_file = process_file(dirs, pid);
+
+    thread::spawn(move || {
+        if let Err(e) = child.wait() {
+            debug!("Unable to wait for process {}: {}", pid, e);
+        }
+        debug!("Unregistering process {}", pid);
+        if let Err(e) = remove_process_file(&process_file) {
+            debug!("Unable to unregister process {}: {}", pid, e);
+        }
+    });
+}
+
+/// Lists running processes, removing processes that have exited from the registry
+pub(crate) fn list(dirs: &FloraDirs) -> Result<Vec<FloraProcess>, FloraError> {
+    let mut processes = Vec::new();
+
+    for entry in read_dir(dirs.get_process_root())? {
+        let path = entry?.path();
+        // Entries are removed when their process exits, which may happen while listing
+        let process_toml = match fs::read_to_string(&path) {
+            Ok(process_toml) => process_toml,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        match toml::from_str::<FloraProcess>(&process_toml) {
+            Ok(process) if process.is_alive() => processes.push(process),
+            _ => {
+                debug!("Pruning process entry {}", path.display());
+                match fs::remove_file(&path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                }
+            }
+        }
+    }
+    processes.sort_by_key(|process| (process.started_at, process.pid));
+
+    Ok(processes)
+}
+
+/// Sends SIGTERM to a process and removes it from the registry
+///
+/// A process that already exited, e.g. because `wineserver -k` killed it, counts as terminated.
+/// The registry entry is removed even if signaling fails.
+pub(crate) fn terminate(dirs: &FloraDirs, process: &FloraProcess) -> Result<(), FloraError> {
+    let result = match process.is_alive() {
+        true => {
+            debug!("Terminating process {}", process.pid);
+
+            match signal::kill(Pid::from_raw(process.pid as i32), Signal::SIGTERM) {
+                Ok(()) | Err(Errno::ESRCH) => Ok(()),
+                Err(e) => Err(FloraError::ProcessSignalError(process.pid, e)),
+            }
+        }
+        false => Ok(()),
+    };
+
+    unregister(dirs, process.pid)?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        process::Command,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        dirs::FloraDirs,
+        process::{detach, list, register, terminate, wait},
+    };
+
+    #[test]
+    fn terminate_exited() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().to_path_buf()).unwrap();
+        fs::create_dir_all(dirs.get_process_root()).unwrap();
+
+        let mut command = Command::new("sleep");
+        command.arg("10");
+        let mut child = command.spawn().unwrap();
+        let process = register(&dirs, "seed", None, &command, &child).unwrap();
+        assert_eq!(list(&dirs).unwrap().len(), 1);
+
+        // The process exits before flora signals it, as after wineserver -k
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        terminate(&dirs, &process).unwrap();
+        assert!(list(&dirs).unwrap().is_empty());
+        assert!(
+            fs::read_dir(dirs.get_process_root())
+                .unwrap()
+                .next()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn wait_unregisters() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().to_path_buf()).unwrap();
+        fs::create_dir_all(dirs.get_process_root()).unwrap();
+
+        let mut command = Command::new("true");
+        let mut child = command.spawn().unwrap();
+        let process = register(&dirs, "seed", None, &command, &child).unwrap();
+
+        assert!(wait(&dirs, process.pid, &mut child).unwrap().success());
+        assert!(list(&dirs).unwrap().is_empty());
+    }
+
+    #[test]
+    fn detach_reaps() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().to_path_buf()).unwrap();
+        fs::create_dir_all(dirs.get_process_root()).unwrap();
+
+        let mut command = Command::new("sleep");
+        command.arg("0.1");
+        let child = command.spawn().unwrap();
+        let process = register(&dirs, "seed", None, &command, &child).unwrap();
+        detach(&dirs, process.pid, child);
+
+        let started = Instant::now();
+        while fs::exists(format!("/proc/{}", process.pid)).unwrap() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "process not reaped"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(
+            fs::read_dir(dirs.get_process_root())
+                .unwrap()
+                .next()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,1328 @@
+use std::{
+    fmt::Display,
+    fs,
+    os::unix::{fs::MetadataExt, net::UnixStream},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::debug;
+use nix::unistd::getuid;
+
+use crate::errors::FloraError;
+
+/// Seconds between the Windows epoch (1601) and the UNIX epoch (1970)
+const WINDOWS_EPOCH_OFFSET: u64 = 11644473600;
+
+const REGEDIT_HEADER: &str = "Windows Registry Editor Version 5.00";
+const REGEDIT4_HEADER: &str = "REGEDIT4";
+
+const REG_SZ: u32 = 1;
+const REG_EXPAND_SZ: u32 = 2;
+const REG_BINARY: u32 = 3;
+const REG_DWORD: u32 = 4;
+const REG_MULTI_SZ: u32 = 7;
+const REG_QWORD: u32 = 11;
+
+/// Registry root keys stored in a Wine prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloraRegistryRoot {
+    /// HKEY_CURRENT_USER, stored in user.reg
+    CurrentUser,
+    /// HKEY_LOCAL_MACHINE, stored in system.reg
+    LocalMachine,
+}
+
+impl FloraRegistryRoot {
+    fn file_name(&self) -> &'static str {
+        match self {
+            Self::CurrentUser => "user.reg",
+            Self::LocalMachine => "system.reg",
+        }
+    }
+}
+
+impl Display for FloraRegistryRoot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CurrentUser => write!(f, "HKEY_CURRENT_USER"),
+            Self::LocalMachine => write!(f, "HKEY_LOCAL_MACHINE"),
+        }
+    }
+}
+
+/// Full path of a registry key, e.g. `HKCU\Software\Wine`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloraRegistryKeyPath {
+    pub root: FloraRegistryRoot,
+    /// Path relative to the root key, separated with backslashes
+    pub path: String,
+}
+
+impl FromStr for FloraRegistryKeyPath {
+    type Err = FloraError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let key_path = s.replace('/', "\\");
+        let (root_name, path) = key_path.split_once('\\').unwrap_or((&key_path, ""));
+
+        let (root, path) = match root_name.to_uppercase().as_str() {
+            "HKCU" | "HKEY_CURRENT_USER" => (FloraRegistryRoot::CurrentUser, String::from(path)),
+            "HKLM" | "HKEY_LOCAL_MACHINE" => (FloraRegistryRoot::LocalMachine, String::from(path)),
+            // Classes root is a view of the machine's classes
+            "HKCR" | "HKEY_CLASSES_ROOT" => (
+                FloraRegistryRoot::LocalMachine,
+                join_key_path("Software\\Classes", path),
+            ),
+            _ => return Err(FloraError::InvalidRegistryKey(String::from(s))),
+        };
+
+        let path: Vec<_> = path.split('\\').filter(|part| !part.is_empty()).collect();
+        if path.is_empty() {
+            return Err(FloraError::InvalidRegistryKey(String::from(s)));
+        }
+
+        Ok(Self {
+            root,
+            path: path.join("\\"),
+        })
+    }
+}
+
+impl Display for FloraRegistryKeyPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\\{}", self.root, self.path)
+    }
+}
+
+/// Data of a registry value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FloraRegistryData {
+    /// REG_SZ
+    String(String),
+    /// REG_EXPAND_SZ
+    ExpandString(String),
+    /// REG_MULTI_SZ
+    MultiString(Vec<String>),
+    /// REG_DWORD
+    Dword(u32),
+    /// REG_QWORD
+    Qword(u64),
+    /// REG_BINARY
+    Binary(Vec<u8>),
+    /// Any other value type, with its raw data
+    Other(u32, Vec<u8>),
+}
+
+impl FloraRegistryData {
+    /// Name of the value type, e.g. REG_SZ
+    pub fn type_name(&self) -> String {
+        match self {
+            Self::String(_) => String::from("REG_SZ"),
+            Self::ExpandString(_) => String::from("REG_EXPAND_SZ"),
+            Self::MultiString(_) => String::from("REG_MULTI_SZ"),
+            Self::Dword(_) => String::from("REG_DWORD"),
+            Self::Qword(_) => String::from("REG_QWORD"),
+            Self::Binary(_) => String::from("REG_BINARY"),
+            Self::Other(value_type, _) => format!("0x{:x}", value_type),
+        }
+    }
+
+    fn value_type(&self) -> u32 {
+        match self {
+            Self::String(_) => REG_SZ,
+            Self::ExpandString(_) => REG_EXPAND_SZ,
+            Self::MultiString(_) => REG_MULTI_SZ,
+            Self::Dword(_) => REG_DWORD,
+            Self::Qword(_) => REG_QWORD,
+            Self::Binary(_) => REG_BINARY,
+            Self::Other(value_type, _) => *value_type,
+        }
+    }
+
+    /// Creates data from a value type and its raw bytes
+    fn from_bytes(value_type: u32, bytes: Vec<u8>) -> Self {
+        match value_type {
+            REG_SZ => Self::String(decode_utf16_string(&bytes)),
+            REG_EXPAND_SZ => Self::ExpandString(decode_utf16_string(&bytes)),
+            REG_MULTI_SZ => Self::MultiString(split_multi_string(&decode_utf16(&bytes))),
+            REG_DWORD if bytes.len() == 4 => {
+                Self::Dword(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+            REG_QWORD if bytes.len() == 8 => {
+                let mut qword = [0u8; 8];
+                qword.copy_from_slice(&bytes);
+                Self::Qword(u64::from_le_bytes(qword))
+            }
+            REG_BINARY => Self::Binary(bytes),
+            _ => Self::Other(value_type, bytes),
+        }
+    }
+
+    /// Raw bytes of the data, as stored by Windows
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::String(string) | Self::ExpandString(string) => encode_utf16_string(string),
+            Self::MultiString(strings) => {
+                let mut bytes: Vec<u8> = strings
+                    .iter()
+                    .flat_map(|s| encode_utf16_string(s))
+                    .collect();
+                bytes.extend([0, 0]);
+                bytes
+            }
+            Self::Dword(dword) => dword.to_le_bytes().to_vec(),
+            Self::Qword(qword) => qword.to_le_bytes().to_vec(),
+            Self::Binary(bytes) | Self::Other(_, bytes) => bytes.clone(),
+        }
+    }
+}
+
+impl Display for FloraRegistryData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::String(string) | Self::ExpandString(string) => write!(f, "{}", string),
+            Self::MultiString(strings) => write!(f, "{}", strings.join("\n")),
+            Self::Dword(dword) => write!(f, "0x{:08x} ({})", dword, dword),
+            Self::Qword(qword) => write!(f, "0x{:016x} ({})", qword, qword),
+            Self::Binary(bytes) | Self::Other(_, bytes) => write!(f, "{}", format_hex(bytes)),
+        }
+    }
+}
+
+/// A value in a registry key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloraRegistryValue {
+    /// Name of the value, or None for the key's default value
+    pub name: Option<String>,
+    pub data: FloraRegistryData,
+    /// Value as read from the hive, written back unchanged to keep its exact form
+    raw: Option<String>,
+}
+
+impl FloraRegistryValue {
+    pub fn new(name: Option<&str>, data: FloraRegistryData) -> Self {
+        Self {
+            name: name.map(String::from),
+            data,
+            raw: None,
+        }
+    }
+
+    fn matches_name(&self, name: Option<&str>) -> bool {
+        match (&self.name, name) {
+            (Some(value_name), Some(name)) => value_name.eq_ignore_ascii_case(name),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A registry key and its values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloraRegistryKey {
+    /// Path relative to the hive's root key
+    pub name: String,
+    pub values: Vec<FloraRegistryValue>,
+    /// Last modification time, in seconds since the UNIX epoch
+    modified: Option<u64>,
+    /// Metadata lines following the key, such as `#time=` and `#class=`
+    metadata: Vec<String>,
+}
+
+impl FloraRegistryKey {
+    fn new(name: &str) -> Self {
+        Self {
+            name: String::from(name),
+            values: Vec::new(),
+            modified: None,
+            metadata: Vec::new(),
+        }
+    }
+
+    pub fn get_value(&self, name: Option<&str>) -> Option<&FloraRegistryValue> {
+        self.values.iter().find(|value| value.matches_name(name))
+    }
+
+    /// Marks the key as modified now, like wineserver does when saving a changed key
+    fn touch(&mut self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let filetime = (now + WINDOWS_EPOCH_OFFSET) * 10_000_000;
+
+        self.modified = Some(now);
+        self.metadata.retain(|line| !line.starts_with("#time="));
+        self.metadata.insert(0, format!("#time={:x}", filetime));
+    }
+}
+
+/// A Wine registry hive file, such as `user.reg`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FloraRegistryHive {
+    /// Lines before the first key, such as the version and `#arch=` lines
+    header: Vec<String>,
+    keys: Vec<FloraRegistryKey>,
+}
+
+impl FloraRegistryHive {
+    /// Parses a hive in Wine's registry format
+    pub(crate) fn parse(content: &str) -> Result<Self, FloraError> {
+        let mut header = Vec::new();
+        let mut keys: Vec<FloraRegistryKey> = Vec::new();
+
+        let mut lines = content.lines().enumerate();
+        while let Some((line_index, line)) = lines.next() {
+            let line_number = line_index + 1;
+            let parse_error =
+                |message: &str| FloraError::RegistryParseError(line_number, String::from(message));
+
+            if let Some(key_line) = line.strip_prefix('[') {
+                let (name, rest) =
+                    parse_wine_string(key_line, ']').ok_or(parse_error("Unterminated key name"))?;
+                let modified = rest.trim().parse().ok();
+
+                keys.push(FloraRegistryKey {
+                    modified,
+                    ..FloraRegistryKey::new(&name)
+                });
+                continue;
+            }
+
+            let Some(key) = keys.last_mut() else {
+                header.push(String::from(line));
+                continue;
+            };
+
+            if line.starts_with('#') {
+                key.metadata.push(String::from(line));
+            } else if line.starts_with('"') || line.starts_with('@') {
+                // Binary data is wrapped over multiple lines, ending each with a backslash
+                let mut raw = String::from(line);
+                let mut joined = String::from(line);
+                while joined.ends_with('\\') && is_hex_value(&joined) {
+                    let Some((_, next_line)) = lines.next() else {
+                        break;
+                    };
+                    joined.pop();
+                    joined.push_str(next_line.trim_start());
+                    raw.push('\n');
+                    raw.push_str(next_line);
+                }
+
+                let (name, data) = parse_wine_value(&joined).ok_or(parse_error("Invalid value"))?;
+                key.values.push(FloraRegistryValue {
+                    name,
+                    data,
+                    raw: Some(raw),
+                });
+            } else if !line.trim().is_empty() && !line.starts_with(";;") {
+                return Err(parse_error("Unexpected line"));
+            }
+        }
+
+        while header
+            .last()
+            .is_some_and(|line: &String| line.trim().is_empty())
+        {
+            header.pop();
+        }
+
+        Ok(Self { header, keys })
+    }
+
+    pub(crate) fn read(path: &Path) -> Result<Self, FloraError> {
+        let content = fs::read(path)?;
+        Self::parse(&String::from_utf8_lossy(&content))
+    }
+
+    /// Writes the hive to a temporary file first, so an interrupted write keeps the old hive
+    pub(crate) fn write(&self, path: &Path) -> Result<(), FloraError> {
+        let mut temp_path = path.to_path_buf();
+        temp_path.set_extension("reg.flora-tmp");
+
+        fs::write(&temp_path, self.to_string())?;
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+
+    fn key_index(&self, key_name: &str) -> Option<usize> {
+        self.keys
+            .iter()
+            .position(|key| key.name.eq_ignore_ascii_case(key_name))
+    }
+
+    pub(crate) fn get_key(&self, key_name: &str) -> Option<&FloraRegistryKey> {
+        self.key_index(key_name).map(|index| &self.keys[index])
+    }
+
+    /// Lists a key and all of its subkeys
+    pub(crate) fn get_key_tree(&self, key_name: &str) -> Vec<&FloraRegistryKey> {
+        self.keys
+            .iter()
+            .filter(|key| is_same_or_subkey(&key.name, key_name))
+            .collect()
+    }
+
+    fn get_or_create_key(&mut self, key_name: &str) -> &mut FloraRegistryKey {
+        let index = match self.key_index(key_name) {
+            Some(index) => index,
+            None => {
+                self.keys.push(FloraRegistryKey::new(key_name));
+                self.keys.len() - 1
+            }
+        };
+
+        &mut self.keys[index]
+    }
+
+    pub(crate) fn create_key(&mut self, key_name: &str) {
+        if self.key_index(key_name).is_none() {
+            self.get_or_create_key(key_name).touch();
+        }
+    }
+
+    /// Sets a value, creating its key if it doesn't exist
+    pub(crate) fn set_value(&mut self, key_name: &str, value: FloraRegistryValue) {
+        let key = self.get_or_create_key(key_name);
+
+        match key
+            .values
+            .iter_mut()
+            .find(|existing| existing.matches_name(value.name.as_deref()))
+        {
+            Some(existing) => *existing = value,
+            None => key.values.push(value),
+        }
+        key.touch();
+    }
+
+    pub(crate) fn delete_value(
+        &mut self,
+        key_name: &str,
+        value_name: Option<&str>,
+    ) -> Result<(), FloraError> {
+        let index = self
+            .key_index(key_name)
+            .ok_or(FloraError::RegistryKeyNotFound(String::from(key_name)))?;
+        let key = &mut self.keys[index];
+
+        let value_index = key
+            .values
+            .iter()
+            .position(|value| value.matches_name(value_name))
+            .ok_or(FloraError::RegistryValueNotFound(String::from(
+                value_name.unwrap_or("@"),
+            )))?;
+        key.values.remove(value_index);
+        key.touch();
+
+        Ok(())
+    }
+
+    /// Deletes a key and all of its subkeys
+    pub(crate) fn delete_key(&mut self, key_name: &str) -> Result<(), FloraError> {
+        if self.get_key_tree(key_name).is_empty() {
+            return Err(FloraError::RegistryKeyNotFound(String::from(key_name)));
+        }
+        self.keys
+            .retain(|key| !is_same_or_subkey(&key.name, key_name));
+
+        Ok(())
+    }
+}
+
+impl Display for FloraRegistryHive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.header {
+            writeln!(f, "{}", line)?;
+        }
+
+        for key in &self.keys {
+            write!(f, "\n[{}]", escape_wine_string(&key.name, ']'))?;
+            if let Some(modified) = key.modified {
+                write!(f, " {}", modified)?;
+            }
+            writeln!(f)?;
+
+            for line in &key.metadata {
+                writeln!(f, "{}", line)?;
+            }
+            for value in &key.values {
+                match &value.raw {
+                    Some(raw) => writeln!(f, "{}", raw)?,
+                    None => writeln!(f, "{}", format_wine_value(value))?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Registry of a Wine prefix
+pub struct FloraRegistry {
+    prefix: PathBuf,
+}
+
+impl FloraRegistry {
+    pub(crate) fn new(prefix: &Path) -> Self {
+        Self {
+            prefix: prefix.to_path_buf(),
+        }
+    }
+
+    fn hive_path(&self, root: FloraRegistryRoot) -> PathBuf {
+        let mut hive_path = self.prefix.clone();
+        hive_path.push(root.file_name());
+
+        hive_path
+    }
+
+    /// Whether Wine created the hive yet, which happens on the prefix's first launch
+    pub(crate) fn hive_exists(&self, root: FloraRegistryRoot) -> bool {
+        self.hive_path(root).exists()
+    }
+
+    fn read_hive(&self, root: FloraRegistryRoot) -> Result<FloraRegistryHive, FloraError> {
+        let hive_path = self.hive_path(root);
+        if !fs::exists(&hive_path)? {
+            return Err(FloraError::RegistryHiveNotFound(hive_path));
+        }
+
+        debug!("Reading registry hive {}", hive_path.display());
+        FloraRegistryHive::read(&hive_path)
+    }
+
+    /// Reads a hive for modification, refusing while wineserver may overwrite it
+    fn read_hive_for_write(
+        &self,
+        root: FloraRegistryRoot,
+    ) -> Result<FloraRegistryHive, FloraError> {
+        if is_wineserver_running(&self.prefix) {
+            return Err(FloraError::WineserverRunning(self.prefix.clone()));
+        }
+
+        self.read_hive(root)
+    }
+
+    fn write_hive(
+        &self,
+        root: FloraRegistryRoot,
+        hive: &FloraRegistryHive,
+    ) -> Result<(), FloraError> {
+        let hive_path = self.hive_path(root);
+
+        debug!("Writing registry hive {}", hive_path.display());
+        hive.write(&hive_path)
+    }
+
+    pub fn get_key(&self, key: &FloraRegistryKeyPath) -> Result<FloraRegistryKey, FloraError> {
+        self.read_hive(key.root)?
+            .get_key(&key.path)
+            .cloned()
+            .ok_or(FloraError::RegistryKeyNotFound(key.to_string()))
+    }
+
+    pub fn set_value(
+        &self,
+        key: &FloraRegistryKeyPath,
+        value: FloraRegistryValue,
+    ) -> Result<(), FloraError> {
+        let mut hive = self.read_hive_for_write(key.root)?;
+        hive.set_value(&key.path, value);
+
+        self.write_hive(key.root, &hive)
+    }
+
+    pub fn delete_value(
+        &self,
+        key: &FloraRegistryKeyPath,
+        value_name: Option<&str>,
+    ) -> Result<(), FloraError> {
+        let mut hive = self.read_hive_for_write(key.root)?;
+        hive.delete_value(&key.path, value_name)?;
+
+        self.write_hive(key.root, &hive)
+    }
+
+    pub fn delete_key(&self, key: &FloraRegistryKeyPath) -> Result<(), FloraError> {
+        let mut hive = self.read_hive_for_write(key.root)?;
+        hive.delete_key(&key.path)
+            .map_err(|_| FloraError::RegistryKeyNotFound(key.to_string()))?;
+
+        self.write_hive(key.root, &hive)
+    }
+
+    /// Exports a key and its subkeys in the .reg format used by regedit
+    pub fn export(&self, key: &FloraRegistryKeyPath) -> Result<String, FloraError> {
+        let hive = self.read_hive(key.root)?;
+        let keys = hive.get_key_tree(&key.path);
+        if keys.is_empty() {
+            return Err(FloraError::RegistryKeyNotFound(key.to_string()));
+        }
+
+        let mut export = format!("{}\n", REGEDIT_HEADER);
+        for key_entry in keys {
+            export.push_str(&format!("\n[{}\\{}]\n", key.root, key_entry.name));
+            for value in &key_entry.values {
+                export.push_str(&format_regedit_value(value));
+                export.push('\n');
+            }
+        }
+
+        Ok(export)
+    }
+
+    /// Imports keys and values from a .reg file in the format used by regedit
+    pub fn import(&self, content: &str) -> Result<(), FloraError> {
+        let operations = parse_regedit(content)?;
+
+        let mut hives: Vec<(FloraRegistryRoot, FloraRegistryHive)> = Vec::new();
+        for operation in operations {
+            let root = operation.key().root;
+            let index = match hives.iter().position(|(hive_root, _)| *hive_root == root) {
+                Some(index) => index,
+                None => {
+                    hives.push((root, self.read_hive_for_write(root)?));
+                    hives.len() - 1
+                }
+            };
+            let hive = &mut hives[index].1;
+
+            match operation {
+                FloraRegeditOperation::CreateKey(key) => hive.create_key(&key.path),
+                FloraRegeditOperation::DeleteKey(key) => {
+                    // Deleting a missing key is not an error in regedit
+                    let _ = hive.delete_key(&key.path);
+                }
+                FloraRegeditOperation::SetValue(key, value) => hive.set_value(&key.path, value),
+                FloraRegeditOperation::DeleteValue(key, value_name) => {
+                    let _ = hive.delete_value(&key.path, value_name.as_deref());
+                }
+            }
+        }
+
+        for (root, hive) in hives {
+            self.write_hive(root, &hive)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks whether a wineserver is serving the prefix, by connecting to its socket
+pub(crate) fn is_wineserver_running(prefix: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(prefix) else {
+        return false;
+    };
+
+    // wineserver keeps its socket in a directory named after the prefix's device and inode
+    let socket_path = format!(
+        "/tmp/.wine-{}/server-{:x}-{:x}/socket",
+        getuid(),
+        metadata.dev(),
+        metadata.ino()
+    );
+    debug!("Checking wineserver socket at {}", socket_path);
+
+    UnixStream::connect(socket_path).is_ok()
+}
+
+fn join_key_path(parent: &str, child: &str) -> String {
+    if child.is_empty() {
+        String::from(parent)
+    } else {
+        format!("{}\\{}", parent, child)
+    }
+}
+
+fn is_same_or_subkey(key_name: &str, parent: &str) -> bool {
+    let key_name = key_name.to_lowercase();
+    let parent = parent.to_lowercase();
+
+    key_name == parent || key_name.starts_with(&format!("{}\\", parent))
+}
+
+fn decode_utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+
+    String::from_utf16_lossy(&units)
+}
+
+/// Decodes a null terminated UTF-16 string, dropping the terminator
+fn decode_utf16_string(bytes: &[u8]) -> String {
+    let string = decode_utf16(bytes);
+
+    match string.strip_suffix('\0') {
+        Some(string) => String::from(string),
+        None => string,
+    }
+}
+
+/// Encodes a string as null terminated UTF-16
+fn encode_utf16_string(string: &str) -> Vec<u8> {
+    string
+        .encode_utf16()
+        .chain([0])
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Splits the contents of a REG_MULTI_SZ value, ignoring the terminating empty strings
+fn split_multi_string(string: &str) -> Vec<String> {
+    let mut strings: Vec<String> = string.split('\0').map(String::from).collect();
+    while strings.last().is_some_and(String::is_empty) {
+        strings.pop();
+    }
+
+    strings
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    hex.split(',')
+        .map(str::trim)
+        .filter(|byte| !byte.is_empty())
+        .map(|byte| u8::from_str_radix(byte, 16).ok())
+        .collect()
+}
+
+/// Formats hex data like Wine and regedit, wrapping lines at 80 columns
+fn format_wrapped_hex(prefix: &str, bytes: &[u8]) -> String {
+    let mut formatted = String::from(prefix);
+    let mut column = prefix.len();
+
+    for (index, byte) in bytes.iter().enumerate() {
+        formatted.push_str(&format!("{:02x}", byte));
+        column += 2;
+
+        if index + 1 < bytes.len() {
+            formatted.push(',');
+            column += 1;
+            if column > 76 {
+                formatted.push_str("\\\n  ");
+                column = 2;
+            }
+        }
+    }
+
+    formatted
+}
+
+fn is_hex_value(line: &str) -> bool {
+    parse_value_name(line, parse_wine_string).is_some_and(|(_, data)| data.starts_with("hex"))
+}
+
+/// Escapes a string like wineserver, using C escapes and \x for non-ASCII characters
+fn escape_wine_string(string: &str, quote: char) -> String {
+    let units: Vec<u16> = string.encode_utf16().collect();
+    let mut escaped = String::new();
+
+    for (index, unit) in units.iter().enumerate() {
+        let unit = *unit;
+        match unit {
+            0x07 => escaped.push_str("\\a"),
+            0x08 => escaped.push_str("\\b"),
+            0x09 => escaped.push_str("\\t"),
+            0x0a => escaped.push_str("\\n"),
+            0x0b => escaped.push_str("\\v"),
+            0x0c => escaped.push_str("\\f"),
+            0x0d => escaped.push_str("\\r"),
+            0x1b => escaped.push_str("\\e"),
+            0..0x20 => escaped.push_str(&format!("\\{:03o}", unit)),
+            0x80.. => {
+                // A following hex digit would be read as part of a short escape
+                let next_is_hex = units
+                    .get(index + 1)
+                    .and_then(|next| char::from_u32(*next as u32))
+                    .is_some_and(|next| next.is_ascii_hexdigit());
+                if next_is_hex {
+                    escaped.push_str(&format!("\\x{:04x}", unit));
+                } else {
+                    escaped.push_str(&format!("\\x{:x}", unit));
+                }
+            }
+            _ => {
+                let c = unit as u8 as char;
+                if c == '\\' || c == quote {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+        }
+    }
+
+    escaped
+}
+
+/// Parses a string escaped like wineserver up to its closing quote, returning the rest
+fn parse_wine_string(input: &str, quote: char) -> Option<(String, &str)> {
+    let mut units: Vec<u16> = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        if c == quote {
+            return Some((
+                String::from_utf16_lossy(&units),
+                &input[index + c.len_utf8()..],
+            ));
+        }
+        if c != '\\' {
+            let mut buffer = [0u16; 2];
+            units.extend_from_slice(c.encode_utf16(&mut buffer));
+            continue;
+        }
+
+        let (_, escape) = chars.next()?;
+        let unit = match escape {
+            'a' => 0x07,
+            'b' => 0x08,
+            'e' => 0x1b,
+            'f' => 0x0c,
+            'n' => 0x0a,
+            'r' => 0x0d,
+            't' => 0x09,
+            'v' => 0x0b,
+            'x' => {
+                let mut value = 0u16;
+                for _ in 0..4 {
+                    match chars.peek().and_then(|(_, digit)| digit.to_digit(16)) {
+                        Some(digit) => {
+                            value = value * 16 + digit as u16;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                value
+            }
+            '0'..='7' => {
+                let mut value = escape.to_digit(8)? as u16;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|(_, digit)| digit.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit as u16;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                value
+            }
+            other => {
+                let mut buffer = [0u16; 2];
+                units.extend_from_slice(other.encode_utf16(&mut buffer));
+                continue;
+            }
+        };
+        units.push(unit);
+    }
+
+    None
+}
+
+/// Parses a string escaped like regedit up to its closing quote, returning the rest
+fn parse_regedit_string(input: &str, quote: char) -> Option<(String, &str)> {
+    let mut string = String::new();
+    let mut chars = input.char_indices();
+
+    while let Some((index, c)) = chars.next() {
+        if c == quote {
+            return Some((string, &input[index + c.len_utf8()..]));
+        }
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+
+        match chars.next()?.1 {
+            'n' => string.push('\n'),
+            'r' => string.push('\r'),
+            '0' => string.push('\0'),
+            other => string.push(other),
+        }
+    }
+
+    None
+}
+
+fn escape_regedit_string(string: &str) -> String {
+    let mut escaped = String::new();
+    for c in string.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+type StringParser = fn(&str, char) -> Option<(String, &str)>;
+
+/// Splits a value line into its name and the text after the equals sign
+fn parse_value_name(line: &str, parse_string: StringParser) -> Option<(Option<String>, &str)> {
+    let (name, rest) = if let Some(rest) = line.strip_prefix('@') {
+        (None, rest)
+    } else {
+        let (name, rest) = parse_string(line.strip_prefix('"')?, '"')?;
+        (Some(name), rest)
+    };
+
+    Some((name, rest.trim_start().strip_prefix('=')?.trim_start()))
+}
+
+/// Parses hex data with an optional type, such as `hex(2):41,00,00,00`
+fn parse_hex_data(data: &str) -> Option<FloraRegistryData> {
+    let rest = data.strip_prefix("hex")?;
+    let (value_type, hex) = if let Some(hex) = rest.strip_prefix(':') {
+        (REG_BINARY, hex)
+    } else {
+        let (value_type, hex) = rest.strip_prefix('(')?.split_once("):")?;
+        (u32::from_str_radix(value_type, 16).ok()?, hex)
+    };
+
+    Some(FloraRegistryData::from_bytes(
+        value_type,
+        parse_hex_bytes(hex)?,
+    ))
+}
+
+fn parse_dword(data: &str) -> Option<FloraRegistryData> {
+    let dword = data.strip_prefix("dword:")?.trim();
+
+    Some(FloraRegistryData::Dword(
+        u32::from_str_radix(dword, 16).ok()?,
+    ))
+}
+
+/// Parses a value line in Wine's registry format
+fn parse_wine_value(line: &str) -> Option<(Option<String>, FloraRegistryData)> {
+    let (name, data) = parse_value_name(line, parse_wine_string)?;
+
+    let data = if let Some(string) = data.strip_prefix('"') {
+        FloraRegistryData::String(parse_wine_string(string, '"')?.0)
+    } else if let Some(rest) = data.strip_prefix("str(") {
+        let (value_type, string) = rest.split_once("):\"")?;
+        let value_type = u32::from_str_radix(value_type, 16).ok()?;
+        let (string, _) = parse_wine_string(string, '"')?;
+
+        match value_type {
+            REG_SZ => FloraRegistryData::String(string),
+            REG_EXPAND_SZ => FloraRegistryData::ExpandString(string),
+            REG_MULTI_SZ => FloraRegistryData::MultiString(split_multi_string(&string)),
+            _ => FloraRegistryData::Other(value_type, encode_utf16_string(&string)),
+        }
+    } else if data.starts_with("dword:") {
+        parse_dword(data)?
+    } else {
+        parse_hex_data(data)?
+    };
+
+    Some((name, data))
+}
+
+fn format_value_name(name: &Option<String>, escape: impl Fn(&str) -> String) -> String {
+    match name {
+        Some(name) => format!("\"{}\"", escape(name)),
+        None => String::from("@"),
+    }
+}
+
+/// Formats a value in Wine's registry format
+fn format_wine_value(value: &FloraRegistryValue) -> String {
+    let name = format_value_name(&value.name, |name| escape_wine_string(name, '"'));
+
+    match &value.data {
+        FloraRegistryData::String(string) => {
+            format!("{}=\"{}\"", name, escape_wine_string(string, '"'))
+        }
+        FloraRegistryData::ExpandString(string) => {
+            format!("{}=str(2):\"{}\"", name, escape_wine_string(string, '"'))
+        }
+        FloraRegistryData::MultiString(strings) => {
+            let string: String = strings.iter().map(|s| format!("{}\0", s)).collect();
+            format!("{}=str(7):\"{}\"", name, escape_wine_string(&string, '"'))
+        }
+        FloraRegistryData::Dword(dword) => format!("{}=dword:{:08x}", name, dword),
+        data => format_hex_value(&name, data),
+    }
+}
+
+/// Formats a value in the .reg format used by regedit
+fn format_regedit_value(value: &FloraRegistryValue) -> String {
+    let name = format_value_name(&value.name, escape_regedit_string);
+
+    match &value.data {
+        FloraRegistryData::String(string) => {
+            format!("{}=\"{}\"", name, escape_regedit_string(string))
+        }
+        FloraRegistryData::Dword(dword) => format!("{}=dword:{:08x}", name, dword),
+        data => format_hex_value(&name, data),
+    }
+}
+
+fn format_hex_value(name: &str, data: &FloraRegistryData) -> String {
+    let prefix = match data.value_type() {
+        REG_BINARY => format!("{}=hex:", name),
+        value_type => format!("{}=hex({:x}):", name, value_type),
+    };
+
+    format_wrapped_hex(&prefix, &data.to_bytes())
+}
+
+/// A change described by a .reg file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FloraRegeditOperation {
+    CreateKey(FloraRegistryKeyPath),
+    DeleteKey(FloraRegistryKeyPath),
+    SetValue(FloraRegistryKeyPath, FloraRegistryValue),
+    DeleteValue(FloraRegistryKeyPath, Option<String>),
+}
+
+impl FloraRegeditOperation {
+    fn key(&self) -> &FloraRegistryKeyPath {
+        match self {
+            Self::CreateKey(key)
+            | Self::DeleteKey(key)
+            | Self::SetValue(key, _)
+            | Self::DeleteValue(key, _) => key,
+        }
+    }
+}
+
+/// Parses a .reg file in the format used by regedit
+fn parse_regedit(content: &str) -> Result<Vec<FloraRegeditOperation>, FloraError> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut lines = content.lines().enumerate();
+
+    match lines.next() {
+        Some((_, header)) if [REGEDIT_HEADER, REGEDIT4_HEADER].contains(&header.trim()) => {}
+        _ => {
+            return Err(FloraError::RegistryParseError(
+                1,
+                String::from("Missing regedit header"),
+            ));
+        }
+    }
+
+    let mut operations = Vec::new();
+    let mut current_key: Option<FloraRegistryKeyPath> = None;
+
+    while let Some((line_index, line)) = lines.next() {
+        let line_number = line_index + 1;
+        let parse_error =
+            |message: &str| FloraError::RegistryParseError(line_number, String::from(message));
+
+        let mut line = String::from(line.trim_end());
+        while line.ends_with('\\') && line.contains("=hex") {
+            let Some((_, next_line)) = lines.next() else {
+                break;
+            };
+            line.pop();
+            line.push_str(next_line.trim());
+        }
+
+        if line.trim().is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(key_line) = line.strip_prefix('[') {
+            let key_line = key_line
+                .strip_suffix(']')
+                .ok_or(parse_error("Unterminated key name"))?;
+
+            if let Some(key_name) = key_line.strip_prefix('-') {
+                operations.push(FloraRegeditOperation::DeleteKey(key_name.parse()?));
+                current_key = None;
+            } else {
+                let key: FloraRegistryKeyPath = key_line.parse()?;
+                operations.push(FloraRegeditOperation::CreateKey(key.clone()));
+                current_key = Some(key);
+            }
+            continue;
+        }
+
+        let key = current_key
+            .clone()
+            .ok_or(parse_error("Value outside of a key"))?;
+        let (name, data) =
+            parse_value_name(&line, parse_regedit_string).ok_or(parse_error("Invalid value"))?;
+
+        if data == "-" {
+            operations.push(FloraRegeditOperation::DeleteValue(key, name));
+            continue;
+        }
+
+        let data = if let Some(string) = data.strip_prefix('"') {
+            FloraRegistryData::String(
+                parse_regedit_string(string, '"')
+                    .ok_or(parse_error("Unterminated string"))?
+                    .0,
+            )
+        } else if data.starts_with("dword:") {
+            parse_dword(data).ok_or(parse_error("Invalid dword"))?
+        } else {
+            parse_hex_data(data).ok_or(parse_error("Invalid value data"))?
+        };
+
+        operations.push(FloraRegeditOperation::SetValue(
+            key,
+            FloraRegistryValue::new(name.as_deref(), data),
+        ));
+    }
+
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER_REG: &str = r#"WINE REGISTRY Version 2
+;; All keys relative to \\User\\S-1-5-21-0-0-0-1000
+
+#arch=win64
+
+[Control Panel\\Desktop] 1700000000
+#time=1da1234567890ab
+"FontSmoothing"="2"
+"WheelScrollLines"="3"
+
+[Software\\Wine\\DllOverrides] 1700000001
+#time=1da1234567890ac
+"d3d9"="native,builtin"
+@="default"
+
+[Software\\Wine\\Test] 1700000002
+#time=1da1234567890ad
+"Path"=str(2):"%SystemRoot%\\system32"
+"List"=str(7):"one\0two\0"
+"Flag"=dword:0000002a
+"Blob"=hex:00,01,02,03,04,05,06,07,08,09,0a,0b,0c,0d,0e,0f,10,11,12,13,14,15,16,\
+  17,18
+"Big"=hex(b):01,00,00,00,00,00,00,00
+"Unicode"="caf\xe9 \x00e9x"
+"#;
+
+    fn key(path: &str) -> FloraRegistryKeyPath {
+        path.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_wine_hive() {
+        let hive = FloraRegistryHive::parse(USER_REG).unwrap();
+
+        let test_key = hive.get_key("software\\wine\\test").unwrap();
+        assert_eq!(
+            test_key.get_value(Some("path")).unwrap().data,
+            FloraRegistryData::ExpandString(String::from("%SystemRoot%\\system32"))
+        );
+        assert_eq!(
+            test_key.get_value(Some("List")).unwrap().data,
+            FloraRegistryData::MultiString(vec![String::from("one"), String::from("two")])
+        );
+        assert_eq!(
+            test_key.get_value(Some("Flag")).unwrap().data,
+            FloraRegistryData::Dword(42)
+        );
+        assert_eq!(
+            test_key.get_value(Some("Blob")).unwrap().data,
+            FloraRegistryData::Binary((0..=0x18).collect())
+        );
+        assert_eq!(
+            test_key.get_value(Some("Big")).unwrap().data,
+            FloraRegistryData::Qword(1)
+        );
+        assert_eq!(
+            test_key.get_value(Some("Unicode")).unwrap().data,
+            FloraRegistryData::String(String::from("café éx"))
+        );
+
+        let overrides = hive.get_key("Software\\Wine\\DllOverrides").unwrap();
+        assert_eq!(
+            overrides.get_value(None).unwrap().data,
+            FloraRegistryData::String(String::from("default"))
+        );
+    }
+
+    #[test]
+    fn writes_unchanged_hive_verbatim() {
+        let hive = FloraRegistryHive::parse(USER_REG).unwrap();
+
+        assert_eq!(hive.to_string(), USER_REG);
+    }
+
+    #[test]
+    fn round_trips_modified_values() {
+        let mut hive = FloraRegistryHive::parse(USER_REG).unwrap();
+        let values = [
+            FloraRegistryData::String(String::from("quote \" back\\slash\nnew caf\u{e9}1")),
+            FloraRegistryData::ExpandString(String::from("%ProgramFiles%")),
+            FloraRegistryData::MultiString(vec![String::from("a"), String::from("b")]),
+            FloraRegistryData::Dword(0xdeadbeef),
+            FloraRegistryData::Qword(u64::MAX),
+            FloraRegistryData::Binary((0..100).collect()),
+        ];
+        for (index, data) in values.iter().enumerate() {
+            let name = format!("Value{}", index);
+            hive.set_value(
+                "Software\\Flora",
+                FloraRegistryValue::new(Some(&name), data.clone()),
+            );
+        }
+
+        let reparsed = FloraRegistryHive::parse(&hive.to_string()).unwrap();
+        let key = reparsed.get_key("Software\\Flora").unwrap();
+        for (index, data) in values.iter().enumerate() {
+            let name = format!("Value{}", index);
+            assert_eq!(&key.get_value(Some(&name)).unwrap().data, data);
+        }
+        assert!(key.modified.is_some());
+    }
+
+    #[test]
+    fn deletes_values_and_key_trees() {
+        let mut hive = FloraRegistryHive::parse(USER_REG).unwrap();
+
+        hive.delete_value("Control Panel\\Desktop", Some("fontsmoothing"))
+            .unwrap();
+        assert!(
+            hive.get_key("Control Panel\\Desktop")
+                .unwrap()
+                .get_value(Some("FontSmoothing"))
+                .is_none()
+        );
+        assert!(
+            hive.delete_value("Control Panel\\Desktop", Some("Missing"))
+                .is_err()
+        );
+
+        hive.delete_key("Software\\Wine").unwrap();
+        assert!(hive.get_key("Software\\Wine\\DllOverrides").is_none());
+        assert!(hive.get_key("Software\\Wine\\Test").is_none());
+        assert!(hive.get_key("Control Panel\\Desktop").is_some());
+    }
+
+    #[test]
+    fn parses_key_paths() {
+        assert_eq!(
+            key("HKCU\\Software\\Wine\\"),
+            FloraRegistryKeyPath {
+                root: FloraRegistryRoot::CurrentUser,
+                path: String::from("Software\\Wine"),
+            }
+        );
+        assert_eq!(
+            key("hkey_local_machine/System/CurrentControlSet"),
+            FloraRegistryKeyPath {
+                root: FloraRegistryRoot::LocalMachine,
+                path: String::from("System\\CurrentControlSet"),
+            }
+        );
+        assert_eq!(key("HKCR\\.txt").path, "Software\\Classes\\.txt");
+        assert!("HKU\\Software".parse::<FloraRegistryKeyPath>().is_err());
+        assert!("HKCU".parse::<FloraRegistryKeyPath>().is_err());
+    }
+
+    #[test]
+    fn parses_regedit_files() {
+        let content = "Windows Registry Editor Version 5.00\n\
+            \n\
+            ; Comment\n\
+            [HKEY_CURRENT_USER\\Software\\Wine]\n\
+            \"Version\"=\"win10\"\n\
+            \"Path\"=\"C:\\\\Games\"\n\
+            \"Old\"=-\n\
+            \"Expand\"=hex(2):25,00,41,00,25,00,00,00\n\
+            \"Long\"=hex:00,01,\\\n  02\n\
+            \n\
+            [-HKEY_LOCAL_MACHINE\\Software\\Old]\n";
+
+        let operations = parse_regedit(content).unwrap();
+        let wine_key = key("HKCU\\Software\\Wine");
+        assert_eq!(
+            operations,
+            vec![
+                FloraRegeditOperation::CreateKey(wine_key.clone()),
+                FloraRegeditOperation::SetValue(
+                    wine_key.clone(),
+                    FloraRegistryValue::new(
+                        Some("Version"),
+                        FloraRegistryData::String(String::from("win10"))
+                    )
+                ),
+                FloraRegeditOperation::SetValue(
+                    wine_key.clone(),
+                    FloraRegistryValue::new(
+                        Some("Path"),
+                        FloraRegistryData::String(String::from("C:\\Games"))
+                    )
+                ),
+                FloraRegeditOperation::DeleteValue(wine_key.clone(), Some(String::from("Old"))),
+                FloraRegeditOperation::SetValue(
+                    wine_key.clone(),
+                    FloraRegistryValue::new(
+                        Some("Expand"),
+                        FloraRegistryData::ExpandString(String::from("%A%"))
+                    )
+                ),
+                FloraRegeditOperation::SetValue(
+                    wine_key,
+                    FloraRegistryValue::new(Some("Long"), FloraRegistryData::Binary(vec![0, 1, 2]))
+                ),
+                FloraRegeditOperation::DeleteKey(key("HKLM\\Software\\Old")),
+            ]
+        );
+
+        assert!(parse_regedit("[HKEY_CURRENT_USER\\Software]\n").is_err());
+    }
+
+    #[test]
+    fn exported_values_import_back() {
+        let hive = FloraRegistryHive::parse(USER_REG).unwrap();
+        let test_key = hive.get_key("Software\\Wine\\Test").unwrap();
+
+        let mut content = format!(
+            "{}\n\n[HKEY_CURRENT_USER\\Software\\Wine\\Test]\n",
+            REGEDIT_HEADER
+        );
+        for value in &test_key.values {
+            content.push_str(&format_regedit_value(value));
+            content.push('\n');
+        }
+
+        let imported: Vec<_> = parse_regedit(&content)
+            .unwrap()
+            .into_iter()
+            .filter_map(|operation| match operation {
+                FloraRegeditOperation::SetValue(_, value) => Some(value.data),
+                _ => None,
+            })
+            .collect();
+        let original: Vec<_> = test_key
+            .values
+            .iter()
+            .map(|value| value.data.clone())
+            .collect();
+        assert_eq!(imported, original);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::seed::{FloraGamescopeSettings, FloraGamescopeWindowMode};
+
+/// Builds the gamescope wrapper command, ending with the `--` separator
+pub(crate) fn gamescope_wrapper(settings: &FloraGamescopeSettings) -> Vec<String> {
+    let mut wrapper = vec![String::from("gamescope")];
+
+    let numeric_args = [
+        ("-W", settings.output_width),
+        ("-H", settings.output_height),
+        ("-w", settings.nested_width),
+        ("-h", settings.nested_height),
+        ("-r", settings.refresh_rate),
+    ];
+    for (flag, value) in numeric_args {
+        if let Some(value) = value {
+            wrapper.push(String::from(flag));
+            wrapper.push(value.to_string());
+        }
+    }
+
+    match settings.window_mode {
+        Some(FloraGamescopeWindowMode::Fullscreen) => wrapper.push(String::from("-f")),
+        Some(FloraGamescopeWindowMode::Borderless) => wrapper.push(String::from("-b")),
+        Some(FloraGamescopeWindowMode::Windowed) | None => {}
+    }
+
+    if let Some(filter) = settings.filter {
+        wrapper.push(String::from("-F"));
+        wrapper.push(filter.to_string());
+    }
+    if let Some(scaler) = settings.scaler {
+        wrapper.push(String::from("-S"));
+        wrapper.push(scaler.to_string());
+    }
+
+    if settings.hdr {
+        wrapper.push(String::from("--hdr-enabled"));
+    }
+    if let Some(extra_args) = &settings.extra_args {
+        wrapper.extend(extra_args.iter().cloned());
+    }
+
+    wrapper.push(String::from("--"));
+
+    wrapper
+}
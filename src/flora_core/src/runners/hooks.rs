@@ -0,0 +1,234 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use log::{debug, warn};
+
+use crate::{
+    dirs::FloraDirs,
+    errors::FloraError,
+    process::{FloraRunResult, FloraWaitMode},
+    seed::{FloraHook, FloraHookFailure, FloraSeedApp, FloraSeedSettings},
+};
+
+#[derive(Debug, Clone, Copy)]
+enum FloraHookStage {
+    PreLaunch,
+    PostExit,
+}
+
+impl Display for FloraHookStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FloraHookStage::PreLaunch => write!(f, "pre_launch"),
+            FloraHookStage::PostExit => write!(f, "post_exit"),
+        }
+    }
+}
+
+/// Hooks to run around a launch, in execution order
+struct FloraHooks {
+    pre_launch: Vec<FloraHook>,
+    post_exit: Vec<FloraHook>,
+}
+
+impl FloraHooks {
+    fn new(settings: &Option<Box<FloraSeedSettings>>, app: Option<&FloraSeedApp>) -> Self {
+        let (seed_pre_launch, seed_post_exit) = settings
+            .as_ref()
+            .map(|settings| {
+                (
+                    settings.pre_launch.clone().unwrap_or_default(),
+                    settings.post_exit.clone().unwrap_or_default(),
+                )
+            })
+            .unwrap_or_default();
+        let (app_pre_launch, app_post_exit) = app
+            .map(|app| {
+                (
+                    app.pre_launch.clone().unwrap_or_default(),
+                    app.post_exit.clone().unwrap_or_default(),
+                )
+            })
+            .unwrap_or_default();
+
+        // Seed hooks wrap around app hooks
+        Self {
+            pre_launch: [seed_pre_launch, app_pre_launch].concat(),
+            post_exit: [app_post_exit, seed_post_exit].concat(),
+        }
+    }
+}
+
+fn run_hook(
+    dirs: &FloraDirs,
+    name: &str,
+    stage: FloraHookStage,
+    hook: &FloraHook,
+    env: &BTreeMap<String, String>,
+) -> Result<(), FloraError> {
+    let mut log_file = dirs.get_log_file(name)?;
+    writeln!(log_file, "[flora] Running {} hook: {}", stage, hook.command)?;
+    debug!("Running {} hook: {}", stage, hook.command);
+
+    let log_out = dirs.get_log_file(name)?;
+    let log_err = dirs.get_log_file(name)?;
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(&hook.command)
+        .envs(env)
+        .stdin(Stdio::null())
+        .stdout(log_out)
+        .stderr(log_err)
+        .status();
+
+    let failure = match status {
+        Ok(status) => {
+            writeln!(log_file, "[flora] {} hook exited with {}", stage, status)?;
+            (!status.success()).then(|| status.to_string())
+        }
+        Err(e) => {
+            writeln!(log_file, "[flora] {} hook failed to start: {}", stage, e)?;
+            Some(e.to_string())
+        }
+    };
+
+    match (failure, hook.on_failure) {
+        (None, _) | (Some(_), FloraHookFailure::Ignore) => Ok(()),
+        (Some(failure), FloraHookFailure::Warn) => {
+            warn!("{} hook {} failed: {}", stage, hook.command, failure);
+            Ok(())
+        }
+        (Some(failure), FloraHookFailure::Abort) => {
+            Err(FloraError::HookFailed(hook.command.clone(), failure))
+        }
+    }
+}
+
+/// Launches a process, surrounded by the seed's and app's hooks.
+///
+/// Pre-launch hooks run seed first, then app. Post-exit hooks run app first, then seed,
+/// and force the launch to wait for the process to exit. They also run when the launch fails or a
+/// pre-launch hook aborts it, so the setup done by pre-launch hooks is undone, and the first
+/// error is returned afterwards.
+pub(crate) fn run_with_hooks(
+    dirs: &FloraDirs,
+    name: &str,
+    settings: &Option<Box<FloraSeedSettings>>,
+    app: Option<&FloraSeedApp>,
+    env: &BTreeMap<String, String>,
+    wait: FloraWaitMode,
+    launch: impl FnOnce(FloraWaitMode) -> Result<FloraRunResult, FloraError>,
+) -> Result<FloraRunResult, FloraError> {
+    let hooks = FloraHooks::new(settings, app);
+
+    let pre_launch_result = hooks
+        .pre_launch
+        .iter()
+        .try_for_each(|hook| run_hook(dirs, name, FloraHookStage::PreLaunch, hook, env));
+
+    let wait = if !hooks.post_exit.is_empty() && wait == FloraWaitMode::NoWait {
+        debug!("Post-exit hooks defined, waiting for process to exit");
+        FloraWaitMode::Process
+    } else {
+        wait
+    };
+    let result = pre_launch_result.and_then(|()| launch(wait));
+
+    let post_exit_result = hooks
+        .post_exit
+        .iter()
+        .try_for_each(|hook| run_hook(dirs, name, FloraHookStage::PostExit, hook, env));
+
+    let result = result?;
+    post_exit_result?;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fs};
+
+    use crate::{
+        dirs::FloraDirs,
+        errors::FloraError,
+        process::FloraWaitMode,
+        runners::hooks::run_with_hooks,
+        seed::{FloraHook, FloraHookFailure, FloraSeedSettings},
+    };
+
+    #[test]
+    fn post_exit_after_failed_launch() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().to_path_buf()).unwrap();
+        fs::create_dir_all(dirs.get_log_root()).unwrap();
+        let marker = root.path().join("post_exit");
+
+        let settings = Some(Box::new(FloraSeedSettings {
+            post_exit: Some(vec![FloraHook {
+                command: format!("touch '{}'", marker.display()),
+                on_failure: FloraHookFailure::Abort,
+            }]),
+            ..Default::default()
+        }));
+
+        let result = run_with_hooks(
+            &dirs,
+            "seed",
+            &settings,
+            None,
+            &BTreeMap::new(),
+            FloraWaitMode::NoWait,
+            |wait| {
+                assert_eq!(wait, FloraWaitMode::Process);
+                Err(FloraError::RunnerNone)
+            },
+        );
+
+        assert!(matches!(result, Err(FloraError::RunnerNone)));
+        assert!(marker.exists());
+    }
+
+    #[test]
+    fn post_exit_after_aborted_pre_launch() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().to_path_buf()).unwrap();
+        fs::create_dir_all(dirs.get_log_root()).unwrap();
+        let marker = root.path().join("post_exit");
+
+        let settings = Some(Box::new(FloraSeedSettings {
+            pre_launch: Some(vec![
+                FloraHook {
+                    command: String::from("true"),
+                    on_failure: FloraHookFailure::Abort,
+                },
+                FloraHook {
+                    command: String::from("false"),
+                    on_failure: FloraHookFailure::Abort,
+                },
+            ]),
+            post_exit: Some(vec![FloraHook {
+                command: format!("touch '{}'", marker.display()),
+                on_failure: FloraHookFailure::Abort,
+            }]),
+            ..Default::default()
+        }));
+
+        let result = run_with_hooks(
+            &dirs,
+            "seed",
+            &settings,
+            None,
+            &BTreeMap::new(),
+            FloraWaitMode::NoWait,
+            |_| panic!("launched after a pre-launch hook aborted"),
+        );
+
+        assert!(matches!(result, Err(FloraError::HookFailed(command, _)) if command == "false"));
+        assert!(marker.exists());
+    }
+}
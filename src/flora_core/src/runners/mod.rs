@@ -1,34 +1,70 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use log::{debug, info, warn};
+
 use crate::{
     config::FloraConfig,
     dirs::FloraDirs,
     errors::FloraError,
+    process::{FloraRunResult, FloraWaitMode},
+    registry::{
+        FloraRegistry, FloraRegistryData, FloraRegistryKeyPath, FloraRegistryRoot,
+        FloraRegistryValue,
+    },
     runners::{proton::FloraProtonRunner, wine::FloraWineRunner},
-    seed::{FloraSeed, FloraSeedApp, FloraSeedType},
-    start_menu::FloraSeedStartMenuItem,
+    seed::{
+        self, FloraDllOverrideMode, FloraSeed, FloraSeedApp, FloraSeedSettings, FloraSeedType,
+        FloraWindowsVersion,
+    },
 };
 
+/// Gamescope wrapper
+mod gamescope;
+/// Launch hooks
+mod hooks;
 /// Proton runner
 pub mod proton;
 /// Wine runner
 pub mod wine;
 
 pub trait FloraRunner {
+    fn get_prefix(&self) -> &Path;
+    fn get_seed_name(&self) -> &str;
+    /// Name of the runner, as shown to users
+    fn get_runner_name(&self) -> &'static str;
+    /// User whose profile in the prefix holds the Start Menu
+    fn get_prefix_user(&self) -> String;
+    fn init_prefix(&self, quiet: bool) -> Result<FloraRunResult, FloraError>;
     fn run_config(
         &self,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError>;
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError>;
     fn run_tricks(
         &self,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError>;
-    fn run_executable(&self, args: &[&str], quiet: bool, wait: bool) -> Result<(), FloraError>;
-    fn create_desktop_entry(&self, app: &FloraSeedApp) -> Result<(), FloraError>;
-    fn get_start_menu_entry_location(&self, menu_name: &str) -> Result<String, FloraError>;
-    fn list_start_menu_entries(&self) -> Result<Vec<FloraSeedStartMenuItem>, FloraError>;
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError>;
+    fn run_executable(
+        &self,
+        args: &[&str],
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError>;
+    fn run_app(
+        &self,
+        app: &FloraSeedApp,
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError>;
+    fn kill(&self) -> Result<(), FloraError>;
 }
 
 pub fn create_runner<'a>(
@@ -45,6 +81,7 @@ pub fn create_runner<'a>(
                 config,
                 &seed.settings,
                 seed.get_env(),
+                seed.get_dll_overrides(),
                 wine_seed,
             )?;
 
@@ -57,6 +94,7 @@ pub fn create_runner<'a>(
                 config,
                 &seed.settings,
                 seed.get_env(),
+                seed.get_dll_overrides(),
                 proton_seed,
             )?;
 
@@ -65,3 +103,206 @@ pub fn create_runner<'a>(
         FloraSeedType::None => Err(FloraError::RunnerNone),
     }
 }
+
+/// Resolves the prefix location of a seed
+///
+/// A relative prefix is relative to the configured prefix location. Without a prefix, the default
+/// prefix is used.
+pub(crate) fn resolve_prefix(
+    prefix: Option<&str>,
+    prefix_location: &str,
+    default_prefix: &str,
+) -> PathBuf {
+    match prefix {
+        Some(path) if Path::new(path).is_relative() => Path::new(prefix_location).join(path),
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(default_prefix),
+    }
+}
+
+/// Resolves the wrapper commands for a launch, outermost first.
+///
+/// App wrappers replace seed wrappers. At each level, the preset's wrappers come before the
+/// explicit wrappers. The seed's launcher command comes next, and gamescope is always the
+/// innermost wrapper.
+pub(crate) fn resolve_wrappers(
+    config: &FloraConfig,
+    settings: &Option<Box<FloraSeedSettings>>,
+    app: Option<&FloraSeedApp>,
+) -> Result<Vec<Vec<String>>, FloraError> {
+    let (wrapper_preset, wrappers) = match app {
+        Some(app) if app.wrapper_preset.is_some() || app.wrappers.is_some() => {
+            (&app.wrapper_preset, &app.wrappers)
+        }
+        _ => match settings {
+            Some(settings) => (&settings.wrapper_preset, &settings.wrappers),
+            None => (&None, &None),
+        },
+    };
+
+    let mut wrapper_commands = Vec::new();
+    if let Some(preset_name) = wrapper_preset {
+        let preset = config
+            .wrapper_presets
+            .get(preset_name)
+            .ok_or(FloraError::WrapperPresetNotFound(preset_name.clone()))?;
+        wrapper_commands.extend(preset.iter().cloned());
+    }
+    if let Some(wrappers) = wrappers {
+        wrapper_commands.extend(wrappers.iter().cloned());
+    }
+    if let Some(settings) = settings
+        && let Some(launcher) = &settings.launcher_command
+    {
+        wrapper_commands.push(launcher.clone());
+    }
+
+    let mut wrappers = wrapper_commands
+        .iter()
+        .map(|wrapper| {
+            shlex::split(wrapper)
+                .filter(|wrapper_param| !wrapper_param.is_empty())
+                .ok_or(FloraError::IncorrectLauncherCommand(wrapper.clone()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let gamescope_settings = app.and_then(|app| app.gamescope.as_ref()).or(settings
+        .as_ref()
+        .and_then(|settings| settings.gamescope.as_ref()));
+    if let Some(gamescope_settings) = gamescope_settings {
+        wrappers.push(gamescope::gamescope_wrapper(gamescope_settings));
+    }
+
+    Ok(wrappers)
+}
+
+/// Builds the WINEDLLOVERRIDES value from seed and app DLL overrides.
+///
+/// App overrides take precedence over seed overrides, and both are appended to the
+/// WINEDLLOVERRIDES value from the environment, if any.
+pub(crate) fn generate_dll_overrides(
+    seed_overrides: &BTreeMap<String, FloraDllOverrideMode>,
+    app: Option<&FloraSeedApp>,
+    env_overrides: Option<&String>,
+) -> Result<Option<String>, FloraError> {
+    let mut dll_overrides = BTreeMap::new();
+    let app_overrides = app.map(FloraSeedApp::get_dll_overrides);
+    for (dll_name, mode) in seed_overrides.iter().chain(app_overrides.iter().flatten()) {
+        dll_overrides.insert(seed::normalize_dll_name(dll_name)?, *mode);
+    }
+
+    if dll_overrides.is_empty() {
+        return Ok(env_overrides.cloned());
+    }
+
+    let override_value = env_overrides
+        .into_iter()
+        .filter(|env_overrides| !env_overrides.is_empty())
+        .cloned()
+        .chain(
+            dll_overrides
+                .iter()
+                .map(|(dll_name, mode)| format!("{}={}", dll_name, mode.to_override_value())),
+        )
+        .collect::<Vec<_>>()
+        .join(";");
+
+    Ok(Some(override_value))
+}
+
+/// Registry key holding Wine's prefix-wide settings
+pub(crate) fn wine_key() -> FloraRegistryKeyPath {
+    FloraRegistryKeyPath {
+        root: FloraRegistryRoot::CurrentUser,
+        path: String::from("Software\\Wine"),
+    }
+}
+
+/// Registry key holding Wine's settings for an app's executable
+pub(crate) fn app_defaults_key(app: &FloraSeedApp) -> FloraRegistryKeyPath {
+    let exe_name = app
+        .application_location
+        .rsplit(['\\', '/'])
+        .next()
+        .unwrap_or_default();
+
+    FloraRegistryKeyPath {
+        root: FloraRegistryRoot::CurrentUser,
+        path: format!("Software\\Wine\\AppDefaults\\{}", exe_name),
+    }
+}
+
+/// Writes the seed's and app's Windows versions to the prefix registry, when they changed.
+///
+/// The seed's version is set in `HKCU\Software\Wine`, like winecfg does, and the app's version
+/// under `AppDefaults` for its executable. Versions are not applied while wineserver is running,
+/// since it would overwrite the registry on exit.
+pub(crate) fn apply_windows_version(
+    prefix: &Path,
+    seed_version: Option<FloraWindowsVersion>,
+    app: Option<&FloraSeedApp>,
+) -> Result<(), FloraError> {
+    let mut versions = Vec::new();
+    if let Some(seed_version) = seed_version {
+        versions.push((wine_key(), seed_version));
+    }
+    if let Some(app) = app
+        && let Some(app_version) = app.windows_version
+    {
+        versions.push((app_defaults_key(app), app_version));
+    }
+    if versions.is_empty() {
+        return Ok(());
+    }
+
+    let registry = FloraRegistry::new(prefix);
+    if !registry.hive_exists(FloraRegistryRoot::CurrentUser) {
+        info!("Prefix is not initialized yet, Windows version will be applied on next launch");
+        return Ok(());
+    }
+
+    for (key, version) in versions {
+        let version = FloraRegistryData::String(version.to_string());
+
+        let current_version = registry.get_key(&key).ok().and_then(|key| {
+            key.get_value(Some("Version"))
+                .map(|value| value.data.clone())
+        });
+        if current_version.as_ref() == Some(&version) {
+            continue;
+        }
+
+        debug!("Setting Windows version of {} to {}", key, version);
+        match registry.set_value(&key, FloraRegistryValue::new(Some("Version"), version)) {
+            Err(FloraError::WineserverRunning(_)) => {
+                warn!("Wineserver is running, Windows version will be applied on next launch");
+                return Ok(());
+            }
+            result => result?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats a boolean option as an environment variable value
+pub(crate) fn env_flag(value: bool) -> String {
+    String::from(if value { "1" } else { "0" })
+}
+
+/// Creates a command running a program inside a chain of wrappers
+pub(crate) fn wrap_command(wrappers: &[Vec<String>], program: impl AsRef<OsStr>) -> Command {
+    match wrappers.split_first() {
+        Some((outermost, inner)) => {
+            let mut command = Command::new(&outermost[0]);
+            command.args(&outermost[1..]);
+            for wrapper in inner {
+                command.args(wrapper);
+            }
+            command.arg(program);
+
+            command
+        }
+        None => Command::new(program),
+    }
+}
@@ -3,27 +3,28 @@ use std::{
     env, fs,
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    time::Instant,
 };
 
-use flora_icon::FloraLink;
 use log::{debug, info};
-use walkdir::WalkDir;
 
 use crate::{
     config::FloraConfig,
     dirs::FloraDirs,
     errors::FloraError,
-    runners::FloraRunner,
-    seed::{FloraProtonSeed, FloraSeedApp, FloraSeedSettings},
-    start_menu::FloraSeedStartMenuItem,
+    process::{self, FloraRunResult, FloraWaitMode},
+    runners::{self, FloraRunner, hooks},
+    seed::{FloraDllOverrideMode, FloraProtonSeed, FloraSeedApp, FloraSeedSettings},
     winepath,
 };
 
 pub struct FloraProtonRunner<'a> {
     name: &'a str,
     dirs: &'a FloraDirs,
+    config: &'a FloraConfig,
     settings: &'a Option<Box<FloraSeedSettings>>,
     env: BTreeMap<String, String>,
+    dll_overrides: BTreeMap<String, FloraDllOverrideMode>,
     proton_seed: &'a FloraProtonSeed,
 
     prefix: PathBuf,
@@ -71,26 +72,14 @@ impl<'a> FloraProtonRunner<'a> {
         config: &'a FloraConfig,
         settings: &'a Option<Box<FloraSeedSettings>>,
         env: BTreeMap<String, String>,
+        dll_overrides: BTreeMap<String, FloraDllOverrideMode>,
         proton_seed: &'a FloraProtonSeed,
     ) -> Result<Self, FloraError> {
-        let proton_prefix = if let Some(path) = &proton_seed.proton_prefix {
-            // Prefix is defined in seed
-            // Use prefix defined by seed.
-            if Path::new(&path).is_relative() {
-                // Prefix is relative to wine prefix location
-                let mut new_path = PathBuf::from(config.proton.proton_prefix_location.clone());
-                new_path.push(path);
-
-                new_path
-            } else {
-                // Prefix is absolute
-                PathBuf::from(path)
-            }
-        } else {
-            // Prefix is not defined in seed, but there is a default prefix defined globally.
-            // Use default prefix from global configuration.
-            PathBuf::from(&config.proton.default_proton_prefix)
-        };
+        let proton_prefix = runners::resolve_prefix(
+            proton_seed.proton_prefix.as_deref(),
+            &config.proton.proton_prefix_location,
+            &config.proton.default_proton_prefix,
+        );
 
         let proton_runtime = if let Some(runner) = &proton_seed.proton_runtime {
             // Proton runtime is defined in seed.
@@ -128,15 +117,17 @@ impl<'a> FloraProtonRunner<'a> {
         debug!("Proton prefix: {}", proton_prefix.to_string_lossy());
 
         if !fs::exists(&proton_prefix).map_err(FloraError::FileAccessError)? {
-            info!("Prefix not found, but will be created at launch");
+            info!("Prefix not found, but will be created at launch or with flora seed init");
         }
 
         Ok(Self {
             name,
             dirs,
+            config,
             settings,
             proton_seed,
             env,
+            dll_overrides,
 
             prefix: proton_prefix,
             runtime: proton_runtime,
@@ -146,76 +137,217 @@ impl<'a> FloraProtonRunner<'a> {
 }
 
 impl<'a> FloraProtonRunner<'a> {
-    fn get_system_start_menu_dir(&self) -> PathBuf {
-        let mut proton_prefix = self.prefix.clone();
-        proton_prefix.push("drive_c/ProgramData/Microsoft/Windows/Start Menu");
-
-        proton_prefix
+    fn get_wineserver(&self) -> Option<PathBuf> {
+        // Proton ships its Wine build in either files or dist
+        ["files/bin/wineserver", "dist/bin/wineserver"]
+            .iter()
+            .map(|wineserver| {
+                let mut wineserver_path = self.runtime.clone();
+                wineserver_path.push(wineserver);
+
+                wineserver_path
+            })
+            .find(|wineserver_path| wineserver_path.exists())
     }
 
-    fn get_start_menu_dir(&self) -> PathBuf {
-        let mut proton_prefix = self.prefix.clone();
-        proton_prefix.push("drive_c/users");
-        proton_prefix.push("steamuser");
-        proton_prefix.push("AppData/Roaming/Microsoft/Windows/Start Menu");
-
-        proton_prefix
-    }
-    fn gather_command_info(&self) -> Result<(PathBuf, PathBuf), FloraError> {
-        Ok((self.runtime.clone(), self.prefix.clone()))
+    fn gather_command_info(&self) -> (PathBuf, PathBuf) {
+        (self.runtime.clone(), self.prefix.clone())
     }
-    fn generate_command(&self, args: &[&str]) -> Result<Command, FloraError> {
-        let (proton_tool, proton_prefix) = self.gather_command_info()?;
-        let mut command = if let Some(settings) = self.settings
-            && let Some(launcher) = &settings.launcher_command
-        {
-            let command_param = shlex::split(launcher)
-                .ok_or(FloraError::IncorrectLauncherCommand(launcher.clone()))?;
-            let (launch_command, launch_args) = (
-                &command_param
-                    .first()
-                    .ok_or(FloraError::IncorrectLauncherCommand(launcher.clone()))?,
-                &command_param[1..],
-            );
+    fn generate_options_env(&self) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        let Some(options) = self.settings.as_ref().and_then(|s| s.options.as_ref()) else {
+            return env;
+        };
 
-            let mut command = Command::new(launch_command);
-            command.args(launch_args);
-            command.arg(&self.umu);
+        // Proton enables esync and fsync by default, and only has switches to turn them off
+        let toggles = [
+            ("PROTON_NO_ESYNC", options.esync.map(|esync| !esync)),
+            ("PROTON_NO_FSYNC", options.fsync.map(|fsync| !fsync)),
+            ("PROTON_USE_NTSYNC", options.ntsync),
+            ("DXVK_ASYNC", options.dxvk_async),
+            ("PROTON_ENABLE_WAYLAND", options.wayland),
+            ("PROTON_LOG", options.proton_log),
+            (
+                "PROTON_FORCE_LARGE_ADDRESS_AWARE",
+                options.large_address_aware,
+            ),
+        ];
+        for (name, value) in toggles {
+            if let Some(value) = value {
+                env.insert(String::from(name), runners::env_flag(value));
+            }
+        }
+        if let Some(debug_channels) = &options.debug_channels {
+            env.insert(String::from("WINEDEBUG"), debug_channels.clone());
+        }
+        if let Some(dxvk_hud) = &options.dxvk_hud {
+            env.insert(String::from("DXVK_HUD"), dxvk_hud.clone());
+        }
+        // VKD3D_CONFIG only has flags to turn features on
+        if options.vkd3d_async == Some(true) {
+            env.insert(String::from("VKD3D_CONFIG"), String::from("async"));
+        }
 
-            command
-        } else {
-            Command::new(&self.umu)
-        };
+        env
+    }
 
-        for (env_name, env_val) in self.env.iter() {
-            command.env(env_name, env_val);
+    fn generate_env(
+        &self,
+        app: Option<&FloraSeedApp>,
+    ) -> Result<BTreeMap<String, String>, FloraError> {
+        let (proton_tool, proton_prefix) = self.gather_command_info();
+
+        // Seed environment takes precedence over seed options
+        let mut env = self.generate_options_env();
+        env.extend(self.env.clone());
+        if let Some(app) = app {
+            // App environment takes precedence over seed environment
+            env.extend(app.get_env());
         }
 
-        command
-            .env("WINEPREFIX", proton_prefix)
-            .env("PROTONPATH", proton_tool)
-            .env("PROTON_VERB", "run")
-            .args(args);
+        env.insert(
+            String::from("WINEPREFIX"),
+            String::from(proton_prefix.to_string_lossy()),
+        );
+        env.insert(
+            String::from("PROTONPATH"),
+            String::from(proton_tool.to_string_lossy()),
+        );
+        env.insert(String::from("PROTON_VERB"), String::from("run"));
 
         if let Some(game_id) = &self.proton_seed.game_id {
-            command.env("GAMEID", game_id);
+            env.insert(String::from("GAMEID"), game_id.clone());
         }
         if let Some(store) = &self.proton_seed.store {
-            command.env("STORE", store);
+            env.insert(String::from("STORE"), store.clone());
+        }
+
+        if let Some(dll_overrides) =
+            runners::generate_dll_overrides(&self.dll_overrides, app, env.get("WINEDLLOVERRIDES"))?
+        {
+            env.insert(String::from("WINEDLLOVERRIDES"), dll_overrides);
         }
 
+        Ok(env)
+    }
+
+    fn generate_command(
+        &self,
+        args: &[&str],
+        app: Option<&FloraSeedApp>,
+    ) -> Result<Command, FloraError> {
+        let wrappers = runners::resolve_wrappers(self.config, self.settings, app)?;
+        let mut command = runners::wrap_command(&wrappers, &self.umu);
+
+        if let Some(app) = app
+            && let Some(working_directory) = &app.working_directory
+        {
+            command.current_dir(winepath::resolve_path(&self.prefix, working_directory));
+        }
+
+        command.envs(self.generate_env(app)?).args(args);
+
         debug!("Using {} to launch {}", &self.umu, args.join(" "));
 
         Ok(command)
     }
+
+    fn wait_prefix(&self) -> Result<(), FloraError> {
+        if let Some(wineserver) = self.get_wineserver() {
+            debug!(
+                "Waiting for wineserver of {}",
+                self.prefix.to_string_lossy()
+            );
+
+            Command::new(&wineserver)
+                .env("WINEPREFIX", &self.prefix)
+                .arg("-w")
+                .status()
+                .map_err(FloraError::RunnerExecError)?;
+        } else {
+            debug!("No wineserver found in Proton runtime, not waiting for prefix");
+        }
+
+        Ok(())
+    }
+
+    fn spawn_command(
+        &self,
+        mut command: Command,
+        app_name: Option<&str>,
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        if quiet {
+            let log_out = self.dirs.get_log_file(self.name)?;
+            let log_err = self.dirs.get_log_file(self.name)?;
+            command.stdin(Stdio::null()).stdout(log_out).stderr(log_err);
+        }
+
+        let started = Instant::now();
+        let mut handle = command.spawn().map_err(FloraError::RunnerExecError)?;
+        let process = process::register(self.dirs, self.name, app_name, &command, &handle)?;
+        if wait != FloraWaitMode::NoWait {
+            let status = process::wait(self.dirs, process.pid, &mut handle)?;
+
+            let duration = started.elapsed();
+            debug!(
+                "Process {} exited with {} after {:?}",
+                process.pid, status, duration
+            );
+
+            if wait == FloraWaitMode::Prefix {
+                self.wait_prefix()?;
+            }
+
+            Ok(FloraRunResult::exited(process.pid, status, duration))
+        } else {
+            process::detach(self.dirs, process.pid, handle);
+
+            Ok(FloraRunResult::spawned(process.pid))
+        }
+    }
 }
 impl<'a> FloraRunner for FloraProtonRunner<'a> {
+    fn get_prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    fn get_seed_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_runner_name(&self) -> &'static str {
+        "Proton"
+    }
+
+    fn get_prefix_user(&self) -> String {
+        String::from("steamuser")
+    }
+
+    fn init_prefix(&self, quiet: bool) -> Result<FloraRunResult, FloraError> {
+        debug!("Using {} to initialize prefix", &self.umu);
+
+        // umu-launcher sets up the prefix before running wineboot
+        let mut command = Command::new(&self.umu);
+        command
+            .envs(self.generate_env(None)?)
+            .args(["wineboot", "--init"]);
+
+        let result = self.spawn_command(command, None, quiet, FloraWaitMode::Prefix)?;
+        if result.exit_code == Some(0) {
+            runners::apply_windows_version(&self.prefix, self.proton_seed.windows_version, None)?;
+        }
+
+        Ok(result)
+    }
+
     fn run_config(
         &self,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
         let mut winecfg_path = vec!["winecfg"];
 
         if let Some(additional_args) = args {
@@ -229,8 +361,8 @@ impl<'a> FloraRunner for FloraProtonRunner<'a> {
         &self,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
         let mut winetricks_path = vec!["winetricks"];
 
         if let Some(additional_args) = args {
@@ -240,134 +372,72 @@ impl<'a> FloraRunner for FloraProtonRunner<'a> {
         self.run_executable(&winetricks_path, quiet, wait)
     }
 
-    fn run_executable(&self, args: &[&str], quiet: bool, wait: bool) -> Result<(), FloraError> {
-        let mut command = self.generate_command(args)?;
-
-        if quiet {
-            let log_out = self.dirs.get_log_file(self.name)?;
-            let log_err = self.dirs.get_log_file(self.name)?;
-            command.stdin(Stdio::null()).stdout(log_out).stderr(log_err);
-        }
+    fn run_executable(
+        &self,
+        args: &[&str],
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        runners::apply_windows_version(&self.prefix, self.proton_seed.windows_version, None)?;
 
-        let mut handle = command.spawn().map_err(FloraError::RunnerExecError)?;
-        if wait {
-            handle.wait().map_err(FloraError::RunnerExecError)?;
-        }
+        let command = self.generate_command(args, None)?;
+        let env = self.generate_env(None)?;
 
-        Ok(())
+        hooks::run_with_hooks(
+            self.dirs,
+            self.name,
+            self.settings,
+            None,
+            &env,
+            wait,
+            |wait| self.spawn_command(command, None, quiet, wait),
+        )
     }
-    fn create_desktop_entry(&self, app: &FloraSeedApp) -> Result<(), FloraError> {
-        // Get link path
-        let target_linux_path = winepath::windows_to_unix(&self.prefix, &app.application_location);
-
-        let exe_find = flora_icon::find_lnk_exe_location(&target_linux_path)?;
-
-        let icon_path = self.dirs.get_icon_file(self.name, &app.application_name);
-        let mut icon_name = String::from("applications-other");
 
-        if let FloraLink::Other(location) = exe_find {
-            // Not an EXE or LNK, use other icon
-            icon_name = flora_icon::get_icon_name_from_path(&location)?;
-        } else if let FloraLink::WindowsIco(ico_path) = exe_find {
-            let windows_ico_path = winepath::windows_to_unix(&self.prefix, &ico_path);
-            debug!("We got icon from {}", &windows_ico_path.to_string_lossy());
+    fn run_app(
+        &self,
+        app: &FloraSeedApp,
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        runners::apply_windows_version(&self.prefix, self.proton_seed.windows_version, Some(app))?;
 
-            flora_icon::extract_icon_from_ico(&icon_path, &PathBuf::from(&windows_ico_path))?;
-            icon_name = String::from(icon_path.to_string_lossy())
-        } else {
-            debug!("No icon location, search exe for icons");
-            let exe_location = match exe_find {
-                FloraLink::LinuxExe(path) => path,
-                FloraLink::WindowsExe(path) => winepath::windows_to_unix(&self.prefix, &path),
-                _ => panic!("Windows ICO should be handled in the former case!"),
-            };
-
-            if flora_icon::extract_icon_from_exe(&icon_path, &exe_location)? {
-                debug!("We got icon from {}", exe_location.to_string_lossy());
-                icon_name = String::from(icon_path.to_string_lossy())
-            };
+        let mut args = vec![app.application_location.as_str()];
+        if let Some(arguments) = &app.arguments {
+            args.extend(arguments.iter().map(String::as_str));
         }
 
-        // Create desktop entry files
-        let desktop_entry = format!(
-            "[Desktop Entry]
-Type=Application
-Categories={}
-Name={}
-Icon={}
-Exec=flora run -a -w {} \"{}\"
-Comment=Run {} with Flora (Proton seed {})
-Terminal=false",
-            app.category.clone().unwrap_or(String::from("X-Flora")),
-            app.application_name,
-            icon_name,
-            self.name,
-            app.application_name,
-            app.application_name,
-            self.name
-        );
-
-        let desktop_entry_location = self
-            .dirs
-            .get_desktop_entry_file(self.name, &app.application_name);
+        let command = self.generate_command(&args, Some(app))?;
+        let env = self.generate_env(Some(app))?;
 
-        debug!(
-            "Writing {} desktop entry to {}",
+        hooks::run_with_hooks(
+            self.dirs,
             self.name,
-            desktop_entry_location.to_string_lossy()
-        );
-
-        fs::write(&desktop_entry_location, desktop_entry)
-            .map_err(|e| FloraError::DesktopEntryWriteError(desktop_entry_location, e))?;
-
-        Ok(())
+            self.settings,
+            Some(app),
+            &env,
+            wait,
+            |wait| self.spawn_command(command, Some(&app.application_name), quiet, wait),
+        )
     }
 
-    fn get_start_menu_entry_location(&self, menu_name: &str) -> Result<String, FloraError> {
-        for start_menu_dir in [self.get_start_menu_dir(), self.get_system_start_menu_dir()] {
-            for entry in WalkDir::new(start_menu_dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if let Some(file_name) = entry.path().file_name()
-                    && file_name.eq_ignore_ascii_case(format!("{}.lnk", menu_name))
-                {
-                    debug!("Found Start Menu item: {}", entry.path().display());
-                    let path = String::from(entry.path().to_string_lossy());
-
-                    let winepath = winepath::unix_to_windows(&self.prefix, &PathBuf::from(path));
-
-                    debug!("Winepath: {}", winepath);
-                    return Ok(winepath);
-                }
-            }
+    fn kill(&self) -> Result<(), FloraError> {
+        if !fs::exists(&self.prefix)? {
+            return Ok(());
         }
 
-        Err(FloraError::StartMenuLocationNotFound(menu_name.to_string()))
-    }
+        if let Some(wineserver) = self.get_wineserver() {
+            debug!("Using {} to kill prefix", wineserver.to_string_lossy());
 
-    fn list_start_menu_entries(&self) -> Result<Vec<FloraSeedStartMenuItem>, FloraError> {
-        let mut start_menu_entries = Vec::new();
-
-        for start_menu_dir in [self.get_start_menu_dir(), self.get_system_start_menu_dir()] {
-            for entry in WalkDir::new(start_menu_dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if let Some(extension) = entry.path().extension()
-                    && extension == "lnk"
-                    && let Some(file_stem) = entry.path().file_stem()
-                {
-                    debug!("Found Start Menu item: {}", entry.path().display());
-
-                    start_menu_entries.push(FloraSeedStartMenuItem {
-                        start_menu_name: String::from(file_stem.to_string_lossy()),
-                        start_menu_location: winepath::unix_to_windows(&self.prefix, entry.path()),
-                    });
-                }
-            }
+            Command::new(&wineserver)
+                .env("WINEPREFIX", &self.prefix)
+                .arg("-k")
+                .status()
+                .map_err(FloraError::RunnerExecError)?;
+        } else {
+            debug!("No wineserver found in Proton runtime, only killing tracked processes");
         }
 
-        Ok(start_menu_entries)
+        Ok(())
     }
 }
@@ -3,27 +3,29 @@ use std::{
     fs,
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    time::Instant,
 };
 
-use flora_icon::FloraLink;
 use log::{debug, info};
-use walkdir::WalkDir;
 
 use crate::{
     config::FloraConfig,
     dirs::FloraDirs,
     errors::FloraError,
-    runners::FloraRunner,
-    seed::{FloraSeedApp, FloraSeedSettings, FloraWineSeed},
-    start_menu::FloraSeedStartMenuItem,
+    process::{self, FloraRunResult, FloraWaitMode},
+    runners::{self, FloraRunner, hooks},
+    seed::{FloraDllOverrideMode, FloraSeedApp, FloraSeedSettings, FloraWineSeed},
     winepath,
 };
 
 pub struct FloraWineRunner<'a> {
     name: &'a str,
     dirs: &'a FloraDirs,
+    config: &'a FloraConfig,
     settings: &'a Option<Box<FloraSeedSettings>>,
     env: BTreeMap<String, String>,
+    dll_overrides: BTreeMap<String, FloraDllOverrideMode>,
+    wine_seed: &'a FloraWineSeed,
 
     prefix: PathBuf,
     runtime: PathBuf,
@@ -36,26 +38,14 @@ impl<'a> FloraWineRunner<'a> {
         config: &'a FloraConfig,
         settings: &'a Option<Box<FloraSeedSettings>>,
         env: BTreeMap<String, String>,
+        dll_overrides: BTreeMap<String, FloraDllOverrideMode>,
         wine_seed: &'a FloraWineSeed,
     ) -> Result<Self, FloraError> {
-        let wine_prefix = if let Some(path) = &wine_seed.wine_prefix {
-            // Prefix is defined in seed
-            // Use prefix defined in seed.
-            if Path::new(&path).is_relative() {
-                // Prefix is relative to wine prefix location
-                let mut new_path = PathBuf::from(config.wine.wine_prefix_location.clone());
-                new_path.push(path);
-
-                new_path
-            } else {
-                // Prefix is absolute
-                PathBuf::from(path)
-            }
-        } else {
-            // Prefix is not defined in seed, but there is a default prefix defined globally.
-            // Use default prefix from global configuration.
-            PathBuf::from(&config.wine.default_wine_prefix)
-        };
+        let wine_prefix = runners::resolve_prefix(
+            wine_seed.wine_prefix.as_deref(),
+            &config.wine.wine_prefix_location,
+            &config.wine.default_wine_prefix,
+        );
 
         let wine_runtime = if let Some(runner) = &wine_seed.wine_runtime {
             // Wine runtime is defined in seed.
@@ -87,14 +77,17 @@ impl<'a> FloraWineRunner<'a> {
         debug!("Wine prefix: {}", wine_prefix.to_string_lossy());
 
         if !fs::exists(&wine_prefix)? {
-            info!("Prefix not found, but will be created at launch");
+            info!("Prefix not found, but will be created at launch or with flora seed init");
         }
 
         Ok(Self {
             name,
             dirs,
+            config,
             settings,
             env,
+            dll_overrides,
+            wine_seed,
 
             prefix: wine_prefix,
             runtime: wine_runtime,
@@ -103,64 +96,120 @@ impl<'a> FloraWineRunner<'a> {
 }
 
 impl<'a> FloraWineRunner<'a> {
-    fn get_start_menu_dir(&self) -> PathBuf {
-        let mut wine_prefix = self.prefix.clone();
-        let username = whoami::username().unwrap_or(String::from("steamuser"));
-
-        wine_prefix.push("drive_c/users");
-        wine_prefix.push(username);
-        wine_prefix.push("AppData/Roaming/Microsoft/Windows/Start Menu");
-
-        wine_prefix
-    }
-
-    fn get_system_start_menu_dir(&self) -> PathBuf {
-        let mut wine_prefix = self.prefix.clone();
-        wine_prefix.push("drive_c/ProgramData/Microsoft/Windows/Start Menu");
+    fn get_wineserver(&self) -> PathBuf {
+        let mut wineserver = self.runtime.clone();
+        wineserver.push("bin/wineserver");
 
-        wine_prefix
+        wineserver
     }
 
-    fn gather_command_info(&self) -> Result<(PathBuf, PathBuf), FloraError> {
-        Ok((self.runtime.clone(), self.prefix.clone()))
+    fn gather_command_info(&self) -> (PathBuf, PathBuf) {
+        (self.runtime.clone(), self.prefix.clone())
     }
-    fn generate_command(&self, args: &[&str]) -> Result<Command, FloraError> {
-        let (wine_runtime, wine_prefix) = self.gather_command_info()?;
 
-        let mut wine_exe = wine_runtime.clone();
-        if !wine_runtime.to_string_lossy().is_empty() {
+    fn get_wine_exe(&self) -> PathBuf {
+        let mut wine_exe = self.runtime.clone();
+        if !self.runtime.as_os_str().is_empty() {
             wine_exe.push("bin/wine");
         } else {
             // Use system wine
             wine_exe.push("wine");
         }
 
-        let mut command = if let Some(settings) = self.settings
-            && let Some(launcher) = &settings.launcher_command
-        {
-            let command_param = shlex::split(launcher)
-                .ok_or(FloraError::IncorrectLauncherCommand(launcher.clone()))?;
-            let (launch_command, launch_args) = (
-                &command_param
-                    .first()
-                    .ok_or(FloraError::IncorrectLauncherCommand(launcher.clone()))?,
-                &command_param[1..],
-            );
+        wine_exe
+    }
 
-            let mut command = Command::new(launch_command);
-            command.args(launch_args);
-            command.arg(&wine_exe);
+    /// WINEARCH is only passed when the prefix is created, as Wine refuses to start otherwise
+    fn generate_arch_env(&self) -> Option<(String, String)> {
+        let prefix_arch = self.wine_seed.prefix_arch?;
+        if self.prefix.exists() {
+            return None;
+        }
 
-            command
-        } else {
-            Command::new(&wine_exe)
+        Some((String::from("WINEARCH"), prefix_arch.to_string()))
+    }
+    fn generate_options_env(&self) -> BTreeMap<String, String> {
+        let mut env = BTreeMap::new();
+        let Some(options) = self.settings.as_ref().and_then(|s| s.options.as_ref()) else {
+            return env;
         };
 
-        for (env_name, env_val) in self.env.iter() {
-            command.env(env_name, env_val);
+        let toggles = [
+            ("WINEESYNC", options.esync),
+            ("WINEFSYNC", options.fsync),
+            ("WINENTSYNC", options.ntsync),
+            ("DXVK_ASYNC", options.dxvk_async),
+            ("WINE_LARGE_ADDRESS_AWARE", options.large_address_aware),
+        ];
+        for (name, value) in toggles {
+            if let Some(value) = value {
+                env.insert(String::from(name), runners::env_flag(value));
+            }
+        }
+        if let Some(debug_channels) = &options.debug_channels {
+            env.insert(String::from("WINEDEBUG"), debug_channels.clone());
+        }
+        if let Some(dxvk_hud) = &options.dxvk_hud {
+            env.insert(String::from("DXVK_HUD"), dxvk_hud.clone());
+        }
+        // VKD3D_CONFIG only has flags to turn features on
+        if options.vkd3d_async == Some(true) {
+            env.insert(String::from("VKD3D_CONFIG"), String::from("async"));
+        }
+
+        if options.wayland.is_some() || options.proton_log.is_some() {
+            debug!("Ignoring Proton-only options for Wine seed {}", self.name);
+        }
+
+        env
+    }
+
+    fn generate_env(
+        &self,
+        app: Option<&FloraSeedApp>,
+    ) -> Result<BTreeMap<String, String>, FloraError> {
+        let (_, wine_prefix) = self.gather_command_info();
+
+        // Seed environment takes precedence over seed options
+        let mut env = self.generate_options_env();
+        env.extend(self.env.clone());
+        if let Some(app) = app {
+            // App environment takes precedence over seed environment
+            env.extend(app.get_env());
+        }
+
+        env.insert(
+            String::from("WINEPREFIX"),
+            String::from(wine_prefix.to_string_lossy()),
+        );
+        env.extend(self.generate_arch_env());
+
+        if let Some(dll_overrides) =
+            runners::generate_dll_overrides(&self.dll_overrides, app, env.get("WINEDLLOVERRIDES"))?
+        {
+            env.insert(String::from("WINEDLLOVERRIDES"), dll_overrides);
+        }
+
+        Ok(env)
+    }
+
+    fn generate_command(
+        &self,
+        args: &[&str],
+        app: Option<&FloraSeedApp>,
+    ) -> Result<Command, FloraError> {
+        let wine_exe = self.get_wine_exe();
+
+        let wrappers = runners::resolve_wrappers(self.config, self.settings, app)?;
+        let mut command = runners::wrap_command(&wrappers, &wine_exe);
+
+        if let Some(app) = app
+            && let Some(working_directory) = &app.working_directory
+        {
+            command.current_dir(winepath::resolve_path(&self.prefix, working_directory));
         }
 
-        command.env("WINEPREFIX", wine_prefix).args(args);
+        command.envs(self.generate_env(app)?).args(args);
 
         debug!(
             "Using {} to launch {}",
@@ -170,15 +219,102 @@ impl<'a> FloraWineRunner<'a> {
 
         Ok(command)
     }
+
+    fn wait_prefix(&self) -> Result<(), FloraError> {
+        let wineserver = self.get_wineserver();
+        debug!(
+            "Waiting for wineserver of {}",
+            self.prefix.to_string_lossy()
+        );
+
+        Command::new(&wineserver)
+            .env("WINEPREFIX", &self.prefix)
+            .arg("-w")
+            .status()
+            .map_err(FloraError::RunnerExecError)?;
+
+        Ok(())
+    }
+
+    fn spawn_command(
+        &self,
+        mut command: Command,
+        app_name: Option<&str>,
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        if quiet {
+            let log_out = self.dirs.get_log_file(self.name)?;
+            let log_err = self.dirs.get_log_file(self.name)?;
+            command.stdin(Stdio::null()).stdout(log_out).stderr(log_err);
+        }
+
+        let started = Instant::now();
+        let mut handle = command.spawn().map_err(FloraError::RunnerExecError)?;
+        let process = process::register(self.dirs, self.name, app_name, &command, &handle)?;
+        if wait != FloraWaitMode::NoWait {
+            let status = process::wait(self.dirs, process.pid, &mut handle)?;
+
+            let duration = started.elapsed();
+            debug!(
+                "Process {} exited with {} after {:?}",
+                process.pid, status, duration
+            );
+
+            if wait == FloraWaitMode::Prefix {
+                self.wait_prefix()?;
+            }
+
+            Ok(FloraRunResult::exited(process.pid, status, duration))
+        } else {
+            process::detach(self.dirs, process.pid, handle);
+
+            Ok(FloraRunResult::spawned(process.pid))
+        }
+    }
 }
 
 impl<'a> FloraRunner for FloraWineRunner<'a> {
+    fn get_prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    fn get_seed_name(&self) -> &str {
+        self.name
+    }
+
+    fn get_runner_name(&self) -> &'static str {
+        "Wine"
+    }
+
+    fn get_prefix_user(&self) -> String {
+        whoami::username().unwrap_or(String::from("steamuser"))
+    }
+
+    fn init_prefix(&self, quiet: bool) -> Result<FloraRunResult, FloraError> {
+        let wine_exe = self.get_wine_exe();
+        debug!("Using {} to initialize prefix", wine_exe.to_string_lossy());
+
+        // Wrappers are skipped, wineboot has no window to show
+        let mut command = Command::new(&wine_exe);
+        command
+            .envs(self.generate_env(None)?)
+            .args(["wineboot", "--init"]);
+
+        let result = self.spawn_command(command, None, quiet, FloraWaitMode::Prefix)?;
+        if result.exit_code == Some(0) {
+            runners::apply_windows_version(&self.prefix, self.wine_seed.windows_version, None)?;
+        }
+
+        Ok(result)
+    }
+
     fn run_config(
         &self,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
         let mut winecfg_path = vec!["winecfg"];
 
         if let Some(additional_args) = args {
@@ -192,178 +328,97 @@ impl<'a> FloraRunner for FloraWineRunner<'a> {
         &self,
         args: &Option<Vec<&str>>,
         quiet: bool,
-        wait: bool,
-    ) -> Result<(), FloraError> {
-        let (wine_dir, wine_prefix) = self.gather_command_info()?;
-
-        let mut wine_exe = wine_dir.clone();
-        if !wine_dir.as_os_str().is_empty() {
-            wine_exe.push("bin/wine");
-        } else {
-            // Use system wine
-            wine_exe.push("wine");
-        }
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        let wine_exe = self.get_wine_exe();
 
         debug!("Using {} for winetricks", wine_exe.to_string_lossy());
 
-        use std::process::Command;
-        let mut command = Command::new("winetricks");
-
-        for (env_name, env_val) in self.env.iter() {
-            command.env(env_name, env_val);
-        }
-
-        command
-            .env("WINEPREFIX", wine_prefix)
-            .env("WINE", wine_exe)
-            .arg("-q");
+        runners::apply_windows_version(&self.prefix, self.wine_seed.windows_version, None)?;
+        // Winetricks runs Wine with the seed's options and DLL overrides, like any other launch
+        let env = self.generate_env(None)?;
 
-        if quiet {
-            let log_out = self.dirs.get_log_file(self.name)?;
-            let log_err = self.dirs.get_log_file(self.name)?;
-            command.stdin(Stdio::null()).stdout(log_out).stderr(log_err);
-        }
+        let mut command = Command::new("winetricks");
+        command.envs(&env).env("WINE", wine_exe).arg("-q");
 
         if let Some(args) = args {
             command.args(args);
         }
-        let mut handle = command.spawn().map_err(FloraError::RunnerExecError)?;
-        if wait {
-            handle.wait().map_err(FloraError::RunnerExecError)?;
-        }
 
-        Ok(())
+        hooks::run_with_hooks(
+            self.dirs,
+            self.name,
+            self.settings,
+            None,
+            &env,
+            wait,
+            |wait| self.spawn_command(command, None, quiet, wait),
+        )
     }
 
-    fn run_executable(&self, args: &[&str], quiet: bool, wait: bool) -> Result<(), FloraError> {
-        let mut command = self.generate_command(args)?;
-
-        if quiet {
-            let log_out = self.dirs.get_log_file(self.name)?;
-            let log_err = self.dirs.get_log_file(self.name)?;
-            command.stdin(Stdio::null()).stdout(log_out).stderr(log_err);
-        }
+    fn run_executable(
+        &self,
+        args: &[&str],
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        runners::apply_windows_version(&self.prefix, self.wine_seed.windows_version, None)?;
 
-        let mut handle = command.spawn().map_err(FloraError::RunnerExecError)?;
-        if wait {
-            handle.wait().map_err(FloraError::RunnerExecError)?;
-        }
+        let command = self.generate_command(args, None)?;
+        let env = self.generate_env(None)?;
 
-        Ok(())
+        hooks::run_with_hooks(
+            self.dirs,
+            self.name,
+            self.settings,
+            None,
+            &env,
+            wait,
+            |wait| self.spawn_command(command, None, quiet, wait),
+        )
     }
 
-    fn create_desktop_entry(&self, app: &FloraSeedApp) -> Result<(), FloraError> {
-        // Get link path
-        let target_linux_path = winepath::windows_to_unix(&self.prefix, &app.application_location);
-
-        let exe_find = flora_icon::find_lnk_exe_location(&target_linux_path)?;
-
-        let icon_path = self.dirs.get_icon_file(self.name, &app.application_name);
-        let mut icon_name = String::from("applications-other");
-
-        if let FloraLink::Other(location) = exe_find {
-            // Not an EXE or LNK, use other icon
-            icon_name = flora_icon::get_icon_name_from_path(&location)?;
-        } else if let FloraLink::WindowsIco(ico_path) = exe_find {
-            let windows_ico_path = winepath::windows_to_unix(&self.prefix, &ico_path);
-            debug!("We got icon from {}", &windows_ico_path.to_string_lossy());
+    fn run_app(
+        &self,
+        app: &FloraSeedApp,
+        quiet: bool,
+        wait: FloraWaitMode,
+    ) -> Result<FloraRunResult, FloraError> {
+        runners::apply_windows_version(&self.prefix, self.wine_seed.windows_version, Some(app))?;
 
-            flora_icon::extract_icon_from_ico(&icon_path, &PathBuf::from(&windows_ico_path))?;
-            icon_name = String::from(icon_path.to_string_lossy())
-        } else {
-            debug!("No icon location, search exe for icons");
-            let exe_location = match exe_find {
-                FloraLink::LinuxExe(path) => path,
-                FloraLink::WindowsExe(path) => winepath::windows_to_unix(&self.prefix, &path),
-                _ => panic!("Windows ICO should be handled in the former case!"),
-            };
-
-            if flora_icon::extract_icon_from_exe(&icon_path, &exe_location)? {
-                debug!("We got icon from {}", exe_location.to_string_lossy());
-                icon_name = String::from(icon_path.to_string_lossy());
-            };
+        let mut args = vec![app.application_location.as_str()];
+        if let Some(arguments) = &app.arguments {
+            args.extend(arguments.iter().map(String::as_str));
         }
 
-        // Create desktop entry files
-        let desktop_entry = format!(
-            "[Desktop Entry]
-Type=Application
-Categories={}
-Name={}
-Icon={}
-Exec=flora run -a -w {} \"{}\"
-Comment=Run {} with Flora (Wine seed {})
-Terminal=false",
-            app.category.clone().unwrap_or(String::from("X-Flora")),
-            app.application_name,
-            icon_name,
-            self.name,
-            app.application_name,
-            app.application_name,
-            self.name
-        );
-
-        let desktop_entry_location = self
-            .dirs
-            .get_desktop_entry_file(self.name, &app.application_name);
+        let command = self.generate_command(&args, Some(app))?;
+        let env = self.generate_env(Some(app))?;
 
-        debug!(
-            "Writing {} desktop entry to {}",
+        hooks::run_with_hooks(
+            self.dirs,
             self.name,
-            desktop_entry_location.to_string_lossy()
-        );
-
-        fs::write(&desktop_entry_location, &desktop_entry)
-            .map_err(|e| FloraError::DesktopEntryWriteError(desktop_entry_location, e))?;
-
-        Ok(())
+            self.settings,
+            Some(app),
+            &env,
+            wait,
+            |wait| self.spawn_command(command, Some(&app.application_name), quiet, wait),
+        )
     }
 
-    fn get_start_menu_entry_location(&self, menu_name: &str) -> Result<String, FloraError> {
-        for start_menu_dir in [self.get_start_menu_dir(), self.get_system_start_menu_dir()] {
-            for entry in WalkDir::new(start_menu_dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if let Some(file_name) = entry.path().file_name()
-                    && file_name.eq_ignore_ascii_case(format!("{}.lnk", menu_name))
-                {
-                    debug!("Found Start Menu item: {}", entry.path().display());
-                    let path = String::from(entry.path().to_string_lossy());
-
-                    let winepath = winepath::unix_to_windows(&self.prefix, &PathBuf::from(path));
-
-                    debug!("Winepath: {}", winepath);
-                    return Ok(winepath);
-                }
-            }
+    fn kill(&self) -> Result<(), FloraError> {
+        if !fs::exists(&self.prefix)? {
+            return Ok(());
         }
 
-        Err(FloraError::StartMenuLocationNotFound(menu_name.to_string()))
-    }
-
-    fn list_start_menu_entries(&self) -> Result<Vec<FloraSeedStartMenuItem>, FloraError> {
-        let mut start_menu_entries = Vec::new();
+        let wineserver = self.get_wineserver();
+        debug!("Using {} to kill prefix", wineserver.to_string_lossy());
 
-        for start_menu_dir in [self.get_start_menu_dir(), self.get_system_start_menu_dir()] {
-            for entry in WalkDir::new(start_menu_dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if let Some(extension) = entry.path().extension()
-                    && extension == "lnk"
-                    && let Some(file_stem) = entry.path().file_stem()
-                {
-                    debug!("Found Start Menu item: {}", entry.path().display());
-
-                    start_menu_entries.push(FloraSeedStartMenuItem {
-                        start_menu_name: String::from(file_stem.to_string_lossy()),
-                        start_menu_location: winepath::unix_to_windows(&self.prefix, entry.path()),
-                    });
-                }
-            }
-        }
+        Command::new(&wineserver)
+            .env("WINEPREFIX", &self.prefix)
+            .arg("-k")
+            .status()
+            .map_err(FloraError::RunnerExecError)?;
 
-        Ok(start_menu_entries)
+        Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +19,42 @@ pub enum FloraSeedType {
 pub struct FloraWineSeed {
     pub wine_prefix: Option<String>,
     pub wine_runtime: Option<String>,
+    /// Windows version reported to apps, applied to the prefix on launch
+    pub windows_version: Option<FloraWindowsVersion>,
+    /// Architecture of the prefix, only used when the prefix is created
+    pub prefix_arch: Option<FloraPrefixArch>,
+}
+
+/// Wine prefix architectures, passed as WINEARCH
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FloraPrefixArch {
+    Win32,
+    Win64,
+    Wow64,
+}
+
+impl Display for FloraPrefixArch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Win32 => write!(f, "win32"),
+            Self::Win64 => write!(f, "win64"),
+            Self::Wow64 => write!(f, "wow64"),
+        }
+    }
+}
+
+impl FromStr for FloraPrefixArch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "win32" => Ok(Self::Win32),
+            "win64" => Ok(Self::Win64),
+            "wow64" => Ok(Self::Wow64),
+            _ => Err(format!("Unknown prefix architecture: {}", s)),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -27,6 +63,52 @@ pub struct FloraProtonSeed {
     pub proton_runtime: Option<String>,
     pub game_id: Option<String>,
     pub store: Option<String>,
+    /// Windows version reported to apps, applied to the prefix on launch
+    pub windows_version: Option<FloraWindowsVersion>,
+}
+
+/// Windows versions Wine can report, named like winecfg does
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FloraWindowsVersion {
+    WinXp,
+    Vista,
+    Win7,
+    Win8,
+    Win81,
+    Win10,
+    Win11,
+}
+
+impl Display for FloraWindowsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WinXp => write!(f, "winxp"),
+            Self::Vista => write!(f, "vista"),
+            Self::Win7 => write!(f, "win7"),
+            Self::Win8 => write!(f, "win8"),
+            Self::Win81 => write!(f, "win81"),
+            Self::Win10 => write!(f, "win10"),
+            Self::Win11 => write!(f, "win11"),
+        }
+    }
+}
+
+impl FromStr for FloraWindowsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "winxp" => Ok(Self::WinXp),
+            "vista" => Ok(Self::Vista),
+            "win7" => Ok(Self::Win7),
+            "win8" => Ok(Self::Win8),
+            "win81" => Ok(Self::Win81),
+            "win10" => Ok(Self::Win10),
+            "win11" => Ok(Self::Win11),
+            _ => Err(format!("Unknown Windows version: {}", s)),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -34,6 +116,7 @@ pub struct FloraSeed {
     pub settings: Option<Box<FloraSeedSettings>>,
     apps: Vec<FloraSeedApp>,
     env: Option<BTreeMap<String, String>>,
+    dll_overrides: Option<BTreeMap<String, FloraDllOverrideMode>>,
 
     #[serde(flatten)]
     pub seed_type: FloraSeedType,
@@ -46,6 +129,7 @@ impl Default for FloraSeed {
             settings: None,
             apps: Vec::new(),
             env: None,
+            dll_overrides: None,
 
             seed_type: FloraSeedType::None,
         }
@@ -164,14 +248,409 @@ impl FloraSeed {
         seed_env.remove(env_name);
     }
 }
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// DLL override functions
+impl FloraSeed {
+    pub fn get_dll_overrides(&self) -> BTreeMap<String, FloraDllOverrideMode> {
+        self.dll_overrides.clone().unwrap_or_default()
+    }
+    pub fn update_dll_override(
+        &mut self,
+        dll_name: &str,
+        mode: FloraDllOverrideMode,
+    ) -> Result<(), FloraError> {
+        let dll_name = normalize_dll_name(dll_name)?;
+        let seed_dll_overrides = self.dll_overrides.get_or_insert(BTreeMap::new());
+        seed_dll_overrides.insert(dll_name, mode);
+
+        Ok(())
+    }
+    pub fn delete_dll_override(&mut self, dll_name: &str) -> Result<(), FloraError> {
+        let dll_name = normalize_dll_name(dll_name)?;
+        let seed_dll_overrides = self.dll_overrides.get_or_insert(BTreeMap::new());
+        seed_dll_overrides
+            .remove(&dll_name)
+            .ok_or(FloraError::DllOverrideNotFound(dll_name))?;
+
+        Ok(())
+    }
+}
+
+/// How Wine loads a DLL
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloraDllOverrideMode {
+    #[serde(rename = "native")]
+    Native,
+    #[serde(rename = "builtin")]
+    Builtin,
+    /// Native, falling back to builtin
+    #[serde(rename = "native,builtin")]
+    NativeBuiltin,
+    /// Builtin, falling back to native
+    #[serde(rename = "builtin,native")]
+    BuiltinNative,
+    #[serde(rename = "disabled")]
+    Disabled,
+}
+
+impl FloraDllOverrideMode {
+    /// Value of the mode in WINEDLLOVERRIDES
+    pub(crate) fn to_override_value(self) -> &'static str {
+        match self {
+            Self::Native => "n",
+            Self::Builtin => "b",
+            Self::NativeBuiltin => "n,b",
+            Self::BuiltinNative => "b,n",
+            Self::Disabled => "",
+        }
+    }
+}
+
+impl Display for FloraDllOverrideMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Native => write!(f, "native"),
+            Self::Builtin => write!(f, "builtin"),
+            Self::NativeBuiltin => write!(f, "native,builtin"),
+            Self::BuiltinNative => write!(f, "builtin,native"),
+            Self::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+impl FromStr for FloraDllOverrideMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "native" | "n" => Ok(Self::Native),
+            "builtin" | "b" => Ok(Self::Builtin),
+            "native,builtin" | "n,b" => Ok(Self::NativeBuiltin),
+            "builtin,native" | "b,n" => Ok(Self::BuiltinNative),
+            "disabled" | "" => Ok(Self::Disabled),
+            _ => Err(format!("Unknown DLL override mode: {}", s)),
+        }
+    }
+}
+
+/// Validates a DLL name, lowercasing it and removing the .dll extension like Wine does
+pub(crate) fn normalize_dll_name(dll_name: &str) -> Result<String, FloraError> {
+    let normalized = dll_name.trim().to_lowercase();
+    let normalized = normalized.strip_suffix(".dll").unwrap_or(&normalized);
+
+    if normalized.is_empty()
+        || normalized
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '=' | ';' | ',' | '/' | '\\'))
+    {
+        return Err(FloraError::InvalidDllName(dll_name.to_string()));
+    }
+
+    Ok(String::from(normalized))
+}
+
+/// Validates a seed name, which is used as a file name for the seed and its prefix
+pub(crate) fn validate_seed_name(name: &str) -> Result<(), FloraError> {
+    if matches!(name, "" | "." | "..") || name.chars().any(|c| c == '/' || c.is_control()) {
+        return Err(FloraError::InvalidSeedName(name.to_string()));
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct FloraSeedSettings {
+    /// Single launcher command, run inside any wrappers
     pub launcher_command: Option<String>,
+    /// Name of a wrapper preset from flora.toml, run outside the seed's wrappers
+    pub wrapper_preset: Option<String>,
+    /// Wrapper commands put in front of the runner, outermost first
+    pub wrappers: Option<Vec<String>>,
+    /// Gamescope session the runner is started in, inside all other wrappers
+    pub gamescope: Option<FloraGamescopeSettings>,
+    /// Common Wine and Proton options, overridden by the seed's environment
+    pub options: Option<FloraWineOptions>,
+    /// Hooks run before launching apps and executables
+    pub pre_launch: Option<Vec<FloraHook>>,
+    /// Hooks run after launched apps and executables exit
+    pub post_exit: Option<Vec<FloraHook>>,
+}
+
+/// Wine and Proton options, translated into environment variables by each runner
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct FloraWineOptions {
+    pub esync: Option<bool>,
+    pub fsync: Option<bool>,
+    pub ntsync: Option<bool>,
+    /// Debug channels passed as `WINEDEBUG`, e.g. `-all` or `+seh,+loaddll`
+    pub debug_channels: Option<String>,
+    /// Elements shown by the DXVK HUD, passed as `DXVK_HUD`
+    pub dxvk_hud: Option<String>,
+    /// Asynchronous shader compilation, for DXVK builds supporting it
+    pub dxvk_async: Option<bool>,
+    /// Asynchronous pipeline compilation, for VKD3D-Proton builds supporting it, passed in
+    /// `VKD3D_CONFIG`
+    pub vkd3d_async: Option<bool>,
+    /// Use the Wayland driver instead of X11, Proton only
+    pub wayland: Option<bool>,
+    /// Write a Proton log to the home directory, Proton only
+    pub proton_log: Option<bool>,
+    /// Allow 32-bit executables to use more than 2 GB of memory
+    pub large_address_aware: Option<bool>,
+}
+
+/// Gamescope options, translated into gamescope command line flags
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FloraGamescopeSettings {
+    /// Output window width (`-W`)
+    pub output_width: Option<u32>,
+    /// Output window height (`-H`)
+    pub output_height: Option<u32>,
+    /// Resolution width the game renders at (`-w`)
+    pub nested_width: Option<u32>,
+    /// Resolution height the game renders at (`-h`)
+    pub nested_height: Option<u32>,
+    /// Frame rate limit of the output (`-r`)
+    pub refresh_rate: Option<u32>,
+    pub window_mode: Option<FloraGamescopeWindowMode>,
+    /// Upscaler filter (`-F`)
+    pub filter: Option<FloraGamescopeFilter>,
+    /// Upscaler type (`-S`)
+    pub scaler: Option<FloraGamescopeScaler>,
+    /// Enable HDR output (`--hdr-enabled`)
+    #[serde(default)]
+    pub hdr: bool,
+    /// Additional gamescope arguments, put after the generated ones
+    pub extra_args: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FloraGamescopeWindowMode {
+    Windowed,
+    /// Fullscreen window (`-f`)
+    Fullscreen,
+    /// Borderless window (`-b`)
+    Borderless,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FloraGamescopeFilter {
+    Linear,
+    Nearest,
+    Fsr,
+    Nis,
+    Pixel,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FloraGamescopeScaler {
+    Auto,
+    Integer,
+    Fit,
+    Fill,
+    Stretch,
+}
+
+impl Display for FloraGamescopeWindowMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Windowed => write!(f, "windowed"),
+            Self::Fullscreen => write!(f, "fullscreen"),
+            Self::Borderless => write!(f, "borderless"),
+        }
+    }
+}
+
+impl Display for FloraGamescopeFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "linear"),
+            Self::Nearest => write!(f, "nearest"),
+            Self::Fsr => write!(f, "fsr"),
+            Self::Nis => write!(f, "nis"),
+            Self::Pixel => write!(f, "pixel"),
+        }
+    }
+}
+
+impl Display for FloraGamescopeScaler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Integer => write!(f, "integer"),
+            Self::Fit => write!(f, "fit"),
+            Self::Fill => write!(f, "fill"),
+            Self::Stretch => write!(f, "stretch"),
+        }
+    }
+}
+
+impl FromStr for FloraGamescopeWindowMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "windowed" => Ok(Self::Windowed),
+            "fullscreen" => Ok(Self::Fullscreen),
+            "borderless" => Ok(Self::Borderless),
+            _ => Err(format!("Unknown window mode: {}", s)),
+        }
+    }
+}
+
+impl FromStr for FloraGamescopeFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "nearest" => Ok(Self::Nearest),
+            "fsr" => Ok(Self::Fsr),
+            "nis" => Ok(Self::Nis),
+            "pixel" => Ok(Self::Pixel),
+            _ => Err(format!("Unknown filter: {}", s)),
+        }
+    }
+}
+
+impl FromStr for FloraGamescopeScaler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "integer" => Ok(Self::Integer),
+            "fit" => Ok(Self::Fit),
+            "fill" => Ok(Self::Fill),
+            "stretch" => Ok(Self::Stretch),
+            _ => Err(format!("Unknown scaler: {}", s)),
+        }
+    }
 }
 
+/// What to do when a hook fails
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FloraHookFailure {
+    /// Stop launching and return an error
+    #[default]
+    Abort,
+    /// Log a warning and continue
+    Warn,
+    /// Continue silently
+    Ignore,
+}
+
+/// A shell command run around a launch, with the seed's environment
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FloraHook {
+    /// Command line, run with `sh -c`
+    pub command: String,
+    #[serde(default)]
+    pub on_failure: FloraHookFailure,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct FloraSeedApp {
     pub application_name: String,
     pub application_location: String,
     pub category: Option<String>,
+    /// Arguments passed to the app after its location
+    pub arguments: Option<Vec<String>>,
+    /// Directory the app is started in, either a Windows or a Unix path
+    pub working_directory: Option<String>,
+    /// Environment variables merged over the seed's environment
+    pub env: Option<BTreeMap<String, String>>,
+    /// DLL overrides merged over the seed's DLL overrides
+    pub dll_overrides: Option<BTreeMap<String, FloraDllOverrideMode>>,
+    /// Windows version for this app only, applied to the prefix's AppDefaults on launch
+    pub windows_version: Option<FloraWindowsVersion>,
+    /// Start Menu shortcut the app was created from
+    pub shortcut: Option<FloraSeedAppShortcut>,
+    /// Wrapper preset replacing the seed's wrappers
+    pub wrapper_preset: Option<String>,
+    /// Wrapper commands replacing the seed's wrappers, outermost first
+    pub wrappers: Option<Vec<String>>,
+    /// Gamescope options replacing the seed's gamescope options
+    pub gamescope: Option<FloraGamescopeSettings>,
+    /// Hooks run after the seed's pre-launch hooks
+    pub pre_launch: Option<Vec<FloraHook>>,
+    /// Hooks run before the seed's post-exit hooks
+    pub post_exit: Option<Vec<FloraHook>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FloraShowCommand {
+    Normal,
+    Maximized,
+    Minimized,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FloraSeedAppShortcut {
+    /// Windows path of the .lnk file
+    pub location: String,
+    /// Windows path of the file containing the shortcut's icon
+    pub icon_location: Option<String>,
+    #[serde(default)]
+    pub icon_index: i32,
+    pub show_command: Option<FloraShowCommand>,
+}
+
+// App env functions
+impl FloraSeedApp {
+    pub fn get_env(&self) -> BTreeMap<String, String> {
+        self.env.clone().unwrap_or_default()
+    }
+    pub fn update_env(&mut self, env_name: &str, env_value: &str) {
+        let app_env = self.env.get_or_insert(BTreeMap::new());
+        app_env.insert(String::from(env_name), String::from(env_value));
+    }
+
+    pub fn delete_env(&mut self, env_name: &str) {
+        let app_env = self.env.get_or_insert(BTreeMap::new());
+        app_env.remove(env_name);
+    }
+
+    pub fn get_dll_overrides(&self) -> BTreeMap<String, FloraDllOverrideMode> {
+        self.dll_overrides.clone().unwrap_or_default()
+    }
+    pub fn update_dll_override(
+        &mut self,
+        dll_name: &str,
+        mode: FloraDllOverrideMode,
+    ) -> Result<(), FloraError> {
+        let dll_name = normalize_dll_name(dll_name)?;
+        let app_dll_overrides = self.dll_overrides.get_or_insert(BTreeMap::new());
+        app_dll_overrides.insert(dll_name, mode);
+
+        Ok(())
+    }
+    pub fn delete_dll_override(&mut self, dll_name: &str) -> Result<(), FloraError> {
+        let dll_name = normalize_dll_name(dll_name)?;
+        let app_dll_overrides = self.dll_overrides.get_or_insert(BTreeMap::new());
+        app_dll_overrides
+            .remove(&dll_name)
+            .ok_or(FloraError::DllOverrideNotFound(dll_name))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::seed::validate_seed_name;
+
+    #[test]
+    fn seed_names() {
+        assert!(validate_seed_name("Touhou 17.5").is_ok());
+        assert!(validate_seed_name("").is_err());
+        assert!(validate_seed_name("..").is_err());
+        assert!(validate_seed_name("../../.bashrc").is_err());
+        assert!(validate_seed_name("line\nbreak").is_err());
+    }
 }
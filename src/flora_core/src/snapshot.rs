@@ -0,0 +1,616 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io,
+    os::unix::fs::{PermissionsExt, symlink},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::{dirs::FloraDirs, errors::FloraError, prefix};
+
+/// A snapshot of a seed's prefix
+///
+/// File contents are stored compressed in a store shared by all snapshots, so files that did not
+/// change between snapshots are only stored once.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FloraSnapshot {
+    pub id: u32,
+    pub label: Option<String>,
+    /// Creation time, in seconds since the UNIX epoch
+    pub created_at: u64,
+    /// Location of the prefix the snapshot was taken from
+    pub prefix: PathBuf,
+    /// Total size of the files in the snapshot, before compression
+    pub size: u64,
+    entries: Vec<FloraSnapshotEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum FloraSnapshotEntryKind {
+    Dir,
+    File,
+    Symlink,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct FloraSnapshotEntry {
+    kind: FloraSnapshotEntryKind,
+    /// Path relative to the prefix
+    path: PathBuf,
+    #[serde(default)]
+    mode: u32,
+    /// SHA-256 of the file contents, naming the object in the store
+    hash: Option<String>,
+    /// Target of a symlink
+    target: Option<PathBuf>,
+}
+
+/// Directory holding the snapshots of every seed, in a directory per seed
+pub(crate) fn seeds_root(dirs: &FloraDirs) -> PathBuf {
+    let mut seeds_root = dirs.get_snapshot_root();
+    seeds_root.push("seeds");
+
+    seeds_root
+}
+
+pub(crate) fn seed_snapshot_dir(dirs: &FloraDirs, seed_name: &str) -> PathBuf {
+    let mut snapshot_dir = seeds_root(dirs);
+    snapshot_dir.push(seed_name);
+
+    snapshot_dir
+}
+
+fn manifest_path(dirs: &FloraDirs, seed_name: &str, id: u32) -> PathBuf {
+    let mut manifest_path = seed_snapshot_dir(dirs, seed_name);
+    manifest_path.push(format!("{}.toml", id));
+
+    manifest_path
+}
+
+fn next_id_path(dirs: &FloraDirs, seed_name: &str) -> PathBuf {
+    let mut next_id_path = seed_snapshot_dir(dirs, seed_name);
+    next_id_path.push("next_id");
+
+    next_id_path
+}
+
+fn object_path(dirs: &FloraDirs, hash: &str) -> PathBuf {
+    let mut object_path = dirs.get_snapshot_root();
+    object_path.push("objects");
+    object_path.push(&hash[..2]);
+    object_path.push(format!("{}.zst", hash));
+
+    object_path
+}
+
+fn hash_file(path: &Path) -> Result<(String, u64), FloraError> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut file, &mut hasher)?;
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// Locks the snapshot store until the returned file is dropped
+///
+/// Objects of a snapshot being taken are not referenced by a manifest yet, so pruning must not
+/// run at the same time. The lock also keeps two snapshots from getting the same id.
+fn lock_store(dirs: &FloraDirs) -> Result<File, FloraError> {
+    let snapshot_root = dirs.get_snapshot_root();
+    fs::create_dir_all(&snapshot_root)?;
+
+    let lock = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(snapshot_root.join("lock"))?;
+    lock.lock()?;
+
+    Ok(lock)
+}
+
+/// Allocates the id of a new snapshot of a seed
+///
+/// Ids are never reused, even after the newest snapshot is deleted.
+fn allocate_id(dirs: &FloraDirs, seed_name: &str) -> Result<u32, FloraError> {
+    let next_id_path = next_id_path(dirs, seed_name);
+    let next_id = match fs::read_to_string(&next_id_path) {
+        Ok(next_id) => next_id.trim().parse().unwrap_or_default(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e.into()),
+    };
+    // Snapshots may have been taken before ids were tracked
+    let after_newest = list(dirs, seed_name)?
+        .iter()
+        .map(|snapshot| snapshot.id)
+        .max()
+        .unwrap_or_default()
+        + 1;
+    let id = next_id.max(after_newest);
+
+    fs::create_dir_all(seed_snapshot_dir(dirs, seed_name))?;
+    fs::write(&next_id_path, (id + 1).to_string())?;
+
+    Ok(id)
+}
+
+/// Compresses a file into the store, unless the same contents are already stored
+fn store_object(dirs: &FloraDirs, path: &Path, hash: &str) -> Result<(), FloraError> {
+    let object_path = object_path(dirs, hash);
+    if fs::exists(&object_path)? {
+        return Ok(());
+    }
+    fs::create_dir_all(object_path.parent().unwrap())?;
+
+    // Write to a temporary file first, so an interrupted snapshot leaves no corrupted object
+    let temp_path = object_path.with_extension(format!("zst.tmp-{}", std::process::id()));
+    zstd::stream::copy_encode(File::open(path)?, File::create(&temp_path)?, 0)?;
+    fs::rename(&temp_path, &object_path)?;
+
+    Ok(())
+}
+
+/// Takes a snapshot of a prefix
+pub(crate) fn create(
+    dirs: &FloraDirs,
+    seed_name: &str,
+    prefix: &Path,
+    label: Option<&str>,
+) -> Result<FloraSnapshot, FloraError> {
+    let _lock = lock_store(dirs)?;
+    let id = allocate_id(dirs, seed_name)?;
+
+    let mut entries = Vec::new();
+    let mut size = 0;
+
+    for entry in WalkDir::new(prefix).follow_links(false).min_depth(1) {
+        let entry = entry.map_err(|e| FloraError::FileAccessError(e.into()))?;
+        let path = entry.path().strip_prefix(prefix).unwrap().to_path_buf();
+        let file_type = entry.file_type();
+        let mode = entry
+            .metadata()
+            .map_err(io::Error::from)?
+            .permissions()
+            .mode();
+
+        if file_type.is_symlink() {
+            entries.push(FloraSnapshotEntry {
+                kind: FloraSnapshotEntryKind::Symlink,
+                path,
+                mode,
+                hash: None,
+                target: Some(fs::read_link(entry.path())?),
+            });
+        } else if file_type.is_dir() {
+            entries.push(FloraSnapshotEntry {
+                kind: FloraSnapshotEntryKind::Dir,
+                path,
+                mode,
+                hash: None,
+                target: None,
+            });
+        } else if file_type.is_file() {
+            let (hash, file_size) = hash_file(entry.path())?;
+            store_object(dirs, entry.path(), &hash)?;
+            size += file_size;
+
+            entries.push(FloraSnapshotEntry {
+                kind: FloraSnapshotEntryKind::File,
+                path,
+                mode,
+                hash: Some(hash),
+                target: None,
+            });
+        } else {
+            debug!("Skipping special file {}", entry.path().display());
+        }
+    }
+
+    let snapshot = FloraSnapshot {
+        id,
+        label: label.map(String::from),
+        created_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        prefix: prefix.to_path_buf(),
+        size,
+        entries,
+    };
+
+    let manifest_path = manifest_path(dirs, seed_name, id);
+    debug!("Writing snapshot manifest {}", manifest_path.display());
+    fs::create_dir_all(manifest_path.parent().unwrap())?;
+    fs::write(&manifest_path, toml::to_string(&snapshot)?)?;
+
+    Ok(snapshot)
+}
+
+/// Lists the snapshots of a seed, oldest first
+pub(crate) fn list(dirs: &FloraDirs, seed_name: &str) -> Result<Vec<FloraSnapshot>, FloraError> {
+    let snapshot_dir = seed_snapshot_dir(dirs, seed_name);
+    if !fs::exists(&snapshot_dir)? {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&snapshot_dir)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            snapshots.push(toml::from_str::<FloraSnapshot>(&fs::read_to_string(
+                &path,
+            )?)?);
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.id);
+
+    Ok(snapshots)
+}
+
+/// Reads a snapshot of a seed
+pub(crate) fn get(dirs: &FloraDirs, seed_name: &str, id: u32) -> Result<FloraSnapshot, FloraError> {
+    let manifest_path = manifest_path(dirs, seed_name, id);
+    if !fs::exists(&manifest_path)? {
+        return Err(FloraError::SnapshotNotFound(id));
+    }
+
+    Ok(toml::from_str(&fs::read_to_string(&manifest_path)?)?)
+}
+
+/// Replaces a prefix with the contents of a snapshot
+///
+/// The snapshot is extracted next to the prefix first, so a failed restore leaves the prefix
+/// untouched. Leftovers of an interrupted restore are cleaned up first.
+pub(crate) fn restore(
+    dirs: &FloraDirs,
+    snapshot: &FloraSnapshot,
+    prefix: &Path,
+) -> Result<(), FloraError> {
+    let _lock = lock_store(dirs)?;
+    let staging = sibling_path(prefix, "flora-restore");
+    if fs::exists(&staging)? {
+        fs::remove_dir_all(&staging)?;
+    }
+    let old_prefix = sibling_path(prefix, "flora-old");
+    if fs::exists(&old_prefix)? {
+        if fs::exists(prefix)? {
+            fs::remove_dir_all(&old_prefix)?;
+        } else {
+            // The restore was interrupted after moving the prefix away, so it is the only copy
+            debug!("Recovering prefix from {}", old_prefix.display());
+            fs::rename(&old_prefix, prefix)?;
+        }
+    }
+
+    let result = extract(dirs, snapshot, prefix, &staging)
+        .and_then(|()| replace_prefix(prefix, &staging, &old_prefix));
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+
+    result
+}
+
+/// Replaces a prefix with a staged one, moving the prefix back if that fails
+fn replace_prefix(prefix: &Path, staging: &Path, old_prefix: &Path) -> Result<(), FloraError> {
+    let has_prefix = fs::exists(prefix)?;
+    if has_prefix {
+        fs::rename(prefix, old_prefix)?;
+    }
+    if let Err(e) = fs::rename(staging, prefix) {
+        if has_prefix && let Err(rollback_error) = fs::rename(old_prefix, prefix) {
+            warn!(
+                "Unable to move prefix back from {}: {}",
+                old_prefix.display(),
+                rollback_error
+            );
+        }
+        return Err(e.into());
+    }
+    if has_prefix {
+        fs::remove_dir_all(old_prefix)?;
+    }
+
+    Ok(())
+}
+
+fn sibling_path(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = prefix.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}", suffix));
+
+    prefix.with_file_name(file_name)
+}
+
+fn extract(
+    dirs: &FloraDirs,
+    snapshot: &FloraSnapshot,
+    prefix: &Path,
+    destination: &Path,
+) -> Result<(), FloraError> {
+    fs::create_dir_all(destination)?;
+
+    for entry in &snapshot.entries {
+        let target = destination.join(&entry.path);
+
+        match entry.kind {
+            FloraSnapshotEntryKind::Dir => fs::create_dir_all(&target)?,
+            FloraSnapshotEntryKind::Symlink => {
+                let link = entry.target.as_ref().ok_or_else(|| {
+                    FloraError::SnapshotCorrupted(snapshot.id, entry.path.clone())
+                })?;
+                symlink(link, &target)?;
+            }
+            FloraSnapshotEntryKind::File => {
+                let hash = entry.hash.as_ref().ok_or_else(|| {
+                    FloraError::SnapshotCorrupted(snapshot.id, entry.path.clone())
+                })?;
+                let object = File::open(object_path(dirs, hash))
+                    .map_err(|_| FloraError::SnapshotCorrupted(snapshot.id, entry.path.clone()))?;
+
+                zstd::stream::copy_decode(object, File::create(&target)?)?;
+                fs::set_permissions(&target, fs::Permissions::from_mode(entry.mode))?;
+            }
+        }
+    }
+
+    // Directories may be read-only, so their permissions are set once they are filled
+    for entry in snapshot.entries.iter().rev() {
+        if entry.kind == FloraSnapshotEntryKind::Dir {
+            let target = destination.join(&entry.path);
+            fs::set_permissions(&target, fs::Permissions::from_mode(entry.mode))?;
+        }
+    }
+
+    prefix::relocate_prefix(destination, &snapshot.prefix, prefix)
+}
+
+/// Moves the snapshots of a seed to a new seed name
+pub(crate) fn rename_seed(
+    dirs: &FloraDirs,
+    seed_name: &str,
+    new_seed_name: &str,
+) -> Result<(), FloraError> {
+    let _lock = lock_store(dirs)?;
+    let snapshot_dir = seed_snapshot_dir(dirs, seed_name);
+    if fs::exists(&snapshot_dir)? {
+        debug!(
+            "Moving snapshots of seed {} to {}",
+            seed_name, new_seed_name
+        );
+        fs::rename(snapshot_dir, seed_snapshot_dir(dirs, new_seed_name))?;
+    }
+
+    Ok(())
+}
+
+/// Deletes a snapshot, then removes stored files no other snapshot uses
+pub(crate) fn delete(dirs: &FloraDirs, seed_name: &str, id: u32) -> Result<(), FloraError> {
+    let _lock = lock_store(dirs)?;
+    let manifest_path = manifest_path(dirs, seed_name, id);
+    if !fs::exists(&manifest_path)? {
+        return Err(FloraError::SnapshotNotFound(id));
+    }
+
+    debug!("Deleting snapshot manifest {}", manifest_path.display());
+    fs::remove_file(&manifest_path)?;
+
+    prune_objects(dirs)?;
+
+    Ok(())
+}
+
+/// Deletes all snapshots of a seed, then removes stored files no other snapshot uses
+///
+/// Returns the directory of the seed's snapshots, if it had any.
+pub(crate) fn delete_seed(
+    dirs: &FloraDirs,
+    seed_name: &str,
+) -> Result<Option<PathBuf>, FloraError> {
+    let _lock = lock_store(dirs)?;
+    let snapshot_dir = seed_snapshot_dir(dirs, seed_name);
+    if !fs::exists(&snapshot_dir)? {
+        return Ok(None);
+    }
+
+    debug!("Deleting snapshots of seed {}", seed_name);
+    fs::remove_dir_all(&snapshot_dir)?;
+    prune_objects(dirs)?;
+
+    Ok(Some(snapshot_dir))
+}
+
+/// Removes stored files that are not used by any snapshot, returning the space freed
+///
+/// The store must be locked.
+fn prune_objects(dirs: &FloraDirs) -> Result<u64, FloraError> {
+    let seeds_root = seeds_root(dirs);
+    let mut used_hashes = HashSet::new();
+
+    if fs::exists(&seeds_root)? {
+        for seed_dir in fs::read_dir(&seeds_root)? {
+            let seed_name = seed_dir?.file_name();
+            for snapshot in list(dirs, &seed_name.to_string_lossy())? {
+                used_hashes.extend(snapshot.entries.into_iter().filter_map(|entry| entry.hash));
+            }
+        }
+    }
+
+    let mut object_root = dirs.get_snapshot_root();
+    object_root.push("objects");
+    if !fs::exists(&object_root)? {
+        return Ok(0);
+    }
+
+    let mut freed = 0;
+    for entry in WalkDir::new(&object_root).min_depth(2).max_depth(2) {
+        let entry = entry.map_err(|e| FloraError::FileAccessError(e.into()))?;
+        let file_name = entry.file_name().to_string_lossy();
+        let Some(hash) = file_name.strip_suffix(".zst") else {
+            // Objects still being written by a snapshot in progress
+            continue;
+        };
+
+        if !used_hashes.contains(hash) {
+            debug!("Removing unused snapshot object {}", entry.path().display());
+            freed += entry.metadata().map_err(io::Error::from)?.len();
+            fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(freed)
+}
+
+impl FloraSnapshot {
+    /// Number of files in the snapshot
+    pub fn file_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == FloraSnapshotEntryKind::File)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::symlink, path::Path};
+
+    use walkdir::WalkDir;
+
+    use crate::{dirs::FloraDirs, snapshot};
+
+    fn object_count(dirs: &FloraDirs) -> usize {
+        WalkDir::new(dirs.get_snapshot_root().join("objects"))
+            .min_depth(2)
+            .into_iter()
+            .count()
+    }
+
+    fn write_prefix(prefix: &Path, content: &str) {
+        fs::create_dir_all(prefix.join("drive_c/windows")).unwrap();
+        fs::write(prefix.join("drive_c/windows/win.ini"), content).unwrap();
+        fs::write(prefix.join("drive_c/shared.dll"), "shared").unwrap();
+        let _ = symlink("../drive_c", prefix.join("c:"));
+    }
+
+    #[test]
+    fn restore_after_interruption() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().join("flora")).unwrap();
+        let prefix = root.path().join("prefix");
+        let old_prefix = root.path().join("prefix.flora-old");
+        let staging = root.path().join("prefix.flora-restore");
+        write_prefix(&prefix, "snapshot");
+        let snapshot = snapshot::create(&dirs, "seed", &prefix, None).unwrap();
+
+        // A stale copy of the prefix is left next to it
+        write_prefix(&old_prefix, "stale");
+        write_prefix(&prefix, "current");
+        snapshot::restore(&dirs, &snapshot, &prefix).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("drive_c/windows/win.ini")).unwrap(),
+            "snapshot"
+        );
+        assert!(!fs::exists(&old_prefix).unwrap());
+
+        // The prefix was moved away, but not replaced yet
+        fs::rename(&prefix, &old_prefix).unwrap();
+        let mut corrupted = snapshot.clone();
+        corrupted.entries.iter_mut().for_each(|entry| {
+            entry.hash = entry.hash.as_ref().map(|_| "0".repeat(64));
+        });
+        assert!(snapshot::restore(&dirs, &corrupted, &prefix).is_err());
+        assert!(!fs::exists(&staging).unwrap());
+        assert!(!fs::exists(&old_prefix).unwrap());
+        assert_eq!(
+            fs::read_to_string(prefix.join("drive_c/windows/win.ini")).unwrap(),
+            "snapshot"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().join("flora")).unwrap();
+        let prefix = root.path().join("prefix");
+
+        write_prefix(&prefix, "first");
+        let first = snapshot::create(&dirs, "seed", &prefix, Some("first")).unwrap();
+        write_prefix(&prefix, "second");
+        let second = snapshot::create(&dirs, "seed", &prefix, None).unwrap();
+        assert_eq!((first.id, second.id), (1, 2));
+        assert_eq!(first.file_count(), 2);
+        // Unchanged files are stored once
+        assert_eq!(object_count(&dirs), 3);
+
+        snapshot::restore(&dirs, &first, &prefix).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("drive_c/windows/win.ini")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            fs::read_link(prefix.join("c:")).unwrap(),
+            Path::new("../drive_c")
+        );
+
+        snapshot::delete(&dirs, "seed", first.id).unwrap();
+        assert_eq!(object_count(&dirs), 2);
+        assert!(snapshot::get(&dirs, "seed", first.id).is_err());
+
+        snapshot::restore(&dirs, &second, &prefix).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("drive_c/windows/win.ini")).unwrap(),
+            "second"
+        );
+    }
+
+    #[test]
+    fn ids_are_not_reused() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().join("flora")).unwrap();
+        let prefix = root.path().join("prefix");
+        write_prefix(&prefix, "content");
+
+        let first = snapshot::create(&dirs, "seed", &prefix, None).unwrap();
+        let second = snapshot::create(&dirs, "seed", &prefix, None).unwrap();
+        snapshot::delete(&dirs, "seed", second.id).unwrap();
+        let third = snapshot::create(&dirs, "seed", &prefix, None).unwrap();
+
+        assert_eq!((first.id, second.id, third.id), (1, 2, 3));
+        let ids: Vec<_> = snapshot::list(&dirs, "seed")
+            .unwrap()
+            .iter()
+            .map(|snapshot| snapshot.id)
+            .collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn delete_seed_snapshots() {
+        let root = tempfile::tempdir().unwrap();
+        let dirs = FloraDirs::new(root.path().join("flora")).unwrap();
+        let prefix = root.path().join("prefix");
+        let other_prefix = root.path().join("other");
+        write_prefix(&prefix, "seed");
+        write_prefix(&other_prefix, "other");
+
+        snapshot::create(&dirs, "seed", &prefix, None).unwrap();
+        snapshot::create(&dirs, "other", &other_prefix, None).unwrap();
+        assert_eq!(object_count(&dirs), 3);
+
+        assert!(snapshot::delete_seed(&dirs, "seed").unwrap().is_some());
+        assert!(snapshot::list(&dirs, "seed").unwrap().is_empty());
+        // Objects shared with the other seed's snapshot are kept
+        assert_eq!(object_count(&dirs), 2);
+        assert!(snapshot::delete_seed(&dirs, "seed").unwrap().is_none());
+    }
+}
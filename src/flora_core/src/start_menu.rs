@@ -1,4 +1,130 @@
+use std::path::{Path, PathBuf};
+
+use flora_icon::FloraLinkShowCommand;
+use log::debug;
+use walkdir::WalkDir;
+
+use crate::{
+    errors::FloraError,
+    seed::{FloraSeedApp, FloraSeedAppShortcut, FloraShowCommand},
+    winepath,
+};
+
 pub struct FloraSeedStartMenuItem {
     pub start_menu_name: String,
     pub start_menu_location: String,
 }
+
+/// Creates an app from a Start Menu shortcut, resolving the shortcut's target.
+///
+/// Shortcuts without a resolvable target (e.g. advertised shortcuts) are launched as-is.
+pub(crate) fn resolve_start_menu_app(
+    prefix: &Path,
+    menu_name: &str,
+    start_menu_location: String,
+) -> FloraSeedApp {
+    let lnk_path = winepath::windows_to_unix(prefix, &start_menu_location);
+
+    match flora_icon::read_lnk_target(&lnk_path) {
+        Ok(target) => {
+            debug!("Start Menu item {} targets {}", menu_name, target.target);
+
+            FloraSeedApp {
+                application_name: menu_name.to_string(),
+                application_location: target.target,
+                arguments: target
+                    .arguments
+                    .as_deref()
+                    .map(winepath::split_windows_arguments)
+                    .filter(|arguments| !arguments.is_empty()),
+                working_directory: target.working_directory,
+                shortcut: Some(FloraSeedAppShortcut {
+                    location: start_menu_location,
+                    icon_location: target.icon_location,
+                    icon_index: target.icon_index,
+                    show_command: Some(match target.show_command {
+                        FloraLinkShowCommand::Normal => FloraShowCommand::Normal,
+                        FloraLinkShowCommand::Maximized => FloraShowCommand::Maximized,
+                        FloraLinkShowCommand::Minimized => FloraShowCommand::Minimized,
+                    }),
+                }),
+                ..Default::default()
+            }
+        }
+        Err(e) => {
+            debug!("Unable to resolve Start Menu item {}: {}", menu_name, e);
+
+            FloraSeedApp {
+                application_name: menu_name.to_string(),
+                application_location: start_menu_location,
+                ..Default::default()
+            }
+        }
+    }
+}
+
+/// Returns the Start Menu directories of a prefix, the user's first
+fn start_menu_dirs(prefix: &Path, user: &str) -> [PathBuf; 2] {
+    [
+        prefix
+            .join("drive_c/users")
+            .join(user)
+            .join("AppData/Roaming/Microsoft/Windows/Start Menu"),
+        prefix.join("drive_c/ProgramData/Microsoft/Windows/Start Menu"),
+    ]
+}
+
+/// Finds the Windows path of a Start Menu shortcut by name
+pub(crate) fn get_start_menu_entry_location(
+    prefix: &Path,
+    user: &str,
+    menu_name: &str,
+) -> Result<String, FloraError> {
+    for start_menu_dir in start_menu_dirs(prefix, user) {
+        for entry in WalkDir::new(start_menu_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if let Some(file_name) = entry.path().file_name()
+                && file_name.eq_ignore_ascii_case(format!("{}.lnk", menu_name))
+            {
+                debug!("Found Start Menu item: {}", entry.path().display());
+                let winepath = winepath::unix_to_windows(prefix, entry.path());
+
+                debug!("Winepath: {}", winepath);
+                return Ok(winepath);
+            }
+        }
+    }
+
+    Err(FloraError::StartMenuLocationNotFound(menu_name.to_string()))
+}
+
+/// Lists the Start Menu shortcuts of a prefix
+pub(crate) fn list_start_menu_entries(
+    prefix: &Path,
+    user: &str,
+) -> Result<Vec<FloraSeedStartMenuItem>, FloraError> {
+    let mut start_menu_entries = Vec::new();
+
+    for start_menu_dir in start_menu_dirs(prefix, user) {
+        for entry in WalkDir::new(start_menu_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            if let Some(extension) = entry.path().extension()
+                && extension == "lnk"
+                && let Some(file_stem) = entry.path().file_stem()
+            {
+                debug!("Found Start Menu item: {}", entry.path().display());
+
+                start_menu_entries.push(FloraSeedStartMenuItem {
+                    start_menu_name: String::from(file_stem.to_string_lossy()),
+                    start_menu_location: winepath::unix_to_windows(prefix, entry.path()),
+                });
+            }
+        }
+    }
+
+    Ok(start_menu_entries)
+}
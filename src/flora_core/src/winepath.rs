@@ -1,6 +1,6 @@
 use std::path::{Component, Path, PathBuf};
 
-use typed_path::{Utf8WindowsComponent, Utf8WindowsPathBuf, Utf8WindowsPrefix};
+use typed_path::{Utf8WindowsComponent, Utf8WindowsPath, Utf8WindowsPathBuf, Utf8WindowsPrefix};
 
 pub fn windows_to_unix(wine_prefix: &Path, windows_path: &String) -> PathBuf {
     let windows_path = Utf8WindowsPathBuf::from(windows_path);
@@ -20,8 +20,74 @@ pub fn windows_to_unix(wine_prefix: &Path, windows_path: &String) -> PathBuf {
     unix_path
 }
 
-pub fn unix_to_windows(prefix: &PathBuf, unix_path: &Path) -> String {
-    let mut dosdevices = prefix.clone();
+/// Resolves a path that is either a Windows path with a drive letter or a Unix path
+pub fn resolve_path(wine_prefix: &Path, path: &str) -> PathBuf {
+    let windows_path = Utf8WindowsPath::new(path);
+
+    if let Some(Utf8WindowsComponent::Prefix(_)) = windows_path.components().next() {
+        windows_to_unix(wine_prefix, &String::from(path))
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+/// Splits a Windows command line into arguments, following the rules of CommandLineToArgvW
+pub fn split_windows_arguments(command_line: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut in_quotes = false;
+    let mut backslashes = 0;
+
+    let mut chars = command_line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                in_argument = true;
+            }
+            '"' => {
+                // 2n backslashes before a quote produce n backslashes,
+                // 2n+1 backslashes produce n backslashes and a literal quote
+                current.extend(std::iter::repeat_n('\\', backslashes / 2));
+                if backslashes % 2 == 1 {
+                    current.push('"');
+                } else if in_quotes && chars.peek() == Some(&'"') {
+                    // Doubled quote inside quotes is a literal quote
+                    current.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = !in_quotes;
+                }
+                backslashes = 0;
+                in_argument = true;
+            }
+            ' ' | '\t' if !in_quotes => {
+                current.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+                if in_argument {
+                    arguments.push(std::mem::take(&mut current));
+                    in_argument = false;
+                }
+            }
+            _ => {
+                current.extend(std::iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+                current.push(c);
+                in_argument = true;
+            }
+        }
+    }
+    current.extend(std::iter::repeat_n('\\', backslashes));
+    if in_argument {
+        arguments.push(current);
+    }
+
+    arguments
+}
+
+pub fn unix_to_windows(prefix: &Path, unix_path: &Path) -> String {
+    let mut dosdevices = prefix.to_path_buf();
     dosdevices.push("dosdevices");
 
     let mut windows_path = Utf8WindowsPathBuf::from("Z:\\");
@@ -90,7 +156,9 @@ pub fn unix_to_windows(prefix: &PathBuf, unix_path: &Path) -> String {
 mod tests {
     use std::path::PathBuf;
 
-    use crate::winepath::{unix_to_windows, windows_to_unix};
+    use crate::winepath::{
+        resolve_path, split_windows_arguments, unix_to_windows, windows_to_unix,
+    };
 
     #[test]
     fn unix_to_windows_root() {
@@ -143,4 +211,40 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn resolve_path_windows() {
+        let prefix = PathBuf::from("/wine/prefix");
+
+        assert_eq!(
+            resolve_path(&prefix, "C:\\Games\\Seed"),
+            PathBuf::from("/wine/prefix/dosdevices/c:/Games/Seed")
+        );
+    }
+
+    #[test]
+    fn resolve_path_unix() {
+        let prefix = PathBuf::from("/wine/prefix");
+
+        assert_eq!(
+            resolve_path(&prefix, "/home/seed/Games"),
+            PathBuf::from("/home/seed/Games")
+        );
+    }
+
+    #[test]
+    fn split_windows_arguments_test() {
+        assert_eq!(
+            split_windows_arguments(r#"-dx11 "C:\Program Files\Seed" /skip"#),
+            vec!["-dx11", "C:\\Program Files\\Seed", "/skip"]
+        );
+    }
+
+    #[test]
+    fn split_windows_arguments_escapes() {
+        assert_eq!(
+            split_windows_arguments(r#"a\\\"b "c\\" d"" "e""f""#),
+            vec!["a\\\"b", "c\\", "d", "e\"f"]
+        );
+    }
 }
@@ -4,19 +4,58 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use lnk::encoding::WINDOWS_1252;
+use lnk::{ShowCommand, encoding::WINDOWS_1252};
 use log::debug;
-use pelite::{FileMap, pe32, pe64, resources::FindError};
+use pelite::{
+    FileMap, pe32, pe64,
+    resources::{FindError, Name},
+};
 use thiserror::Error;
 use xdg_mime::SharedMimeInfo;
 
 pub enum FloraLink {
     LinuxExe(PathBuf),
-    WindowsIco(String),
+    WindowsIco(String, i32),
     WindowsExe(String),
     Other(PathBuf),
 }
 
+/// Window state requested by a Windows shortcut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloraLinkShowCommand {
+    Normal,
+    Maximized,
+    Minimized,
+}
+
+/// Target of a Windows shortcut, resolved from its ShellLink structure
+#[derive(Debug, Clone)]
+pub struct FloraLinkTarget {
+    /// Windows path of the link target
+    pub target: String,
+    /// Command line arguments passed to the target
+    pub arguments: Option<String>,
+    /// Windows path of the working directory
+    pub working_directory: Option<String>,
+    /// Windows path of the file containing the icon
+    pub icon_location: Option<String>,
+    /// Index of the icon inside the icon location
+    pub icon_index: i32,
+    pub show_command: FloraLinkShowCommand,
+}
+
+fn clean_lnk_string(value: &str) -> String {
+    // Clean up null values
+    value.trim_matches(char::from(0)).to_string()
+}
+
+fn non_empty_lnk_string(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(clean_lnk_string)
+        .filter(|value| !value.is_empty())
+}
+
 pub fn get_icon_name_from_path(lnk_location: &Path) -> Result<String, FloraLinkError> {
     let mime_db = SharedMimeInfo::new();
     let mut guess_builder = mime_db.guess_mime_type();
@@ -37,27 +76,52 @@ pub fn find_lnk_exe_location(lnk_location: &Path) -> Result<FloraLink, FloraLink
     if is_exe {
         Ok(FloraLink::LinuxExe(lnk_location.to_owned()))
     } else if let Ok(shortcut) = lnk::ShellLink::open(lnk_location, WINDOWS_1252) {
-        if let Some(icon_location) = shortcut.string_data().icon_location() {
+        if let Some(icon_location) = non_empty_lnk_string(shortcut.string_data().icon_location()) {
             Ok(FloraLink::WindowsIco(
-                icon_location
-                    .to_owned()
-                    .trim_matches(char::from(0)) // Clean up null values
-                    .to_string(),
+                icon_location,
+                *shortcut.header().icon_index(),
             ))
         } else {
-            Ok(FloraLink::WindowsExe(
-                shortcut
+            Ok(FloraLink::WindowsExe(clean_lnk_string(
+                &shortcut
                     .link_target()
-                    .ok_or(FloraLinkError::LinkNoTarget(lnk_location.to_path_buf()))?
-                    .trim_matches(char::from(0)) // Clean up null values
-                    .to_string(),
-            ))
+                    .ok_or(FloraLinkError::LinkNoTarget(lnk_location.to_path_buf()))?,
+            )))
         }
     } else {
         Ok(FloraLink::Other(lnk_location.to_owned()))
     }
 }
 
+/// Resolves the target, arguments, working directory and icon of a Windows shortcut
+pub fn read_lnk_target(lnk_location: &Path) -> Result<FloraLinkTarget, FloraLinkError> {
+    debug!("Reading lnk target: {}", lnk_location.to_string_lossy());
+
+    let shortcut = lnk::ShellLink::open(lnk_location, WINDOWS_1252)
+        .map_err(|_| FloraLinkError::LinkParseError(lnk_location.to_path_buf()))?;
+
+    let target = shortcut
+        .link_target()
+        .map(|target| clean_lnk_string(&target))
+        .filter(|target| !target.is_empty())
+        .ok_or(FloraLinkError::LinkNoTarget(lnk_location.to_path_buf()))?;
+
+    let string_data = shortcut.string_data();
+
+    Ok(FloraLinkTarget {
+        target,
+        arguments: non_empty_lnk_string(string_data.command_line_arguments()),
+        working_directory: non_empty_lnk_string(string_data.working_dir()),
+        icon_location: non_empty_lnk_string(string_data.icon_location()),
+        icon_index: *shortcut.header().icon_index(),
+        show_command: match shortcut.header().show_command() {
+            ShowCommand::ShowMaximized => FloraLinkShowCommand::Maximized,
+            ShowCommand::ShowMinNoActive => FloraLinkShowCommand::Minimized,
+            ShowCommand::ShowNormal => FloraLinkShowCommand::Normal,
+        },
+    })
+}
+
 pub fn extract_icon_from_ico(icon_path: &Path, ico_location: &Path) -> Result<(), FloraLinkError> {
     debug!("ICO location: {}", ico_location.to_string_lossy());
 
@@ -76,9 +140,32 @@ pub fn extract_icon_from_ico(icon_path: &Path, ico_location: &Path) -> Result<()
 
     Ok(())
 }
+/// Selects an icon group by index, with negative indices referring to a resource ID.
+/// Falls back to the first icon group if the index does not exist.
+fn select_icon<'a, T>(icons: impl Iterator<Item = (Name<'a>, T)>, icon_index: i32) -> Option<T> {
+    let mut icons: Vec<_> = icons.collect();
+
+    let idx = if icon_index < 0 {
+        let resource_id = icon_index.unsigned_abs();
+        icons
+            .iter()
+            .position(|(name, _)| matches!(name, Name::Id(id) if *id == resource_id))
+    } else {
+        Some(icon_index as usize).filter(|idx| *idx < icons.len())
+    }
+    .unwrap_or(0);
+
+    if idx < icons.len() {
+        Some(icons.swap_remove(idx).1)
+    } else {
+        None
+    }
+}
+
 pub fn extract_icon_from_exe(
     icon_path: &Path,
     exe_location: &Path,
+    icon_index: i32,
 ) -> Result<bool, FloraLinkError> {
     // Extract main icon from executable
 
@@ -89,19 +176,18 @@ pub fn extract_icon_from_exe(
             use pe64::Pe;
             // PE64 executable
 
-            // Get first icon resource on the EXE
-            let icon_group = pe64_exe
-                .resources()?
-                .icons()
-                .filter_map(|e| e.ok())
-                .next()
-                .ok_or(FloraLinkError::IconNotInExecutable(
-                    exe_location.to_path_buf(),
-                ))?;
+            // Get the requested icon resource on the EXE
+            let icon_group = select_icon(
+                pe64_exe.resources()?.icons().filter_map(|e| e.ok()),
+                icon_index,
+            )
+            .ok_or(FloraLinkError::IconNotInExecutable(
+                exe_location.to_path_buf(),
+            ))?;
 
             // Get ICO resource
             let mut ico_file = vec![];
-            icon_group.1.write(&mut ico_file)?;
+            icon_group.write(&mut ico_file)?;
 
             let ico_cursor = Cursor::new(ico_file);
 
@@ -126,19 +212,18 @@ pub fn extract_icon_from_exe(
             use pe32::Pe;
             // PE32 executable
 
-            // Get first icon resource on the EXE
-            let icon_group = pe32_exe
-                .resources()?
-                .icons()
-                .filter_map(|e| e.ok())
-                .next()
-                .ok_or(FloraLinkError::IconNotInExecutable(
-                    exe_location.to_path_buf(),
-                ))?;
+            // Get the requested icon resource on the EXE
+            let icon_group = select_icon(
+                pe32_exe.resources()?.icons().filter_map(|e| e.ok()),
+                icon_index,
+            )
+            .ok_or(FloraLinkError::IconNotInExecutable(
+                exe_location.to_path_buf(),
+            ))?;
 
             // Get ICO resource
             let mut ico_file = vec![];
-            icon_group.1.write(&mut ico_file)?;
+            icon_group.write(&mut ico_file)?;
 
             let ico_cursor = Cursor::new(ico_file);
 
@@ -165,6 +250,8 @@ pub enum FloraLinkError {
     ExeResourceError(#[from] FindError),
     #[error("Unable to find target of link {0}")]
     LinkNoTarget(PathBuf),
+    #[error("Unable to parse link {0}")]
+    LinkParseError(PathBuf),
     #[error("Unable to find icon in executable {0}")]
     IconNotInExecutable(PathBuf),
     #[error("Unable to find icon in ico file {0}")]
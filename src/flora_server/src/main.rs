@@ -1,10 +1,5 @@
-use directories::BaseDirs;
-use flora_core::{errors::FloraError, manager::FloraManager};
-use tokio::{
-    net::UnixListener,
-    signal::{self},
-};
-use tokio_stream::wrappers::UnixListenerStream;
+use flora_core::manager::FloraManager;
+use tokio::signal::{self};
 use tonic::transport::Server;
 use tower_http::trace::TraceLayer;
 
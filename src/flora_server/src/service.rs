@@ -1,20 +1,34 @@
+use std::path::{Path, PathBuf};
+
 use flora_core::{
+    archive::FloraSeedArchiveManifest,
     errors::FloraError,
+    gc::{FloraGarbage, FloraGarbageKind},
     manager::FloraManager,
-    seed::{FloraProtonSeed, FloraSeed, FloraSeedApp, FloraSeedType, FloraWineSeed},
+    process::{FloraProcess, FloraRunResult, FloraWaitMode},
+    seed::{
+        FloraDllOverrideMode, FloraGamescopeFilter, FloraGamescopeScaler, FloraGamescopeSettings,
+        FloraGamescopeWindowMode, FloraPrefixArch, FloraProtonSeed, FloraSeed, FloraSeedApp,
+        FloraSeedType, FloraWindowsVersion, FloraWineSeed,
+    },
+    snapshot::FloraSnapshot,
 };
-use tokio::fs::File;
+use tokio::task;
 use tonic::{Request, Response, Status};
 
 use crate::proto::{
     self, CreateAppRequest, CreateAppResponse, DeleteAppRequest, DeleteAppResponse,
-    DeleteEnvironmentRequest, DeleteEnvironmentResponse, GenerateAppFromStartMenuRequest,
-    GenerateAppFromStartMenuResponse, ListAppItem, ListEnvironmentItem, ListEnvironmentRequest,
-    ListEnvironmentResponse, ListSeedItem, ListSeedRequest, ListSeedResponse, RenameAppRequest,
-    RenameAppResponse, RunAppRequest, RunAppResponse, RunConfigRequest, RunConfigResponse,
-    RunExecutableRequest, RunExecutableResponse, RunTricksRequest, RunTricksResponse,
+    DeleteDllOverrideRequest, DeleteDllOverrideResponse, DeleteEnvironmentRequest,
+    DeleteEnvironmentResponse, GenerateAppFromStartMenuRequest, GenerateAppFromStartMenuResponse,
+    KillSeedRequest, KillSeedResponse, ListAppItem, ListDllOverrideItem, ListDllOverridesRequest,
+    ListDllOverridesResponse, ListEnvironmentItem, ListEnvironmentRequest, ListEnvironmentResponse,
+    ListProcessesRequest, ListProcessesResponse, ListSeedItem, ListSeedRequest, ListSeedResponse,
+    ProcessItem, RenameAppRequest, RenameAppResponse, RunAppRequest, RunAppResponse,
+    RunConfigRequest, RunConfigResponse, RunExecutableRequest, RunExecutableResponse, RunResult,
+    RunTricksRequest, RunTricksResponse,
     SeedType::{Proton, Unspecified},
-    SetEnvironmentRequest, SetEnvironmentResponse, UpdateAppRequest, UpdateAppResponse,
+    SetDllOverrideRequest, SetDllOverrideResponse, SetEnvironmentRequest, SetEnvironmentResponse,
+    UpdateAppRequest, UpdateAppResponse,
     flora_manager_service_server::FloraManagerService,
 };
 
@@ -37,6 +51,14 @@ fn invalid_error_custom(error: String) -> Status {
     Status::new(tonic::Code::InvalidArgument, error)
 }
 
+fn wait_mode(wait: i32) -> FloraWaitMode {
+    match proto::WaitMode::try_from(wait) {
+        Ok(proto::WaitMode::Process) => FloraWaitMode::Process,
+        Ok(proto::WaitMode::Prefix) => FloraWaitMode::Prefix,
+        Ok(proto::WaitMode::NoWait) | Err(_) => FloraWaitMode::NoWait,
+    }
+}
+
 fn internal_error(error: FloraError) -> Status {
     internal_error_custom(error.to_string())
 }
@@ -45,6 +67,200 @@ fn internal_error_custom(error: String) -> Status {
     Status::new(tonic::Code::Internal, error)
 }
 
+impl From<FloraDllOverrideMode> for proto::DllOverrideMode {
+    fn from(mode: FloraDllOverrideMode) -> Self {
+        match mode {
+            FloraDllOverrideMode::Native => Self::Native,
+            FloraDllOverrideMode::Builtin => Self::Builtin,
+            FloraDllOverrideMode::NativeBuiltin => Self::NativeBuiltin,
+            FloraDllOverrideMode::BuiltinNative => Self::BuiltinNative,
+            FloraDllOverrideMode::Disabled => Self::Disabled,
+        }
+    }
+}
+
+fn dll_override_mode(mode: i32) -> Result<FloraDllOverrideMode, Status> {
+    match proto::DllOverrideMode::try_from(mode) {
+        Ok(proto::DllOverrideMode::Native) => Ok(FloraDllOverrideMode::Native),
+        Ok(proto::DllOverrideMode::Builtin) => Ok(FloraDllOverrideMode::Builtin),
+        Ok(proto::DllOverrideMode::NativeBuiltin) => Ok(FloraDllOverrideMode::NativeBuiltin),
+        Ok(proto::DllOverrideMode::BuiltinNative) => Ok(FloraDllOverrideMode::BuiltinNative),
+        Ok(proto::DllOverrideMode::Disabled) => Ok(FloraDllOverrideMode::Disabled),
+        Ok(proto::DllOverrideMode::Unspecified) | Err(_) => Err(invalid_error_custom(
+            String::from("DLL override mode is not valid"),
+        )),
+    }
+}
+
+impl From<FloraSeedArchiveManifest> for proto::SeedArchiveManifest {
+    fn from(manifest: FloraSeedArchiveManifest) -> Self {
+        Self {
+            flora_version: manifest.flora_version,
+            seed_name: manifest.seed_name,
+            seed_type: manifest.seed_type,
+            runtime: manifest.runtime,
+            apps: manifest.apps,
+            created_at: manifest.created_at,
+            has_prefix: manifest.has_prefix,
+        }
+    }
+}
+
+impl From<&FloraSnapshot> for proto::SnapshotItem {
+    fn from(snapshot: &FloraSnapshot) -> Self {
+        Self {
+            id: snapshot.id,
+            label: snapshot.label.clone(),
+            created_at: snapshot.created_at,
+            size: snapshot.size,
+            file_count: snapshot.file_count() as u64,
+        }
+    }
+}
+
+fn windows_version(version: &str) -> Result<FloraWindowsVersion, Status> {
+    version.parse().map_err(invalid_error_custom)
+}
+
+fn prefix_arch(prefix_arch: &str) -> Result<FloraPrefixArch, Status> {
+    prefix_arch.parse().map_err(invalid_error_custom)
+}
+
+impl From<&FloraGamescopeSettings> for proto::GamescopeSettings {
+    fn from(settings: &FloraGamescopeSettings) -> Self {
+        let window_mode = match settings.window_mode {
+            Some(FloraGamescopeWindowMode::Windowed) => proto::GamescopeWindowMode::Windowed,
+            Some(FloraGamescopeWindowMode::Fullscreen) => proto::GamescopeWindowMode::Fullscreen,
+            Some(FloraGamescopeWindowMode::Borderless) => proto::GamescopeWindowMode::Borderless,
+            None => proto::GamescopeWindowMode::Unspecified,
+        };
+        let filter = match settings.filter {
+            Some(FloraGamescopeFilter::Linear) => proto::GamescopeFilter::Linear,
+            Some(FloraGamescopeFilter::Nearest) => proto::GamescopeFilter::Nearest,
+            Some(FloraGamescopeFilter::Fsr) => proto::GamescopeFilter::Fsr,
+            Some(FloraGamescopeFilter::Nis) => proto::GamescopeFilter::Nis,
+            Some(FloraGamescopeFilter::Pixel) => proto::GamescopeFilter::Pixel,
+            None => proto::GamescopeFilter::Unspecified,
+        };
+        let scaler = match settings.scaler {
+            Some(FloraGamescopeScaler::Auto) => proto::GamescopeScaler::Auto,
+            Some(FloraGamescopeScaler::Integer) => proto::GamescopeScaler::Integer,
+            Some(FloraGamescopeScaler::Fit) => proto::GamescopeScaler::Fit,
+            Some(FloraGamescopeScaler::Fill) => proto::GamescopeScaler::Fill,
+            Some(FloraGamescopeScaler::Stretch) => proto::GamescopeScaler::Stretch,
+            None => proto::GamescopeScaler::Unspecified,
+        };
+
+        Self {
+            output_width: settings.output_width,
+            output_height: settings.output_height,
+            nested_width: settings.nested_width,
+            nested_height: settings.nested_height,
+            refresh_rate: settings.refresh_rate,
+            window_mode: window_mode.into(),
+            filter: filter.into(),
+            scaler: scaler.into(),
+            hdr: settings.hdr,
+            extra_args: settings.extra_args.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<proto::GamescopeSettings> for FloraGamescopeSettings {
+    fn from(settings: proto::GamescopeSettings) -> Self {
+        let window_mode = match settings.window_mode() {
+            proto::GamescopeWindowMode::Windowed => Some(FloraGamescopeWindowMode::Windowed),
+            proto::GamescopeWindowMode::Fullscreen => Some(FloraGamescopeWindowMode::Fullscreen),
+            proto::GamescopeWindowMode::Borderless => Some(FloraGamescopeWindowMode::Borderless),
+            proto::GamescopeWindowMode::Unspecified => None,
+        };
+        let filter = match settings.filter() {
+            proto::GamescopeFilter::Linear => Some(FloraGamescopeFilter::Linear),
+            proto::GamescopeFilter::Nearest => Some(FloraGamescopeFilter::Nearest),
+            proto::GamescopeFilter::Fsr => Some(FloraGamescopeFilter::Fsr),
+            proto::GamescopeFilter::Nis => Some(FloraGamescopeFilter::Nis),
+            proto::GamescopeFilter::Pixel => Some(FloraGamescopeFilter::Pixel),
+            proto::GamescopeFilter::Unspecified => None,
+        };
+        let scaler = match settings.scaler() {
+            proto::GamescopeScaler::Auto => Some(FloraGamescopeScaler::Auto),
+            proto::GamescopeScaler::Integer => Some(FloraGamescopeScaler::Integer),
+            proto::GamescopeScaler::Fit => Some(FloraGamescopeScaler::Fit),
+            proto::GamescopeScaler::Fill => Some(FloraGamescopeScaler::Fill),
+            proto::GamescopeScaler::Stretch => Some(FloraGamescopeScaler::Stretch),
+            proto::GamescopeScaler::Unspecified => None,
+        };
+
+        Self {
+            output_width: settings.output_width,
+            output_height: settings.output_height,
+            nested_width: settings.nested_width,
+            nested_height: settings.nested_height,
+            refresh_rate: settings.refresh_rate,
+            window_mode,
+            filter,
+            scaler,
+            hdr: settings.hdr,
+            extra_args: (!settings.extra_args.is_empty()).then_some(settings.extra_args),
+        }
+    }
+}
+
+impl From<&FloraSeedApp> for ListAppItem {
+    fn from(app: &FloraSeedApp) -> Self {
+        Self {
+            app_name: app.application_name.clone(),
+            app_location: app.application_location.clone(),
+            arguments: app.arguments.clone().unwrap_or_default(),
+            working_directory: app.working_directory.clone(),
+            env: app.get_env().into_iter().collect(),
+            shortcut_location: app.shortcut.as_ref().map(|s| s.location.clone()),
+        }
+    }
+}
+
+impl From<&FloraProcess> for ProcessItem {
+    fn from(process: &FloraProcess) -> Self {
+        Self {
+            pid: process.pid,
+            seed_name: process.seed_name.clone(),
+            app_name: process.app_name.clone(),
+            command: process.command.clone(),
+            started_at: process.started_at,
+        }
+    }
+}
+
+impl From<&FloraGarbage> for proto::GarbageItem {
+    fn from(garbage: &FloraGarbage) -> Self {
+        let kind = match garbage.kind {
+            FloraGarbageKind::DesktopEntry => proto::GarbageKind::DesktopEntry,
+            FloraGarbageKind::Icon => proto::GarbageKind::Icon,
+            FloraGarbageKind::Prefix => proto::GarbageKind::Prefix,
+            FloraGarbageKind::Log => proto::GarbageKind::Log,
+            FloraGarbageKind::Snapshots => proto::GarbageKind::Snapshots,
+        };
+
+        Self {
+            kind: kind.into(),
+            path: garbage.path.to_string_lossy().to_string(),
+            size: garbage.size,
+            removed: garbage.removed,
+        }
+    }
+}
+
+impl From<FloraRunResult> for RunResult {
+    fn from(result: FloraRunResult) -> Self {
+        Self {
+            pid: result.pid,
+            exit_code: result.exit_code,
+            signal: result.signal,
+            duration_ms: result.duration.map(|d| d.as_millis() as u64),
+        }
+    }
+}
+
 #[tonic::async_trait]
 impl FloraManagerService for FloraManagerServiceImpl {
     async fn create_seed(
@@ -52,17 +268,26 @@ impl FloraManagerService for FloraManagerServiceImpl {
         request: Request<proto::CreateSeedRequest>,
     ) -> Result<Response<proto::CreateSeedResponse>, Status> {
         let req = request.into_inner();
+        let windows_version = req
+            .windows_version
+            .as_deref()
+            .map(windows_version)
+            .transpose()?;
+        let prefix_arch = req.prefix_arch.as_deref().map(prefix_arch).transpose()?;
         let mut new_seed = FloraSeed::default();
         new_seed.seed_type = match proto::SeedType::try_from(req.seed_type) {
             Ok(proto::SeedType::Wine) => FloraSeedType::Wine(FloraWineSeed {
                 wine_prefix: req.prefix,
                 wine_runtime: req.runtime,
+                windows_version,
+                prefix_arch,
             }),
             Ok(Proton) => FloraSeedType::Proton(FloraProtonSeed {
                 proton_prefix: req.prefix,
                 proton_runtime: req.runtime,
                 game_id: req.game_id,
                 store: req.game_store,
+                windows_version,
             }),
             Ok(Unspecified) | Err(_) => {
                 return Err(invalid_error_custom(String::from("Incorrect seed type")));
@@ -72,6 +297,10 @@ impl FloraManagerService for FloraManagerServiceImpl {
         self.manager
             .create_seed(&req.seed_name, &new_seed)
             .map_err(invalid_error)?;
+        if req.init {
+            task::block_in_place(|| self.manager.seed_init(&req.seed_name, true))
+                .map_err(internal_error)?;
+        }
 
         Ok(Response::new(proto::CreateSeedResponse {}))
     }
@@ -85,6 +314,10 @@ impl FloraManagerService for FloraManagerServiceImpl {
             .manager
             .get_seed(&req.seed_name)
             .map_err(invalid_error)?;
+        let windows_version = self
+            .manager
+            .get_windows_version(&req.seed_name, None)
+            .map_err(invalid_error)?;
         let res = proto::GetSeedResponse {
             seed_name: req.seed_name,
             seed_type: match seed.seed_type {
@@ -92,15 +325,20 @@ impl FloraManagerService for FloraManagerServiceImpl {
                 FloraSeedType::Proton(_) => String::from("proton"),
                 FloraSeedType::None => String::from("none"),
             },
-            launcher_command: seed.settings.clone().map(|s| s.launcher_command).flatten(),
-            apps: seed
-                .get_apps()
-                .iter()
-                .map(|a| proto::ListAppItem {
-                    app_name: a.application_name.clone(),
-                    app_location: a.application_location.clone(),
-                })
-                .collect(),
+            launcher_command: seed.settings.clone().and_then(|s| s.launcher_command),
+            apps: seed.get_apps().iter().map(ListAppItem::from).collect(),
+            wrapper_preset: seed.settings.clone().and_then(|s| s.wrapper_preset),
+            wrappers: seed
+                .settings
+                .clone()
+                .and_then(|s| s.wrappers)
+                .unwrap_or_default(),
+            gamescope: seed
+                .settings
+                .as_ref()
+                .and_then(|s| s.gamescope.as_ref())
+                .map(proto::GamescopeSettings::from),
+            windows_version,
         };
 
         Ok(Response::new(res))
@@ -115,6 +353,20 @@ impl FloraManagerService for FloraManagerServiceImpl {
             .manager
             .get_seed(&req.seed_name)
             .map_err(invalid_error)?;
+        let new_windows_version = req
+            .windows_version
+            .as_deref()
+            .map(|version| {
+                (!version.is_empty())
+                    .then(|| windows_version(version))
+                    .transpose()
+            })
+            .transpose()?;
+        let new_prefix_arch = req
+            .prefix_arch
+            .as_deref()
+            .map(|arch| (!arch.is_empty()).then(|| prefix_arch(arch)).transpose())
+            .transpose()?;
         match seed.seed_type {
             FloraSeedType::Wine(ref mut wine_settings) => {
                 if req.prefix.is_some() {
@@ -123,6 +375,12 @@ impl FloraManagerService for FloraManagerServiceImpl {
                 if req.runtime.is_some() {
                     wine_settings.wine_runtime = req.runtime.clone();
                 }
+                if let Some(new_windows_version) = new_windows_version {
+                    wine_settings.windows_version = new_windows_version;
+                }
+                if let Some(new_prefix_arch) = new_prefix_arch {
+                    wine_settings.prefix_arch = new_prefix_arch;
+                }
             }
             FloraSeedType::Proton(ref mut proton_settings) => {
                 if req.prefix.is_some() {
@@ -137,6 +395,9 @@ impl FloraManagerService for FloraManagerServiceImpl {
                 if req.game_store.is_some() {
                     proton_settings.store = req.game_store.clone();
                 }
+                if let Some(new_windows_version) = new_windows_version {
+                    proton_settings.windows_version = new_windows_version;
+                }
             }
             FloraSeedType::None => {
                 return Err(Status::new(
@@ -145,6 +406,21 @@ impl FloraManagerService for FloraManagerServiceImpl {
                 ));
             }
         };
+        let settings = seed.settings.get_or_insert_default();
+        if let Some(launcher_command) = req.launcher_command {
+            settings.launcher_command = (!launcher_command.is_empty()).then_some(launcher_command);
+        }
+        if let Some(wrapper_preset) = req.wrapper_preset {
+            settings.wrapper_preset = (!wrapper_preset.is_empty()).then_some(wrapper_preset);
+        }
+        if let Some(wrappers) = req.wrappers {
+            settings.wrappers = (!wrappers.wrappers.is_empty()).then_some(wrappers.wrappers);
+        }
+        if req.clear_gamescope {
+            settings.gamescope = None;
+        } else if let Some(gamescope) = req.gamescope {
+            settings.gamescope = Some(FloraGamescopeSettings::from(gamescope));
+        }
         self.manager
             .update_seed(&req.seed_name, &seed)
             .map_err(internal_error)?;
@@ -179,15 +455,8 @@ impl FloraManagerService for FloraManagerServiceImpl {
                     ),
                     _ => unimplemented!(),
                 };
-                let launcher_command = seed.settings.clone().map(|e| e.launcher_command).flatten();
-                let apps: Vec<_> = seed
-                    .get_apps()
-                    .iter()
-                    .map(|e| ListAppItem {
-                        app_name: e.application_name.clone(),
-                        app_location: e.application_location.clone(),
-                    })
-                    .collect();
+                let launcher_command = seed.settings.clone().and_then(|e| e.launcher_command);
+                let apps: Vec<_> = seed.get_apps().iter().map(ListAppItem::from).collect();
                 let env: Vec<_> = seed
                     .get_env()
                     .iter()
@@ -199,13 +468,13 @@ impl FloraManagerService for FloraManagerServiceImpl {
                 Ok(ListSeedItem {
                     seed_name: e.seed_name.clone(),
                     seed_type: e.seed_type.clone(),
-                    prefix: prefix,
-                    runtime: runtime,
-                    game_id: game_id,
-                    game_store: game_store,
-                    launcher_command: launcher_command,
-                    apps: apps,
-                    env: env,
+                    prefix,
+                    runtime,
+                    game_id,
+                    game_store,
+                    launcher_command,
+                    apps,
+                    env,
                 })
             })
             .collect();
@@ -217,11 +486,114 @@ impl FloraManagerService for FloraManagerServiceImpl {
         request: Request<proto::DeleteSeedRequest>,
     ) -> Result<Response<proto::DeleteSeedResponse>, Status> {
         let req = request.into_inner();
-        self.manager
-            .delete_seed(&req.seed_name)
-            .map_err(internal_error)?;
+        if !req.purge {
+            if req.delete_prefix {
+                return Err(invalid_error_custom(String::from(
+                    "delete_prefix requires purge",
+                )));
+            }
+            self.manager
+                .delete_seed(&req.seed_name)
+                .map_err(internal_error)?;
+
+            return Ok(Response::new(proto::DeleteSeedResponse::default()));
+        }
+
+        // Deleting a prefix can take a while
+        let report =
+            task::block_in_place(|| self.manager.purge_seed(&req.seed_name, req.delete_prefix))
+                .map_err(invalid_error)?;
+        let path_string = |path: &PathBuf| path.to_string_lossy().to_string();
+
+        Ok(Response::new(proto::DeleteSeedResponse {
+            desktop_entries: report.desktop_entries.iter().map(path_string).collect(),
+            icons: report.icons.iter().map(path_string).collect(),
+            log: report.log.as_ref().map(path_string),
+            prefix: report.prefix.as_ref().map(path_string),
+            snapshots: report.snapshots.as_ref().map(path_string),
+        }))
+    }
+
+    async fn clone_seed(
+        &self,
+        request: Request<proto::CloneSeedRequest>,
+    ) -> Result<Response<proto::CloneSeedResponse>, Status> {
+        let req = request.into_inner();
+
+        // Copying a prefix can take a while
+        task::block_in_place(|| {
+            self.manager
+                .clone_seed(&req.seed_name, &req.new_seed_name, req.prefix.as_deref())
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::CloneSeedResponse {}))
+    }
+
+    async fn rename_seed(
+        &self,
+        request: Request<proto::RenameSeedRequest>,
+    ) -> Result<Response<proto::RenameSeedResponse>, Status> {
+        let req = request.into_inner();
+
+        // Relocating paths in a large prefix can take a while
+        task::block_in_place(|| {
+            self.manager
+                .rename_seed(&req.seed_name, &req.new_seed_name, req.rename_prefix)
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::RenameSeedResponse {}))
+    }
+
+    async fn export_seed(
+        &self,
+        request: Request<proto::ExportSeedRequest>,
+    ) -> Result<Response<proto::ExportSeedResponse>, Status> {
+        let req = request.into_inner();
+
+        // Compressing a prefix can take a while
+        let manifest = task::block_in_place(|| {
+            self.manager
+                .export_seed(&req.seed_name, Path::new(&req.output), req.exclude_caches)
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::ExportSeedResponse {
+            manifest: Some(manifest.into()),
+        }))
+    }
+
+    async fn import_seed(
+        &self,
+        request: Request<proto::ImportSeedRequest>,
+    ) -> Result<Response<proto::ImportSeedResponse>, Status> {
+        let req = request.into_inner();
+
+        let manifest = task::block_in_place(|| {
+            self.manager
+                .import_seed(Path::new(&req.archive), req.new_seed_name.as_deref())
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::ImportSeedResponse {
+            manifest: Some(manifest.into()),
+        }))
+    }
 
-        Ok(Response::new(proto::DeleteSeedResponse {}))
+    async fn init_seed(
+        &self,
+        request: Request<proto::InitSeedRequest>,
+    ) -> Result<Response<proto::InitSeedResponse>, Status> {
+        let req = request.into_inner();
+
+        // Blocks until the prefix is ready
+        let result = task::block_in_place(|| self.manager.seed_init(&req.seed_name, true))
+            .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::InitSeedResponse {
+            result: Some(result.into()),
+        }))
     }
 
     async fn create_app(
@@ -236,12 +608,26 @@ impl FloraManagerService for FloraManagerServiceImpl {
         seed.add_app(FloraSeedApp {
             application_name: req.app_name,
             application_location: req.app_location,
-            category: None,
+            arguments: if req.arguments.is_empty() {
+                None
+            } else {
+                Some(req.arguments)
+            },
+            working_directory: req.working_directory,
+            env: if req.env.is_empty() {
+                None
+            } else {
+                Some(req.env.into_iter().collect())
+            },
+            ..Default::default()
         })
         .map_err(invalid_error)?;
         self.manager
             .update_seed(&req.seed_name, &seed)
             .map_err(internal_error)?;
+        self.manager
+            .sync_desktop_entries_on_app_change(&req.seed_name)
+            .map_err(internal_error)?;
 
         Ok(Response::new(CreateAppResponse {}))
     }
@@ -258,6 +644,23 @@ impl FloraManagerService for FloraManagerServiceImpl {
 
         let mut app = seed.get_app(&req.app_name).map_err(invalid_error)?;
         app.application_location = req.app_location;
+        if let Some(arguments) = req.arguments {
+            app.arguments = if arguments.arguments.is_empty() {
+                None
+            } else {
+                Some(arguments.arguments)
+            };
+        }
+        if let Some(working_directory) = req.working_directory {
+            app.working_directory = if working_directory.is_empty() {
+                None
+            } else {
+                Some(working_directory)
+            };
+        }
+        for (env_name, env_value) in req.env.iter() {
+            app.update_env(env_name, env_value);
+        }
         seed.update_app(&req.app_name, app).map_err(invalid_error)?;
 
         self.manager
@@ -284,6 +687,9 @@ impl FloraManagerService for FloraManagerServiceImpl {
         self.manager
             .update_seed(&req.seed_name, &seed)
             .map_err(internal_error)?;
+        self.manager
+            .sync_desktop_entries_on_app_change(&req.seed_name)
+            .map_err(internal_error)?;
 
         Ok(Response::new(RenameAppResponse {}))
     }
@@ -303,10 +709,41 @@ impl FloraManagerService for FloraManagerServiceImpl {
         self.manager
             .update_seed(&req.seed_name, &seed)
             .map_err(internal_error)?;
+        self.manager
+            .sync_desktop_entries_on_app_change(&req.seed_name)
+            .map_err(internal_error)?;
 
         Ok(Response::new(DeleteAppResponse {}))
     }
 
+    async fn generate_menu(
+        &self,
+        request: Request<proto::GenerateMenuRequest>,
+    ) -> Result<Response<proto::GenerateMenuResponse>, Status> {
+        let req = request.into_inner();
+        if req.sync && req.app_name.is_some() {
+            return Err(invalid_error_custom(String::from(
+                "app_name cannot be combined with sync",
+            )));
+        }
+
+        // Extracting icons can take a while
+        let removed = task::block_in_place(|| {
+            if req.sync {
+                self.manager.sync_desktop_entries(req.seed_name.as_deref())
+            } else {
+                self.manager
+                    .create_desktop_entries(req.seed_name.as_deref(), req.app_name.as_deref())
+                    .map(|_| Vec::new())
+            }
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::GenerateMenuResponse {
+            removed: removed.iter().map(proto::GarbageItem::from).collect(),
+        }))
+    }
+
     async fn generate_app_from_start_menu(
         &self,
         request: Request<GenerateAppFromStartMenuRequest>,
@@ -381,17 +818,104 @@ impl FloraManagerService for FloraManagerServiceImpl {
         Ok(Response::new(DeleteEnvironmentResponse {}))
     }
 
+    async fn list_dll_overrides(
+        &self,
+        request: Request<ListDllOverridesRequest>,
+    ) -> Result<Response<ListDllOverridesResponse>, Status> {
+        let req = request.into_inner();
+        let seed = self
+            .manager
+            .get_seed(&req.seed_name)
+            .map_err(invalid_error)?;
+
+        let mut dll_overrides = seed.get_dll_overrides();
+        if let Some(app_name) = &req.app_name {
+            let app = seed.get_app(app_name).map_err(invalid_error)?;
+            dll_overrides.extend(app.get_dll_overrides());
+        }
+
+        let items: Vec<_> = dll_overrides
+            .into_iter()
+            .map(|(dll_name, mode)| ListDllOverrideItem {
+                dll_name,
+                mode: proto::DllOverrideMode::from(mode).into(),
+            })
+            .collect();
+
+        Ok(Response::new(ListDllOverridesResponse { items }))
+    }
+
+    async fn set_dll_override(
+        &self,
+        request: Request<SetDllOverrideRequest>,
+    ) -> Result<Response<SetDllOverrideResponse>, Status> {
+        let req = request.into_inner();
+        let mut seed = self
+            .manager
+            .get_seed(&req.seed_name)
+            .map_err(invalid_error)?;
+        let mode = dll_override_mode(req.mode)?;
+
+        if let Some(app_name) = &req.app_name {
+            let mut app = seed.get_app(app_name).map_err(invalid_error)?;
+            app.update_dll_override(&req.dll_name, mode)
+                .map_err(invalid_error)?;
+            seed.update_app(app_name, app).map_err(invalid_error)?;
+        } else {
+            seed.update_dll_override(&req.dll_name, mode)
+                .map_err(invalid_error)?;
+        }
+
+        self.manager
+            .update_seed(&req.seed_name, &seed)
+            .map_err(internal_error)?;
+
+        Ok(Response::new(SetDllOverrideResponse {}))
+    }
+
+    async fn delete_dll_override(
+        &self,
+        request: Request<DeleteDllOverrideRequest>,
+    ) -> Result<Response<DeleteDllOverrideResponse>, Status> {
+        let req = request.into_inner();
+        let mut seed = self
+            .manager
+            .get_seed(&req.seed_name)
+            .map_err(invalid_error)?;
+
+        if let Some(app_name) = &req.app_name {
+            let mut app = seed.get_app(app_name).map_err(invalid_error)?;
+            app.delete_dll_override(&req.dll_name)
+                .map_err(invalid_error)?;
+            seed.update_app(app_name, app).map_err(invalid_error)?;
+        } else {
+            seed.delete_dll_override(&req.dll_name)
+                .map_err(invalid_error)?;
+        }
+
+        self.manager
+            .update_seed(&req.seed_name, &seed)
+            .map_err(internal_error)?;
+
+        Ok(Response::new(DeleteDllOverrideResponse {}))
+    }
+
     async fn run_config(
         &self,
         request: Request<RunConfigRequest>,
     ) -> Result<Response<RunConfigResponse>, Status> {
         let req = request.into_inner();
 
-        self.manager
-            .seed_config(&req.seed_name, &None, true, false)
-            .map_err(invalid_error)?;
+        // Waiting blocks until the process exits
+        let result = task::block_in_place(|| {
+            self.manager
+                .seed_config(&req.seed_name, &None, true, wait_mode(req.wait))
+        })
+        .map_err(invalid_error)?;
 
-        Ok(Response::new(RunConfigResponse {}))
+        Ok(Response::new(RunConfigResponse {
+            result: Some(result.into()),
+        }))
     }
 
     async fn run_tricks(
@@ -400,11 +924,16 @@ impl FloraManagerService for FloraManagerServiceImpl {
     ) -> Result<Response<RunTricksResponse>, Status> {
         let req = request.into_inner();
 
-        self.manager
-            .seed_tricks(&req.seed_name, &None, true, false)
-            .map_err(invalid_error)?;
+        // Waiting blocks until the process exits
+        let result = task::block_in_place(|| {
+            self.manager
+                .seed_tricks(&req.seed_name, &None, true, wait_mode(req.wait))
+        })
+        .map_err(invalid_error)?;
 
-        Ok(Response::new(RunTricksResponse {}))
+        Ok(Response::new(RunTricksResponse {
+            result: Some(result.into()),
+        }))
     }
 
     async fn run_executable(
@@ -417,22 +946,185 @@ impl FloraManagerService for FloraManagerServiceImpl {
             .ok_or(invalid_error_custom(String::from("Invalid command line")))?;
         let args: Vec<_> = command_param.iter().map(AsRef::as_ref).collect();
 
-        self.manager
-            .seed_run_executable(&req.seed_name, &args, true, false)
-            .map_err(internal_error)?;
+        // Waiting blocks until the process exits
+        let result = task::block_in_place(|| {
+            self.manager
+                .seed_run_executable(&req.seed_name, &args, true, wait_mode(req.wait))
+        })
+        .map_err(internal_error)?;
 
-        Ok(Response::new(RunExecutableResponse {}))
+        Ok(Response::new(RunExecutableResponse {
+            result: Some(result.into()),
+        }))
     }
 
     async fn run_app(
         &self,
         request: Request<RunAppRequest>,
     ) -> Result<Response<RunAppResponse>, Status> {
+        let req = request.into_inner();
+        // Waiting blocks until the process exits
+        let result = task::block_in_place(|| {
+            self.manager.seed_run_app(
+                &req.seed_name,
+                &Some(&req.app_name),
+                true,
+                wait_mode(req.wait),
+            )
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(RunAppResponse {
+            result: Some(result.into()),
+        }))
+    }
+
+    async fn list_snapshots(
+        &self,
+        request: Request<proto::ListSnapshotsRequest>,
+    ) -> Result<Response<proto::ListSnapshotsResponse>, Status> {
+        let req = request.into_inner();
+        let snapshots = self
+            .manager
+            .list_snapshots(&req.seed_name)
+            .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::ListSnapshotsResponse {
+            snapshots: snapshots.iter().map(proto::SnapshotItem::from).collect(),
+        }))
+    }
+
+    async fn create_snapshot(
+        &self,
+        request: Request<proto::CreateSnapshotRequest>,
+    ) -> Result<Response<proto::CreateSnapshotResponse>, Status> {
+        let req = request.into_inner();
+
+        // Compressing a prefix can take a while
+        let snapshot = task::block_in_place(|| {
+            self.manager
+                .create_snapshot(&req.seed_name, req.label.as_deref())
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::CreateSnapshotResponse {
+            snapshot: Some(proto::SnapshotItem::from(&snapshot)),
+        }))
+    }
+
+    async fn restore_snapshot(
+        &self,
+        request: Request<proto::RestoreSnapshotRequest>,
+    ) -> Result<Response<proto::RestoreSnapshotResponse>, Status> {
+        let req = request.into_inner();
+        task::block_in_place(|| self.manager.restore_snapshot(&req.seed_name, req.id))
+            .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::RestoreSnapshotResponse {}))
+    }
+
+    async fn delete_snapshot(
+        &self,
+        request: Request<proto::DeleteSnapshotRequest>,
+    ) -> Result<Response<proto::DeleteSnapshotResponse>, Status> {
         let req = request.into_inner();
         self.manager
-            .seed_run_app(&req.seed_name, &Some(&req.app_name), true, false)
+            .delete_snapshot(&req.seed_name, req.id)
+            .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::DeleteSnapshotResponse {}))
+    }
+
+    async fn list_processes(
+        &self,
+        _: Request<ListProcessesRequest>,
+    ) -> Result<Response<ListProcessesResponse>, Status> {
+        let processes = self.manager.list_processes().map_err(internal_error)?;
+
+        Ok(Response::new(ListProcessesResponse {
+            processes: processes.iter().map(ProcessItem::from).collect(),
+        }))
+    }
+
+    async fn kill_seed(
+        &self,
+        request: Request<KillSeedRequest>,
+    ) -> Result<Response<KillSeedResponse>, Status> {
+        let req = request.into_inner();
+        let processes = self
+            .manager
+            .kill_seed(&req.seed_name)
+            .map_err(invalid_error)?;
+
+        Ok(Response::new(KillSeedResponse {
+            processes: processes.iter().map(ProcessItem::from).collect(),
+        }))
+    }
+
+    async fn open_file(
+        &self,
+        request: Request<proto::OpenFileRequest>,
+    ) -> Result<Response<proto::OpenFileResponse>, Status> {
+        let req = request.into_inner();
+        // Waiting blocks until the process exits
+        let result = task::block_in_place(|| {
+            self.manager.seed_open_file(
+                &req.seed_name,
+                Path::new(&req.file),
+                true,
+                wait_mode(req.wait),
+            )
+        })
+        .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::OpenFileResponse {
+            result: Some(result.into()),
+        }))
+    }
+
+    async fn register_mime_handler(
+        &self,
+        request: Request<proto::RegisterMimeHandlerRequest>,
+    ) -> Result<Response<proto::RegisterMimeHandlerResponse>, Status> {
+        let req = request.into_inner();
+        let desktop_entry = self
+            .manager
+            .register_mime_handler(&req.seed_name)
+            .map_err(invalid_error)?;
+
+        Ok(Response::new(proto::RegisterMimeHandlerResponse {
+            desktop_entry: desktop_entry.to_string_lossy().to_string(),
+        }))
+    }
+
+    async fn find_open_seed(
+        &self,
+        request: Request<proto::FindOpenSeedRequest>,
+    ) -> Result<Response<proto::FindOpenSeedResponse>, Status> {
+        let req = request.into_inner();
+        let seed_name = self
+            .manager
+            .find_open_seed(Path::new(&req.file))
             .map_err(invalid_error)?;
 
-        Ok(Response::new(RunAppResponse {}))
+        Ok(Response::new(proto::FindOpenSeedResponse { seed_name }))
+    }
+
+    async fn collect_garbage(
+        &self,
+        request: Request<proto::CollectGarbageRequest>,
+    ) -> Result<Response<proto::CollectGarbageResponse>, Status> {
+        let req = request.into_inner();
+
+        // Sizing and deleting prefixes can take a while
+        let garbage = task::block_in_place(|| {
+            self.manager
+                .collect_garbage(req.dry_run, req.remove_prefixes)
+        })
+        .map_err(internal_error)?;
+
+        Ok(Response::new(proto::CollectGarbageResponse {
+            items: garbage.iter().map(proto::GarbageItem::from).collect(),
+        }))
     }
 }